@@ -0,0 +1,810 @@
+//! Analytic approximations of American option prices, fast enough to reprice on every tick
+//!
+//! * Barone-Adesi and Whaley (1987), quadratic approximation of the early exercise premium. The critical spot is
+//!   found by Newton's method
+//! * Bjerksund and Stensland (2002), a two step flat exercise boundary, closed form apart from the bivariate normal.
+//!   Puts use the put-call transformation P(S, K, r, q) = C(K, S, q, r)
+//!
+//! Both come in scalar versions, computed in f64, and batch versions computed eight at a time in f32x8.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_::{call_f32x8, ncd_f32x8, npd_f32x8, price_f32x8};
+use crate::normal::{bivariate_ncd, bivariate_ncd_f32x8, european, ncd_f64, npd_f64, sign};
+use crate::solver::brent;
+use bytemuck::cast;
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// Which American approximation to use
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AmericanApproximation {
+    BaroneAdesiWhaley,
+    BjerksundStensland,
+}
+
+const NEWTON_TOLERANCE: f64 = 1.0e-7;
+const NEWTON_MAX_ITER: usize = 100;
+/// Bisection steps of the batch implied vol, enough to pin the vol in [0, 5] to 1e-8
+const BISECTION_STEPS: usize = 30;
+const MIN_VOL: f32 = 0.0001;
+const MAX_VOL: f32 = 5.0;
+
+/// Barone-Adesi Whaley with phi = 1 for a call and -1 for a put
+fn baw(phi: f64, s: f64, k: f64, t: f64, r: f64, v: f64, q: f64) -> f64 {
+    let b = r - q;
+    if phi > 0.0 && b >= r {
+        // A call on an asset without dividends is never exercised early
+        return european(phi, s, k, t, r, v, q);
+    }
+    let v2 = v * v;
+    let n = 2.0 * b / v2;
+    // 2r / (v^2 (1 - exp(-rT))), which tends to 2 / (v^2 T) as r goes to 0
+    let m_over_k = if (r * t).abs() < 1.0e-10 {
+        2.0 / (v2 * t)
+    } else {
+        2.0 * r / (v2 * (1.0 - (-r * t).exp()))
+    };
+    let exponent = |m: f64| (-(n - 1.0) + phi * ((n - 1.0) * (n - 1.0) + 4.0 * m).sqrt()) / 2.0;
+    let q_t = exponent(m_over_k);
+    let carry = ((b - r) * t).exp();
+    let sd = v * t.sqrt();
+
+    // Seed from the perpetual boundary, then Newton on
+    // phi (S* - K) = european(S*) + phi (1 - exp((b - r) T) N(phi d1(S*))) S* / q
+    let q_inf = exponent(2.0 * r / v2);
+    let s_inf = k / (1.0 - 1.0 / q_inf);
+    let h = -(b * t + phi * 2.0 * sd) * k / (s_inf - k);
+    let mut critical = k + (s_inf - k) * (1.0 - h.exp());
+    for _ in 0..NEWTON_MAX_ITER {
+        let d1 = ((critical / k).ln() + (b + v2 / 2.0) * t) / sd;
+        let exercise = 1.0 - carry * ncd_f64(phi * d1);
+        let f = phi * (critical - k)
+            - european(phi, critical, k, t, r, v, q)
+            - phi * exercise * critical / q_t;
+        if (f / k).abs() < NEWTON_TOLERANCE {
+            break;
+        }
+        let slope = phi * exercise * (1.0 - 1.0 / q_t) + carry * npd_f64(d1) / (sd * q_t);
+        // Damped so the critical price stays positive
+        critical = (critical - f / slope).max(critical / 2.0);
+    }
+    if phi * (critical - s) <= 0.0 {
+        return phi * (s - k);
+    }
+    let d1 = ((critical / k).ln() + (b + v2 / 2.0) * t) / sd;
+    let premium = phi * critical / q_t * (1.0 - carry * ncd_f64(phi * d1));
+    european(phi, s, k, t, r, v, q) + premium * (s / critical).powf(q_t)
+}
+
+/// Bjerksund-Stensland 2002 call, the put follows from the put-call transformation
+fn bs2002_call(s: f64, k: f64, t: f64, r: f64, v: f64, q: f64) -> f64 {
+    let b = r - q;
+    if b >= r {
+        return european(1.0, s, k, t, r, v, q);
+    }
+    let v2 = v * v;
+    let beta = (0.5 - b / v2) + ((b / v2 - 0.5).powi(2) + 2.0 * r / v2).sqrt();
+    let b_inf = beta / (beta - 1.0) * k;
+    let b0 = k.max(r / (r - b) * k);
+    let t1 = (5.0f64.sqrt() - 1.0) / 2.0 * t;
+    // The boundary lies between b0 and b_inf, h > 0 can only come from a negative carry swamping a tiny vol
+    let boundary = |t: f64| {
+        let h = -(b * t + 2.0 * v * t.sqrt()) * k * k / ((b_inf - b0) * b0);
+        b0 + (b_inf - b0) * (1.0 - h.min(0.0).exp())
+    };
+    let (i1, i2) = (boundary(t1), boundary(t));
+    if s >= i2 {
+        return s - k;
+    }
+    let alpha1 = (i1 - k) * i1.powf(-beta);
+    let alpha2 = (i2 - k) * i2.powf(-beta);
+
+    let lambda = |gamma: f64| -r + gamma * b + 0.5 * gamma * (gamma - 1.0) * v2;
+    let kappa = |gamma: f64| 2.0 * b / v2 + 2.0 * gamma - 1.0;
+    let phi = |t: f64, gamma: f64, h: f64, i: f64| {
+        let sd = v * t.sqrt();
+        let d = -((s / h).ln() + (b + (gamma - 0.5) * v2) * t) / sd;
+        (lambda(gamma) * t).exp()
+            * s.powf(gamma)
+            * (ncd_f64(d) - (i / s).powf(kappa(gamma)) * ncd_f64(d - 2.0 * (i / s).ln() / sd))
+    };
+    let rho = (t1 / t).sqrt();
+    let psi = |gamma: f64, h: f64| {
+        let drift = (b + (gamma - 0.5) * v2) * t;
+        let drift1 = (b + (gamma - 0.5) * v2) * t1;
+        let (sd, sd1) = (v * t.sqrt(), v * t1.sqrt());
+        let e1 = ((s / i1).ln() + drift1) / sd1;
+        let e2 = ((i2 * i2 / (s * i1)).ln() + drift1) / sd1;
+        let e3 = ((s / i1).ln() - drift1) / sd1;
+        let e4 = ((i2 * i2 / (s * i1)).ln() - drift1) / sd1;
+        let f1 = ((s / h).ln() + drift) / sd;
+        let f2 = ((i2 * i2 / (s * h)).ln() + drift) / sd;
+        let f3 = ((i1 * i1 / (s * h)).ln() + drift) / sd;
+        let f4 = ((s * i1 * i1 / (h * i2 * i2)).ln() + drift) / sd;
+        let kappa = kappa(gamma);
+        (lambda(gamma) * t).exp()
+            * s.powf(gamma)
+            * (bivariate_ncd(-e1, -f1, rho)
+                - (i2 / s).powf(kappa) * bivariate_ncd(-e2, -f2, rho)
+                - (i1 / s).powf(kappa) * bivariate_ncd(-e3, -f3, -rho)
+                + (i1 / i2).powf(kappa) * bivariate_ncd(-e4, -f4, -rho))
+    };
+    alpha2 * s.powf(beta) - alpha2 * phi(t1, beta, i2, i2) + phi(t1, 1.0, i2, i2)
+        - phi(t1, 1.0, i1, i2)
+        - k * phi(t1, 0.0, i2, i2)
+        + k * phi(t1, 0.0, i1, i2)
+        + alpha1 * phi(t1, beta, i1, i2)
+        - alpha1 * psi(beta, i1)
+        + psi(1.0, i1)
+        - psi(1.0, k)
+        - k * psi(0.0, i1)
+        + k * psi(0.0, k)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn approximation_price(
+    approximation: AmericanApproximation,
+    option_dir: OptionDir,
+    s: f64,
+    k: f64,
+    t: f64,
+    r: f64,
+    v: f64,
+    q: f64,
+) -> f64 {
+    let phi = sign(option_dir);
+    let price = match (approximation, option_dir) {
+        (AmericanApproximation::BaroneAdesiWhaley, _) => baw(phi, s, k, t, r, v, q),
+        (AmericanApproximation::BjerksundStensland, OptionDir::CALL) => {
+            bs2002_call(s, k, t, r, v, q)
+        }
+        (AmericanApproximation::BjerksundStensland, OptionDir::PUT) => {
+            bs2002_call(k, s, t, q, v, r)
+        }
+    };
+    // Both approximations overflow for vanishing vols, an American option is worth at least the European and
+    // the intrinsic value
+    let floor = european(phi, s, k, t, r, v, q).max(phi * (s - k));
+    if price.is_finite() {
+        price.max(floor)
+    } else {
+        floor
+    }
+}
+
+/// Barone-Adesi Whaley American option price
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn baw_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    approximation_price(
+        AmericanApproximation::BaroneAdesiWhaley,
+        option_dir,
+        spot as f64,
+        strike as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    ) as f32
+}
+
+/// Bjerksund-Stensland 2002 American option price
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn bjerksund_stensland_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    approximation_price(
+        AmericanApproximation::BjerksundStensland,
+        option_dir,
+        spot as f64,
+        strike as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    ) as f32
+}
+
+/// Implied vol of an American option price under one of the approximations.
+/// Returns NaN when no vol in [0.0001, 5] reproduces the price
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn american_implied_vol(
+    approximation: AmericanApproximation,
+    option_dir: OptionDir,
+    price: f32,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+) -> f32 {
+    brent(
+        |v| {
+            approximation_price(
+                approximation,
+                option_dir,
+                spot as f64,
+                strike as f64,
+                years_to_expiry as f64,
+                risk_free_rate as f64,
+                v,
+                dividend_yield as f64,
+            ) - price as f64
+        },
+        MIN_VOL as f64,
+        MAX_VOL as f64,
+        1.0e-8,
+        100,
+    )
+    .map_or(f32::NAN, |v| v as f32)
+}
+
+/// x^y for positive x
+fn pow_f32x8(x: f32x8, y: f32x8) -> f32x8 {
+    (y * x.ln()).exp()
+}
+
+/// Barone-Adesi Whaley eight at a time, the Newton iteration runs until every lane has converged
+fn baw_f32x8(
+    option_dir: OptionDir,
+    s: f32x8,
+    k: f32x8,
+    t: f32x8,
+    r: f32x8,
+    v: f32x8,
+    q: f32x8,
+) -> f32x8 {
+    let phi = f32x8::splat(match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    });
+    let european = |s: f32x8| price_f32x8(option_dir, s, k, t, r, v, q);
+    let b = r - q;
+    let v2 = v * v;
+    let n = 2.0 * b / v2;
+    let rt = r * t;
+    let m_over_k = rt
+        .abs()
+        .cmp_lt(f32x8::splat(1.0e-6))
+        .blend(2.0 / (v2 * t), 2.0 * r / (v2 * (1.0 - (-rt).exp())));
+    let exponent = |m: f32x8| (-(n - 1.0) + phi * ((n - 1.0) * (n - 1.0) + 4.0 * m).sqrt()) * 0.5;
+    let q_t = exponent(m_over_k);
+    let carry = ((b - r) * t).exp();
+    let sd = v * t.sqrt();
+
+    // Calls on an asset without dividends are never exercised early and have no finite critical price, so those
+    // lanes are left out of the iteration and take the European price at the end
+    let european_lanes = match option_dir {
+        OptionDir::CALL => b.cmp_ge(r),
+        OptionDir::PUT => f32x8::ZERO,
+    };
+    let q_inf = exponent(2.0 * r / v2);
+    let s_inf = k / (1.0 - 1.0 / q_inf);
+    let h = -(b * t + phi * 2.0 * sd) * k / (s_inf - k);
+    let mut critical = european_lanes.blend(k, k + (s_inf - k) * (1.0 - h.exp()));
+    for _ in 0..NEWTON_MAX_ITER {
+        let d1 = ((critical / k).ln() + (b + v2 * 0.5) * t) / sd;
+        let exercise = 1.0 - carry * ncd_f32x8(phi * d1);
+        let f = phi * (critical - k) - european(critical) - phi * exercise * critical / q_t;
+        let converged = (f / k).abs().cmp_lt(f32x8::splat(1.0e-6)) | european_lanes;
+        if converged.all() {
+            break;
+        }
+        let slope = phi * exercise * (1.0 - 1.0 / q_t) + carry * npd_f32x8(d1) / (sd * q_t);
+        critical = converged.blend(critical, (critical - f / slope).max(critical * 0.5));
+    }
+    let d1 = ((critical / k).ln() + (b + v2 * 0.5) * t) / sd;
+    let premium = phi * critical / q_t * (1.0 - carry * ncd_f32x8(phi * d1));
+    let price = european(s) + premium * pow_f32x8(s / critical, q_t);
+    let price = (phi * (critical - s))
+        .cmp_le(f32x8::ZERO)
+        .blend(phi * (s - k), price);
+    european_lanes.blend(european(s), price)
+}
+
+/// Bjerksund-Stensland 2002 call eight at a time
+fn bs2002_call_f32x8(s: f32x8, k: f32x8, t: f32x8, r: f32x8, v: f32x8, q: f32x8) -> f32x8 {
+    let b = r - q;
+    let v2 = v * v;
+    let beta = (0.5 - b / v2) + ((b / v2 - 0.5) * (b / v2 - 0.5) + 2.0 * r / v2).sqrt();
+    let b_inf = beta / (beta - 1.0) * k;
+    let b0 = k.max(r / (r - b) * k);
+    let t1 = t * ((5.0f32.sqrt() - 1.0) / 2.0);
+    let boundary = |t: f32x8| {
+        let h = -(b * t + 2.0 * v * t.sqrt()) * k * k / ((b_inf - b0) * b0);
+        b0 + (b_inf - b0) * (1.0 - h.min(f32x8::ZERO).exp())
+    };
+    let (i1, i2) = (boundary(t1), boundary(t));
+    let alpha1 = (i1 - k) * pow_f32x8(i1, -beta);
+    let alpha2 = (i2 - k) * pow_f32x8(i2, -beta);
+
+    let lambda = |gamma: f32x8| -r + gamma * b + 0.5 * gamma * (gamma - 1.0) * v2;
+    let kappa = |gamma: f32x8| 2.0 * b / v2 + 2.0 * gamma - 1.0;
+    let phi = |t: f32x8, gamma: f32x8, h: f32x8, i: f32x8| {
+        let sd = v * t.sqrt();
+        let d = -((s / h).ln() + (b + (gamma - 0.5) * v2) * t) / sd;
+        (lambda(gamma) * t).exp()
+            * pow_f32x8(s, gamma)
+            * (ncd_f32x8(d)
+                - pow_f32x8(i / s, kappa(gamma)) * ncd_f32x8(d - 2.0 * (i / s).ln() / sd))
+    };
+    // t1 is a fixed fraction of t so the correlation is a constant
+    let rho = ((5.0f32.sqrt() - 1.0) / 2.0).sqrt();
+    let psi = |gamma: f32x8, h: f32x8| {
+        let drift = (b + (gamma - 0.5) * v2) * t;
+        let drift1 = (b + (gamma - 0.5) * v2) * t1;
+        let (sd, sd1) = (v * t.sqrt(), v * t1.sqrt());
+        let e1 = ((s / i1).ln() + drift1) / sd1;
+        let e2 = ((i2 * i2 / (s * i1)).ln() + drift1) / sd1;
+        let e3 = ((s / i1).ln() - drift1) / sd1;
+        let e4 = ((i2 * i2 / (s * i1)).ln() - drift1) / sd1;
+        let f1 = ((s / h).ln() + drift) / sd;
+        let f2 = ((i2 * i2 / (s * h)).ln() + drift) / sd;
+        let f3 = ((i1 * i1 / (s * h)).ln() + drift) / sd;
+        let f4 = ((s * i1 * i1 / (h * i2 * i2)).ln() + drift) / sd;
+        let kappa = kappa(gamma);
+        (lambda(gamma) * t).exp()
+            * pow_f32x8(s, gamma)
+            * (bivariate_ncd_f32x8(-e1, -f1, rho)
+                - pow_f32x8(i2 / s, kappa) * bivariate_ncd_f32x8(-e2, -f2, rho)
+                - pow_f32x8(i1 / s, kappa) * bivariate_ncd_f32x8(-e3, -f3, -rho)
+                + pow_f32x8(i1 / i2, kappa) * bivariate_ncd_f32x8(-e4, -f4, -rho))
+    };
+    let (zero, one) = (f32x8::ZERO, f32x8::ONE);
+    let price = alpha2 * pow_f32x8(s, beta) - alpha2 * phi(t1, beta, i2, i2) + phi(t1, one, i2, i2)
+        - phi(t1, one, i1, i2)
+        - k * phi(t1, zero, i2, i2)
+        + k * phi(t1, zero, i1, i2)
+        + alpha1 * phi(t1, beta, i1, i2)
+        - alpha1 * psi(beta, i1)
+        + psi(one, i1)
+        - psi(one, k)
+        - k * psi(zero, i1)
+        + k * psi(zero, k);
+    let price = s.cmp_ge(i2).blend(s - k, price);
+    b.cmp_ge(r).blend(call_f32x8(s, k, t, r, v, q), price)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn approximation_f32x8(
+    approximation: AmericanApproximation,
+    option_dir: OptionDir,
+    s: f32x8,
+    k: f32x8,
+    t: f32x8,
+    r: f32x8,
+    v: f32x8,
+    q: f32x8,
+) -> f32x8 {
+    let price = match (approximation, option_dir) {
+        (AmericanApproximation::BaroneAdesiWhaley, _) => baw_f32x8(option_dir, s, k, t, r, v, q),
+        (AmericanApproximation::BjerksundStensland, OptionDir::CALL) => {
+            bs2002_call_f32x8(s, k, t, r, v, q)
+        }
+        (AmericanApproximation::BjerksundStensland, OptionDir::PUT) => {
+            bs2002_call_f32x8(k, s, t, q, v, r)
+        }
+    };
+    let intrinsic = match option_dir {
+        OptionDir::CALL => s - k,
+        OptionDir::PUT => k - s,
+    };
+    let floor = price_f32x8(option_dir, s, k, t, r, v, q).max(intrinsic);
+    price.is_finite().blend(price.max(floor), floor)
+}
+
+/// Price arrays eight at a time, the results are at the same index as the inputs
+#[allow(clippy::too_many_arguments)]
+fn batch_price(
+    approximation: AmericanApproximation,
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let spot = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let strike = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let years_to_expiry = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let risk_free_rate = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let volatility = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let dividend_yield = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let price: [f32; 8] = cast(approximation_f32x8(
+            approximation,
+            option_dir,
+            spot,
+            strike,
+            years_to_expiry,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+        ));
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Barone-Adesi Whaley American call pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn baw_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        AmericanApproximation::BaroneAdesiWhaley,
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Barone-Adesi Whaley American put pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn baw_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        AmericanApproximation::BaroneAdesiWhaley,
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Bjerksund-Stensland 2002 American call pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn bjerksund_stensland_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        AmericanApproximation::BjerksundStensland,
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Bjerksund-Stensland 2002 American put pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn bjerksund_stensland_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        AmericanApproximation::BjerksundStensland,
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Implied vols of American option prices eight at a time, by bisection since the approximations have no
+/// cheap vega. The results are at the same index as the inputs, NaN where the price is out of reach of vols in
+/// [0.0001, 5]
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn american_implied_vols(
+    approximation: AmericanApproximation,
+    option_dir: OptionDir,
+    price: &[f32],
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let price = f32x8::from(&price[i..std::cmp::min(max_idx, i + 8)]);
+        let spot = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let strike = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let years_to_expiry = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let risk_free_rate = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let dividend_yield = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let value = |v: f32x8| {
+            approximation_f32x8(
+                approximation,
+                option_dir,
+                spot,
+                strike,
+                years_to_expiry,
+                risk_free_rate,
+                v,
+                dividend_yield,
+            )
+        };
+        let (mut low, mut high) = (f32x8::splat(MIN_VOL), f32x8::splat(MAX_VOL));
+        let attainable = value(low).cmp_le(price) & value(high).cmp_ge(price);
+        for _ in 0..BISECTION_STEPS {
+            let mid = (low + high) * 0.5;
+            let above = value(mid).cmp_gt(price);
+            high = above.blend(mid, high);
+            low = above.blend(low, mid);
+        }
+        let vol: [f32; 8] = cast(attainable.blend((low + high) * 0.5, f32x8::splat(f32::NAN)));
+        res.extend(&vol);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+    use crate::pde::{pde_price, EarlyExercise, Exercise, PdeParams};
+
+    const PDE: PdeParams = PdeParams {
+        space_steps: 800,
+        time_steps: 400,
+        rannacher_steps: 2,
+        concentration: 0.1,
+        early_exercise: EarlyExercise::BrennanSchwartz,
+    };
+
+    #[test]
+    fn reference_values() {
+        // Haug, The Complete Guide to Option Pricing Formulas, Barone-Adesi Whaley calls with b = 0
+        for (&spot, &expected) in [90.0, 100.0, 110.0]
+            .iter()
+            .zip([0.0206, 1.8771, 10.0089].iter())
+        {
+            let call = baw_price(OptionDir::CALL, spot, 100.0, 0.1, 0.1, 0.15, 0.1);
+            assert!((call - expected).abs() < 0.003);
+        }
+        // Haug's Bjerksund-Stensland 1993 example gives 5.2704, the 2002 two step boundary improves on it
+        // while staying below the true American value
+        let call = bjerksund_stensland_price(OptionDir::CALL, 42.0, 40.0, 0.75, 0.04, 0.35, 0.08);
+        let pde = pde_price(
+            OptionDir::CALL,
+            Exercise::American,
+            None,
+            42.0,
+            40.0,
+            0.75,
+            0.04,
+            0.35,
+            0.08,
+            &PDE,
+        )
+        .pv;
+        assert!(call > 5.2704 && call < pde);
+    }
+
+    #[test]
+    fn against_pde() {
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            for &strike in [80.0, 100.0, 120.0].iter() {
+                let pde = pde_price(
+                    dir,
+                    Exercise::American,
+                    None,
+                    100.0,
+                    strike,
+                    1.0,
+                    0.06,
+                    0.3,
+                    0.04,
+                    &PDE,
+                )
+                .pv;
+                let baw = baw_price(dir, 100.0, strike, 1.0, 0.06, 0.3, 0.04);
+                let bs = bjerksund_stensland_price(dir, 100.0, strike, 1.0, 0.06, 0.3, 0.04);
+                assert!((baw - pde).abs() < 0.1);
+                assert!((bs - pde).abs() < 0.1);
+                // Bjerksund-Stensland is a lower bound, being the value of a particular exercise strategy
+                assert!(bs <= pde + 0.001);
+                assert!(bs >= bs_price(dir, 100.0, strike, 1.0, 0.06, 0.3, 0.04) - 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn no_early_exercise_is_european() {
+        let european = bs_price(OptionDir::CALL, 100.0, 110.0, 0.5, 0.05, 0.2, 0.0);
+        assert!(
+            (baw_price(OptionDir::CALL, 100.0, 110.0, 0.5, 0.05, 0.2, 0.0) - european).abs()
+                < 0.001
+        );
+        assert!(
+            (bjerksund_stensland_price(OptionDir::CALL, 100.0, 110.0, 0.5, 0.05, 0.2, 0.0)
+                - european)
+                .abs()
+                < 0.001
+        );
+        // Deep in the money puts are exercised at once
+        assert_eq!(
+            baw_price(OptionDir::PUT, 50.0, 100.0, 0.5, 0.05, 0.2, 0.0),
+            50.0
+        );
+        assert_eq!(
+            bjerksund_stensland_price(OptionDir::PUT, 50.0, 100.0, 0.5, 0.05, 0.2, 0.0),
+            50.0
+        );
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        let spot = [
+            80.0, 90.0, 95.0, 100.0, 105.0, 110.0, 120.0, 140.0, 60.0, 100.0,
+        ];
+        let strike = [100.0; 10];
+        let years_to_expiry = [0.25, 0.5, 1.0, 2.0, 0.1, 0.75, 1.5, 0.5, 3.0, 1.0];
+        let risk_free_rate = [0.05, 0.03, 0.08, 0.01, 0.05, 0.0, 0.06, 0.04, 0.05, 0.05];
+        let volatility = [0.2, 0.3, 0.25, 0.4, 0.15, 0.2, 0.35, 0.5, 0.3, 0.2];
+        let dividend_yield = [0.03, 0.0, 0.05, 0.02, 0.1, 0.04, 0.0, 0.06, 0.02, 0.05];
+        type Batch = fn(&[f32], &[f32], &[f32], &[f32], &[f32], &[f32]) -> Vec<f32>;
+        let batch: [(Batch, AmericanApproximation, OptionDir); 4] = [
+            (
+                baw_call,
+                AmericanApproximation::BaroneAdesiWhaley,
+                OptionDir::CALL,
+            ),
+            (
+                baw_put,
+                AmericanApproximation::BaroneAdesiWhaley,
+                OptionDir::PUT,
+            ),
+            (
+                bjerksund_stensland_call,
+                AmericanApproximation::BjerksundStensland,
+                OptionDir::CALL,
+            ),
+            (
+                bjerksund_stensland_put,
+                AmericanApproximation::BjerksundStensland,
+                OptionDir::PUT,
+            ),
+        ];
+        for (f, approximation, dir) in batch.iter() {
+            let prices = f(
+                &spot,
+                &strike,
+                &years_to_expiry,
+                &risk_free_rate,
+                &volatility,
+                &dividend_yield,
+            );
+            assert_eq!(prices.len(), spot.len());
+            for i in 0..spot.len() {
+                let scalar = approximation_price(
+                    *approximation,
+                    *dir,
+                    spot[i] as f64,
+                    strike[i] as f64,
+                    years_to_expiry[i] as f64,
+                    risk_free_rate[i] as f64,
+                    volatility[i] as f64,
+                    dividend_yield[i] as f64,
+                ) as f32;
+                assert!((prices[i] - scalar).abs() < 0.002);
+            }
+        }
+    }
+
+    #[test]
+    fn implied_vol_round_trip() {
+        let strike = [80.0, 90.0, 100.0, 110.0, 120.0, 100.0, 100.0, 100.0, 100.0];
+        let n = strike.len();
+        let (spot, years_to_expiry, risk_free_rate, dividend_yield) =
+            (vec![100.0; n], vec![0.5; n], vec![0.05; n], vec![0.02; n]);
+        for &approximation in [
+            AmericanApproximation::BaroneAdesiWhaley,
+            AmericanApproximation::BjerksundStensland,
+        ]
+        .iter()
+        {
+            for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+                let mut price: Vec<f32> = strike
+                    .iter()
+                    .map(|&k| {
+                        approximation_price(
+                            approximation,
+                            dir,
+                            100.0,
+                            k as f64,
+                            0.5,
+                            0.05,
+                            0.3,
+                            0.02,
+                        ) as f32
+                    })
+                    .collect();
+                // No vol makes an option worth more than both the spot and the strike
+                price[n - 1] = 200.0;
+                let vols = american_implied_vols(
+                    approximation,
+                    dir,
+                    &price,
+                    &spot,
+                    &strike,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &dividend_yield,
+                );
+                for i in 0..n - 1 {
+                    assert!((vols[i] - 0.3).abs() < 0.001);
+                    let scalar = american_implied_vol(
+                        approximation,
+                        dir,
+                        price[i],
+                        100.0,
+                        strike[i],
+                        0.5,
+                        0.05,
+                        0.02,
+                    );
+                    assert!((scalar - 0.3).abs() < 0.001);
+                }
+                assert!(vols[n - 1].is_nan());
+            }
+        }
+    }
+}
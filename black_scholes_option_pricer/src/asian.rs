@@ -0,0 +1,599 @@
+//! Average rate (Asian) options
+//!
+//! * Geometric averages are lognormal, priced in closed form for continuous averaging (Kemna and Vorst) or any
+//!   schedule of fixings
+//! * Arithmetic averages are approximated by a lognormal with the same first two moments, after Turnbull and
+//!   Wakeman (1991) on the actual fixing schedule or Levy (1992) on a continuously monitored window
+//! * Monte Carlo of the arithmetic average uses the geometric average on the same paths as a control variate
+//!
+//! An average that has already started is handled by splitting it into the fixed and the future part. For the
+//! arithmetic average (K - w A) / (1 - w) is the strike of the future part, w being the weight of the fixings
+//! already set and A their average. Everything is computed in f64.
+use crate::bs::OptionDir;
+use crate::monte_carlo::{
+    monte_carlo_control_variate, Model, MonteCarloParams, MonteCarloResult, Payoff,
+};
+use crate::normal::{ncd_f64, sign};
+
+/// Averaging window of an average rate option
+#[derive(Debug, Copy, Clone)]
+pub struct Averaging {
+    /// Years from now until the averaging starts, 0 once it has started
+    pub start: f32,
+    /// Number of equally spaced fixings over the remaining window, the first one at `start` plus the fixing
+    /// interval and the last one at expiry. 0 averages continuously
+    pub fixings: usize,
+    /// Weight of the fixings already set in the final average, 0 before the averaging starts
+    pub fixed_weight: f32,
+    /// Average of the fixings already set, arithmetic for the arithmetic options and geometric for the geometric
+    pub fixed_average: f32,
+}
+
+impl Averaging {
+    /// Continuous averaging from now to expiry
+    pub fn continuous() -> Averaging {
+        Averaging {
+            start: 0.0,
+            fixings: 0,
+            fixed_weight: 0.0,
+            fixed_average: 0.0,
+        }
+    }
+
+    /// Equally spaced fixings from now to expiry
+    pub fn discrete(fixings: usize) -> Averaging {
+        Averaging {
+            fixings,
+            ..Averaging::continuous()
+        }
+    }
+
+    /// Fixing times of the remaining window, None for continuous averaging
+    fn times(&self, years_to_expiry: f64) -> Option<Vec<f64>> {
+        let start = self.start as f64;
+        let interval = (years_to_expiry - start) / self.fixings as f64;
+        (self.fixings > 0).then(|| {
+            (1..=self.fixings)
+                .map(|i| start + interval * i as f64)
+                .collect()
+        })
+    }
+}
+
+/// Black's formula on a lognormal underlying with forward `forward` and total log variance `variance`,
+/// discounted by `discount`
+fn lognormal(phi: f64, forward: f64, strike: f64, variance: f64, discount: f64) -> f64 {
+    if variance <= 1.0e-14 {
+        return discount * (phi * (forward - strike)).max(0.0);
+    }
+    let sd = variance.sqrt();
+    let d1 = ((forward / strike).ln() + variance / 2.0) / sd;
+    discount * phi * (forward * ncd_f64(phi * d1) - strike * ncd_f64(phi * (d1 - sd)))
+}
+
+/// Geometric average over the fixing `times`, or continuously over [start, T] when there are none
+#[allow(clippy::too_many_arguments)]
+fn geometric(
+    option_dir: OptionDir,
+    s: f64,
+    k: f64,
+    t: f64,
+    r: f64,
+    v: f64,
+    q: f64,
+    times: Option<&[f64]>,
+    averaging: &Averaging,
+) -> f64 {
+    let start = averaging.start as f64;
+    // Mean time and mean of min(t_i, t_j) over the fixings, the log average has variance v^2 times the latter
+    let (mean_time, mean_min) = match times {
+        Some(times) => {
+            let n = times.len() as f64;
+            let sum_min: f64 = times
+                .iter()
+                .enumerate()
+                .map(|(i, t)| t * (2.0 * (times.len() - i) as f64 - 1.0))
+                .sum();
+            (times.iter().sum::<f64>() / n, sum_min / (n * n))
+        }
+        None => {
+            let length = t - start;
+            let sum_min =
+                2.0 * (t * (t * t - start * start) / 2.0 - (t.powi(3) - start.powi(3)) / 3.0);
+            ((start + t) / 2.0, sum_min / (length * length))
+        }
+    };
+    let w = (averaging.fixed_weight as f64).min(1.0);
+    let log_mean = w * (averaging.fixed_average as f64).max(f64::MIN_POSITIVE).ln()
+        + (1.0 - w) * (s.ln() + (r - q - v * v / 2.0) * mean_time);
+    let variance = (1.0 - w).powi(2) * v * v * mean_min;
+    lognormal(
+        sign(option_dir),
+        (log_mean + variance / 2.0).exp(),
+        k,
+        variance,
+        (-r * t).exp(),
+    )
+}
+
+/// (e^(a t1) - e^(a t0)) / a
+fn exp_integral(a: f64, t0: f64, t1: f64) -> f64 {
+    if a.abs() < 1.0e-9 {
+        t1 - t0
+    } else {
+        ((a * t1).exp() - (a * t0).exp()) / a
+    }
+}
+
+/// First two moments of the arithmetic average over the fixing `times`, or continuously over [start, T]
+fn arithmetic_moments(
+    s: f64,
+    t: f64,
+    b: f64,
+    v: f64,
+    times: Option<&[f64]>,
+    start: f64,
+) -> (f64, f64) {
+    match times {
+        Some(times) => {
+            let n = times.len() as f64;
+            let forwards: Vec<f64> = times.iter().map(|t| s * (b * t).exp()).collect();
+            // E[S_i S_j] = F_i F_j exp(v^2 min(t_i, t_j)), summed with the running sum of the later forwards
+            let mut later = 0.0;
+            let mut second = 0.0;
+            for (f, t) in forwards.iter().zip(times.iter()).rev() {
+                second += f * (v * v * t).exp() * (f + 2.0 * later);
+                later += f;
+            }
+            (later / n, second / (n * n))
+        }
+        None => {
+            let length = t - start;
+            // The double integral divides by b, which is moved off zero rather than expanded
+            let b = if b.abs() < 1.0e-6 { 1.0e-6 } else { b };
+            let first = s * exp_integral(b, start, t) / length;
+            let second = 2.0 * s * s / (length * length)
+                * ((b * t).exp() * exp_integral(b + v * v, start, t)
+                    - exp_integral(2.0 * b + v * v, start, t))
+                / b;
+            (first, second)
+        }
+    }
+}
+
+/// Lognormal approximation of the arithmetic average from its moments, allowing for the fixings already set
+#[allow(clippy::too_many_arguments)]
+fn arithmetic(
+    option_dir: OptionDir,
+    s: f64,
+    k: f64,
+    t: f64,
+    r: f64,
+    v: f64,
+    q: f64,
+    times: Option<&[f64]>,
+    averaging: &Averaging,
+) -> f64 {
+    let phi = sign(option_dir);
+    let discount = (-r * t).exp();
+    let w = averaging.fixed_weight as f64;
+    let fixed = w * averaging.fixed_average as f64;
+    if w >= 1.0 {
+        return discount * (phi * (fixed - k)).max(0.0);
+    }
+    let (m1, m2) = arithmetic_moments(s, t, r - q, v, times, averaging.start as f64);
+    let strike = (k - fixed) / (1.0 - w);
+    if strike <= 0.0 {
+        // The fixings already guarantee the call is exercised and the put is not
+        return if phi > 0.0 {
+            discount * (1.0 - w) * (m1 - strike)
+        } else {
+            0.0
+        };
+    }
+    (1.0 - w) * lognormal(phi, m1, strike, (m2 / (m1 * m1)).ln(), discount)
+}
+
+/// Geometric average rate option, continuous or discrete, with the average possibly partly fixed
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn geometric_asian_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    averaging: &Averaging,
+) -> f32 {
+    let t = years_to_expiry as f64;
+    let times = averaging.times(t);
+    geometric(
+        option_dir,
+        spot as f64,
+        strike as f64,
+        t,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+        times.as_deref(),
+        averaging,
+    ) as f32
+}
+
+/// Turnbull-Wakeman approximation of an arithmetic average rate option on the averaging schedule
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn turnbull_wakeman_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    averaging: &Averaging,
+) -> f32 {
+    let t = years_to_expiry as f64;
+    let times = averaging.times(t);
+    arithmetic(
+        option_dir,
+        spot as f64,
+        strike as f64,
+        t,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+        times.as_deref(),
+        averaging,
+    ) as f32
+}
+
+/// Levy approximation of an arithmetic average rate option. The remaining window is always averaged
+/// continuously, `averaging.fixings` is ignored
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn levy_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    averaging: &Averaging,
+) -> f32 {
+    arithmetic(
+        option_dir,
+        spot as f64,
+        strike as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+        None,
+        averaging,
+    ) as f32
+}
+
+/// Average of the path from `first` on, blended with the fixings already set
+struct AveragePayoff {
+    option_dir: OptionDir,
+    strike: f32,
+    first: usize,
+    fixed_weight: f32,
+    fixed_average: f32,
+    geometric: bool,
+}
+
+impl Payoff for AveragePayoff {
+    fn payoff(&self, path: &[f32]) -> f32 {
+        let window = &path[self.first..];
+        let n = window.len() as f32;
+        let w = self.fixed_weight;
+        let average = if self.geometric {
+            let log_average = window.iter().map(|s| s.ln()).sum::<f32>() / n;
+            // Nothing fixed yet leaves the fixed average at 0, whose log must not reach the sum
+            let fixed = if w > 0.0 {
+                w * self.fixed_average.ln()
+            } else {
+                0.0
+            };
+            (fixed + (1.0 - w) * log_average).exp()
+        } else {
+            w * self.fixed_average + (1.0 - w) * window.iter().sum::<f32>() / n
+        };
+        match self.option_dir {
+            OptionDir::CALL => (average - self.strike).max(0.0),
+            OptionDir::PUT => (self.strike - average).max(0.0),
+        }
+    }
+}
+
+/// Monte Carlo price of an arithmetic average rate option under Black Scholes, with the geometric average as a
+/// control variate. The average is taken over the simulation time steps inside the averaging window, so
+/// `params.steps` takes the place of `averaging.fixings`
+#[allow(clippy::too_many_arguments)]
+pub fn asian_monte_carlo(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    averaging: &Averaging,
+    params: &MonteCarloParams,
+) -> MonteCarloResult {
+    let steps = params.steps.max(1);
+    let t = years_to_expiry as f64;
+    let dt = t / steps as f64;
+    // Path points strictly after the start of the window, the last one is always in it
+    let first = ((averaging.start as f64 / dt + 1.0e-9).floor() as usize).min(steps - 1);
+    let times: Vec<f64> = (first + 1..=steps).map(|i| dt * i as f64).collect();
+    let payoff = |geometric| AveragePayoff {
+        option_dir,
+        strike,
+        first,
+        fixed_weight: averaging.fixed_weight,
+        fixed_average: averaging.fixed_average,
+        geometric,
+    };
+    let control_price = geometric(
+        option_dir,
+        spot as f64,
+        strike as f64,
+        t,
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+        Some(&times),
+        averaging,
+    ) as f32;
+    monte_carlo_control_variate(
+        &payoff(false),
+        &payoff(true),
+        control_price,
+        &Model::BlackScholes { volatility },
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        &MonteCarloParams { steps, ..*params },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monte_carlo::{monte_carlo, Sampler};
+
+    const MC: MonteCarloParams = MonteCarloParams {
+        paths: 40_000,
+        steps: 50,
+        seed: 11,
+        antithetic: true,
+        sampler: Sampler::PseudoRandom,
+        brownian_bridge: false,
+    };
+
+    #[test]
+    fn geometric_closed_form() {
+        // Haug, The Complete Guide to Option Pricing Formulas, Kemna-Vorst geometric average put
+        let put = geometric_asian_price(
+            OptionDir::PUT,
+            80.0,
+            85.0,
+            0.25,
+            0.05,
+            0.2,
+            -0.03,
+            &Averaging::continuous(),
+        );
+        assert!((put - 4.6922).abs() < 0.001);
+        // Many fixings approach continuous averaging
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            let continuous = geometric_asian_price(
+                dir,
+                100.0,
+                100.0,
+                1.0,
+                0.05,
+                0.3,
+                0.01,
+                &Averaging::continuous(),
+            );
+            let discrete = geometric_asian_price(
+                dir,
+                100.0,
+                100.0,
+                1.0,
+                0.05,
+                0.3,
+                0.01,
+                &Averaging::discrete(5000),
+            );
+            assert!((continuous - discrete).abs() < 0.005);
+        }
+        // A single fixing at expiry is a vanilla option
+        let vanilla =
+            crate::bs_single::bs_price(OptionDir::CALL, 100.0, 95.0, 0.5, 0.05, 0.3, 0.01);
+        let single = geometric_asian_price(
+            OptionDir::CALL,
+            100.0,
+            95.0,
+            0.5,
+            0.05,
+            0.3,
+            0.01,
+            &Averaging::discrete(1),
+        );
+        assert!((vanilla - single).abs() < 0.002);
+    }
+
+    #[test]
+    fn arithmetic_approximations_against_monte_carlo() {
+        let model = Model::BlackScholes { volatility: 0.25 };
+        for (dir, strike) in [
+            (OptionDir::CALL, 95.0),
+            (OptionDir::CALL, 105.0),
+            (OptionDir::PUT, 100.0),
+        ] {
+            let payoff = AveragePayoff {
+                option_dir: dir,
+                strike,
+                first: 0,
+                fixed_weight: 0.0,
+                fixed_average: 0.0,
+                geometric: false,
+            };
+            let mc = monte_carlo(&payoff, &model, 100.0, 1.0, 0.05, 0.02, &MC);
+            let averaging = Averaging::discrete(MC.steps);
+            let tw = turnbull_wakeman_price(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02, &averaging);
+            assert!((tw - mc.price).abs() < 3.0 * mc.std_error + 0.03);
+            // Levy's continuous window is Turnbull-Wakeman with infinitely many fixings
+            let levy = levy_price(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02, &averaging);
+            let many = turnbull_wakeman_price(
+                dir,
+                100.0,
+                strike,
+                1.0,
+                0.05,
+                0.25,
+                0.02,
+                &Averaging::discrete(5000),
+            );
+            assert!((levy - many).abs() < 0.005);
+        }
+        // The geometric average is the lower bound of the arithmetic
+        let averaging = Averaging::discrete(12);
+        let geometric = geometric_asian_price(
+            OptionDir::CALL,
+            100.0,
+            100.0,
+            1.0,
+            0.05,
+            0.25,
+            0.0,
+            &averaging,
+        );
+        let tw = turnbull_wakeman_price(
+            OptionDir::CALL,
+            100.0,
+            100.0,
+            1.0,
+            0.05,
+            0.25,
+            0.0,
+            &averaging,
+        );
+        assert!(tw > geometric);
+    }
+
+    #[test]
+    fn control_variate() {
+        let averaging = Averaging {
+            start: 0.2,
+            ..Averaging::continuous()
+        };
+        let params = MonteCarloParams {
+            paths: 20_000,
+            ..MC
+        };
+        let controlled = asian_monte_carlo(
+            OptionDir::CALL,
+            100.0,
+            100.0,
+            1.0,
+            0.05,
+            0.3,
+            0.0,
+            &averaging,
+            &params,
+        );
+        let plain = monte_carlo(
+            &AveragePayoff {
+                option_dir: OptionDir::CALL,
+                strike: 100.0,
+                first: 10,
+                fixed_weight: 0.0,
+                fixed_average: 0.0,
+                geometric: false,
+            },
+            &Model::BlackScholes { volatility: 0.3 },
+            100.0,
+            1.0,
+            0.05,
+            0.0,
+            &params,
+        );
+        assert!(controlled.std_error * 5.0 < plain.std_error);
+        assert!((controlled.price - plain.price).abs() < 3.0 * plain.std_error);
+        // The window is the last 40 of the 50 steps
+        let discrete = Averaging {
+            fixings: 40,
+            ..averaging
+        };
+        let tw = turnbull_wakeman_price(
+            OptionDir::CALL,
+            100.0,
+            100.0,
+            1.0,
+            0.05,
+            0.3,
+            0.0,
+            &discrete,
+        );
+        assert!((tw - controlled.price).abs() < 3.0 * controlled.std_error + 0.03);
+    }
+
+    #[test]
+    fn partially_fixed() {
+        // Half the fixings are in at 110, the rest average over the remaining six months
+        let averaging = Averaging {
+            start: 0.0,
+            fixings: 26,
+            fixed_weight: 0.5,
+            fixed_average: 110.0,
+        };
+        let params = MonteCarloParams { steps: 26, ..MC };
+        for (dir, strike) in [(OptionDir::CALL, 105.0), (OptionDir::PUT, 110.0)] {
+            let mc =
+                asian_monte_carlo(dir, 100.0, strike, 0.5, 0.03, 0.3, 0.0, &averaging, &params);
+            let tw = turnbull_wakeman_price(dir, 100.0, strike, 0.5, 0.03, 0.3, 0.0, &averaging);
+            assert!((tw - mc.price).abs() < 3.0 * mc.std_error + 0.03);
+        }
+        // A strike below the fixed part makes the call certain: the discounted expected average less the strike
+        let call = turnbull_wakeman_price(
+            OptionDir::CALL,
+            100.0,
+            50.0,
+            0.5,
+            0.03,
+            0.3,
+            0.0,
+            &averaging,
+        );
+        let forward = 100.0 * (0.03 * 0.5 * 27.0 / 26.0 / 2.0f64).exp();
+        let expected = (-0.03 * 0.5f64).exp() * (55.0 + 0.5 * forward - 50.0);
+        assert!((call as f64 - expected).abs() < 0.05);
+        let put =
+            turnbull_wakeman_price(OptionDir::PUT, 100.0, 50.0, 0.5, 0.03, 0.3, 0.0, &averaging);
+        assert!(put == 0.0);
+        // Fully fixed averages are worth their discounted intrinsic value
+        let fixed = Averaging {
+            fixed_weight: 1.0,
+            ..averaging
+        };
+        let call =
+            turnbull_wakeman_price(OptionDir::CALL, 100.0, 100.0, 0.5, 0.03, 0.3, 0.0, &fixed);
+        assert!((call - 10.0 * (-0.015f32).exp()).abs() < 1.0e-4);
+        let geometric =
+            geometric_asian_price(OptionDir::CALL, 100.0, 100.0, 0.5, 0.03, 0.3, 0.0, &fixed);
+        assert!((geometric - call).abs() < 1.0e-4);
+    }
+}
@@ -0,0 +1,291 @@
+//! P&L attribution of a portfolio between two market snapshots
+//!
+//! The actual P&L fully reprices every position with the market data of the second snapshot and expiries rolled
+//! forward by the time elapsed. It is explained by a Taylor expansion in the moves of spot, volatility, rate and
+//! time, with the greeks of the portfolio at the first snapshot:
+//!
+//! * first order: delta dS, vega dv, rho dr and theta dt
+//! * second order adds gamma dS^2 / 2, vanna dS dv and volga dv^2 / 2
+//!
+//! What the expansion misses, including higher orders and any change of dividend yield, is left unexplained.
+//! Underlyings missing from the second snapshot keep their market data.
+use crate::normal::npd_f64;
+use crate::portfolio::{InstrumentType, MarketData, Portfolio, PortfolioGreeks, Position};
+use std::collections::{BTreeMap, HashMap};
+use wasm_bindgen::prelude::*;
+
+/// Order of the Taylor expansion explaining the P&L
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TaylorOrder {
+    First = 1,
+    Second = 2,
+}
+
+/// Market data of a set of underlyings at one time, such as one tick of each
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct MarketSnapshot {
+    market: HashMap<String, MarketData>,
+}
+
+impl MarketSnapshot {
+    pub fn market_data(&self, underlying: &str) -> Option<MarketData> {
+        self.market.get(underlying).copied()
+    }
+}
+
+#[wasm_bindgen]
+impl MarketSnapshot {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MarketSnapshot {
+        MarketSnapshot::default()
+    }
+
+    /// Set or replace the market data of an underlying
+    pub fn set_market_data(&mut self, underlying: &str, market_data: MarketData) {
+        self.market.insert(underlying.to_string(), market_data);
+    }
+}
+
+/// Actual P&L and its explanation by greeks, with the residual unexplained. Terms beyond the Taylor order are zero
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct PnlAttribution {
+    pub actual: f32,
+    pub delta: f32,
+    pub gamma: f32,
+    pub vega: f32,
+    pub theta: f32,
+    pub rho: f32,
+    pub vanna: f32,
+    pub volga: f32,
+    pub unexplained: f32,
+}
+
+impl std::ops::AddAssign for PnlAttribution {
+    fn add_assign(&mut self, other: PnlAttribution) {
+        self.actual += other.actual;
+        self.delta += other.delta;
+        self.gamma += other.gamma;
+        self.vega += other.vega;
+        self.theta += other.theta;
+        self.rho += other.rho;
+        self.vanna += other.vanna;
+        self.volga += other.volga;
+        self.unexplained += other.unexplained;
+    }
+}
+
+/// Vanna and volga of a vanilla, which are the same for calls and puts
+fn vanna_volga(spot: f32, strike: f32, t: f32, r: f32, v: f32, q: f32) -> (f64, f64) {
+    let (s, k, t, r, v, q) = (
+        spot as f64,
+        strike as f64,
+        t as f64,
+        r as f64,
+        v as f64,
+        q as f64,
+    );
+    let d1 = ((s / k).ln() + (r - q + v * v / 2.0) * t) / (v * t.sqrt());
+    let d2 = d1 - v * t.sqrt();
+    let density = (-q * t).exp() * npd_f64(d1);
+    (-density * d2 / v, s * density * t.sqrt() * d1 * d2 / v)
+}
+
+/// Explanation of the P&L of one position from its greeks in the portfolio, without the actual P&L
+fn explain(
+    position: &Position,
+    greeks: PortfolioGreeks,
+    before: MarketData,
+    after: MarketData,
+    years_elapsed: f32,
+    order: TaylorOrder,
+) -> PnlAttribution {
+    let instrument = &position.instrument;
+    let ds = after.spot - before.spot;
+    let dv = after.volatility - before.volatility;
+    let dr = after.risk_free_rate - before.risk_free_rate;
+    // Expired options only have the intrinsic value left to explain
+    if instrument.instrument_type != InstrumentType::Underlying && instrument.years_to_expiry <= 0.0
+    {
+        return PnlAttribution::default();
+    }
+    let mut explained = PnlAttribution {
+        delta: greeks.delta * ds,
+        vega: greeks.vega * dv,
+        theta: greeks.theta * years_elapsed,
+        rho: greeks.rho * dr,
+        ..Default::default()
+    };
+    if order == TaylorOrder::Second && instrument.instrument_type != InstrumentType::Underlying {
+        let scale = position.quantity * instrument.multiplier;
+        let (vanna, volga) = vanna_volga(
+            before.spot,
+            instrument.strike,
+            instrument.years_to_expiry,
+            before.risk_free_rate,
+            before.volatility,
+            before.dividend_yield,
+        );
+        explained.gamma = greeks.gamma * ds * ds / 2.0;
+        explained.vanna = scale * (vanna * ds as f64 * dv as f64) as f32;
+        explained.volga = scale * (volga * dv as f64 * dv as f64 / 2.0) as f32;
+    }
+    explained
+}
+
+/// The P&L of each underlying between the market of the portfolio and a later snapshot, explained to the first or
+/// second order
+/// Years elapsed should be expressed as a f32 such as 1 day is 1/252 = 0.004
+pub fn attribution_by_underlying(
+    portfolio: &Portfolio,
+    snapshot: &MarketSnapshot,
+    years_elapsed: f32,
+    order: TaylorOrder,
+) -> BTreeMap<String, PnlAttribution> {
+    let mut later = Portfolio::new();
+    for position in portfolio.positions() {
+        let mut position = position.clone();
+        let name = &position.instrument.underlying;
+        if let Some(market) = snapshot
+            .market_data(name)
+            .or_else(|| portfolio.market_data(name))
+        {
+            later.set_market_data(name, market);
+        }
+        if position.instrument.instrument_type != InstrumentType::Underlying {
+            position.instrument.years_to_expiry -= years_elapsed;
+        }
+        later.add(position);
+    }
+    let (before, after) = (portfolio.position_greeks(), later.position_greeks());
+    let mut attribution = BTreeMap::new();
+    for (i, position) in portfolio.positions().iter().enumerate() {
+        let name = &position.instrument.underlying;
+        let mut explained = match (portfolio.market_data(name), later.market_data(name)) {
+            (Some(b), Some(a)) => explain(position, before[i], b, a, years_elapsed, order),
+            _ => PnlAttribution::default(),
+        };
+        explained.actual = after[i].pv - before[i].pv;
+        explained.unexplained = explained.actual
+            - explained.delta
+            - explained.gamma
+            - explained.vega
+            - explained.theta
+            - explained.rho
+            - explained.vanna
+            - explained.volga;
+        *attribution
+            .entry(name.clone())
+            .or_insert_with(PnlAttribution::default) += explained;
+    }
+    attribution
+}
+
+/// The P&L of a portfolio between its market and a later snapshot, explained to the first or second order
+/// Years elapsed should be expressed as a f32 such as 1 day is 1/252 = 0.004
+#[wasm_bindgen]
+pub fn pnl_attribution(
+    portfolio: &Portfolio,
+    snapshot: &MarketSnapshot,
+    years_elapsed: f32,
+    order: TaylorOrder,
+) -> PnlAttribution {
+    let mut total = PnlAttribution::default();
+    for (_, attribution) in attribution_by_underlying(portfolio, snapshot, years_elapsed, order) {
+        total += attribution;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portfolio() -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.25, 0.01));
+        portfolio.set_market_data("XYZ", MarketData::new(50.0, 0.03, 0.4, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Call, 105.0, 0.25, 10.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Put, 90.0, 0.5, -10.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Underlying, 0.0, 0.0, -200.0, 1.0);
+        portfolio.add_position("XYZ", InstrumentType::Call, 50.0, 1.0, 5.0, 100.0);
+        portfolio
+    }
+
+    fn snapshot(spot_move: f32, vol_move: f32, rate_move: f32) -> MarketSnapshot {
+        let mut snapshot = MarketSnapshot::new();
+        snapshot.set_market_data(
+            "ABC",
+            MarketData::new(100.0 + spot_move, 0.03 + rate_move, 0.25 + vol_move, 0.01),
+        );
+        snapshot
+    }
+
+    #[test]
+    fn terms_add_up() {
+        let portfolio = portfolio();
+        let day = 1.0 / 252.0;
+        let attribution = pnl_attribution(
+            &portfolio,
+            &snapshot(2.0, 0.01, 0.001),
+            day,
+            TaylorOrder::Second,
+        );
+        let explained = attribution.delta
+            + attribution.gamma
+            + attribution.vega
+            + attribution.theta
+            + attribution.rho
+            + attribution.vanna
+            + attribution.volga;
+        assert!((attribution.actual - explained - attribution.unexplained).abs() < 1.0e-2);
+        let by_underlying = attribution_by_underlying(
+            &portfolio,
+            &snapshot(2.0, 0.01, 0.001),
+            day,
+            TaylorOrder::Second,
+        );
+        assert_eq!(by_underlying.len(), 2);
+        // XYZ is not in the snapshot, so only time moves it
+        let xyz = by_underlying["XYZ"];
+        assert_eq!(xyz.delta, 0.0);
+        assert!((xyz.actual - xyz.theta).abs() < 0.02 * xyz.theta.abs());
+
+        let first = pnl_attribution(
+            &portfolio,
+            &snapshot(2.0, 0.01, 0.001),
+            day,
+            TaylorOrder::First,
+        );
+        assert_eq!(first.gamma, 0.0);
+        assert_eq!(first.vanna, 0.0);
+        assert_eq!(first.volga, 0.0);
+        assert_eq!(first.delta, attribution.delta);
+        assert_eq!(first.actual, attribution.actual);
+    }
+
+    #[test]
+    fn second_order_explains_more() {
+        let portfolio = portfolio();
+        for &(ds, dv) in &[(3.0, 0.02), (-4.0, 0.03), (5.0, -0.02)] {
+            let snapshot = snapshot(ds, dv, 0.0);
+            let first = pnl_attribution(&portfolio, &snapshot, 1.0 / 252.0, TaylorOrder::First);
+            let second = pnl_attribution(&portfolio, &snapshot, 1.0 / 252.0, TaylorOrder::Second);
+            assert!(second.unexplained.abs() < first.unexplained.abs());
+            assert!(second.unexplained.abs() < 0.05 * second.actual.abs());
+        }
+    }
+
+    #[test]
+    fn cross_greeks_against_finite_differences() {
+        let (s, k, t, r, v, q) = (100.0f32, 110.0f32, 0.5f32, 0.03f32, 0.25f32, 0.01f32);
+        let (vanna, volga) = vanna_volga(s, k, t, r, v, q);
+        let vega = |s: f32, v: f32| crate::bs_single::vega(s, k, t, r, v, q) as f64;
+        let fd_vanna = (vega(s + 0.5, v) - vega(s - 0.5, v)) / 1.0;
+        let fd_volga = (vega(s, v + 0.01) - vega(s, v - 0.01)) / 0.02;
+        assert!((vanna - fd_vanna).abs() < 0.01 * vanna.abs());
+        assert!((volga - fd_volga).abs() < 0.02 * volga.abs());
+    }
+}
@@ -0,0 +1,661 @@
+//! Closed form single barrier options after Reiner and Rubinstein (1991), in the notation of Haug's
+//! Complete Guide to Option Pricing Formulas
+//!
+//! * Down and up, knock-in and knock-out calls and puts with a cash rebate. Knock-out rebates are paid when the
+//!   barrier is hit, knock-in rebates at expiry if the barrier was never hit
+//! * Discrete monitoring through the Broadie-Glasserman-Kou (1997) continuity correction, which moves the
+//!   barrier away from the spot by exp(0.5826 vol sqrt(monitoring interval))
+//!
+//! The scalar and the batch versions share the way the six terms A to F of the formulas are combined.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_::{ncd_f32x8, price_f32x8};
+use crate::bs_single::{bs_price, ncd};
+use bytemuck::cast;
+use std::ops::{Add, Sub};
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// Direction and knock of a single barrier
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BarrierKind {
+    DownAndIn,
+    DownAndOut,
+    UpAndIn,
+    UpAndOut,
+}
+
+/// -zeta(1/2) / sqrt(2 pi), the Broadie-Glasserman-Kou shift
+const BGK_BETA: f32 = 0.5826;
+
+impl BarrierKind {
+    fn is_down(self) -> bool {
+        matches!(self, BarrierKind::DownAndIn | BarrierKind::DownAndOut)
+    }
+
+    fn is_knock_in(self) -> bool {
+        matches!(self, BarrierKind::DownAndIn | BarrierKind::UpAndIn)
+    }
+}
+
+/// Haug's terms A to F
+#[derive(Copy, Clone)]
+struct Terms<T> {
+    a: T,
+    b: T,
+    c: T,
+    d: T,
+    e: T,
+    f: T,
+}
+
+/// Barrier price from the terms, as (strike above the barrier, strike below the barrier)
+fn combine<T: Copy + Add<Output = T> + Sub<Output = T>>(
+    kind: BarrierKind,
+    option_dir: OptionDir,
+    terms: &Terms<T>,
+) -> (T, T) {
+    let Terms { a, b, c, d, e, f } = *terms;
+    match (kind, option_dir) {
+        (BarrierKind::DownAndIn, OptionDir::CALL) => (c + e, a - b + d + e),
+        (BarrierKind::UpAndIn, OptionDir::CALL) => (a + e, b - c + d + e),
+        (BarrierKind::DownAndIn, OptionDir::PUT) => (b - c + d + e, a + e),
+        (BarrierKind::UpAndIn, OptionDir::PUT) => (a - b + d + e, c + e),
+        (BarrierKind::DownAndOut, OptionDir::CALL) => (a - c + f, b - d + f),
+        (BarrierKind::UpAndOut, OptionDir::CALL) => (f, a - b + c - d + f),
+        (BarrierKind::DownAndOut, OptionDir::PUT) => (a - b + c - d + f, f),
+        (BarrierKind::UpAndOut, OptionDir::PUT) => (b - d + f, a - c + f),
+    }
+}
+
+/// Barrier level of the continuously monitored option approximating one monitored every `monitoring_interval`
+/// years. A zero interval leaves the barrier unchanged
+#[wasm_bindgen]
+pub fn bgk_barrier(
+    kind: BarrierKind,
+    barrier: f32,
+    volatility: f32,
+    monitoring_interval: f32,
+) -> f32 {
+    let shift = (BGK_BETA * volatility * monitoring_interval.sqrt()).exp();
+    if kind.is_down() {
+        barrier / shift
+    } else {
+        barrier * shift
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn terms(
+    option_dir: OptionDir,
+    kind: BarrierKind,
+    s: f32,
+    x: f32,
+    h: f32,
+    rebate: f32,
+    t: f32,
+    r: f32,
+    v: f32,
+    q: f32,
+) -> Terms<f32> {
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    let eta = if kind.is_down() { 1.0 } else { -1.0 };
+    let b = r - q;
+    let v2 = v * v;
+    let mu = (b - v2 / 2.0) / v2;
+    let lambda = (mu * mu + 2.0 * r / v2).sqrt();
+    let sd = v * t.sqrt();
+    let x1 = (s / x).ln() / sd + (1.0 + mu) * sd;
+    let x2 = (s / h).ln() / sd + (1.0 + mu) * sd;
+    let y1 = (h * h / (s * x)).ln() / sd + (1.0 + mu) * sd;
+    let y2 = (h / s).ln() / sd + (1.0 + mu) * sd;
+    let z = (h / s).ln() / sd + lambda * sd;
+    let forward = s * ((b - r) * t).exp();
+    let discount = (-r * t).exp();
+    let hs = h / s;
+    let (hs_mu, hs_mu1) = (hs.powf(2.0 * mu), hs.powf(2.0 * (mu + 1.0)));
+    Terms {
+        a: phi * forward * ncd(phi * x1) - phi * x * discount * ncd(phi * (x1 - sd)),
+        b: phi * forward * ncd(phi * x2) - phi * x * discount * ncd(phi * (x2 - sd)),
+        c: phi * forward * hs_mu1 * ncd(eta * y1)
+            - phi * x * discount * hs_mu * ncd(eta * (y1 - sd)),
+        d: phi * forward * hs_mu1 * ncd(eta * y2)
+            - phi * x * discount * hs_mu * ncd(eta * (y2 - sd)),
+        e: rebate * discount * (ncd(eta * (x2 - sd)) - hs_mu * ncd(eta * (y2 - sd))),
+        f: rebate
+            * (hs.powf(mu + lambda) * ncd(eta * z)
+                + hs.powf(mu - lambda) * ncd(eta * (z - 2.0 * lambda * sd))),
+    }
+}
+
+/// Price of a single barrier option with a rebate.
+/// The barrier is monitored every `monitoring_interval` years through the Broadie-Glasserman-Kou correction,
+/// 0 for continuous monitoring. Spots already through the (corrected) barrier give the rebate for knock-outs and
+/// the vanilla price for knock-ins
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn barrier_price(
+    option_dir: OptionDir,
+    kind: BarrierKind,
+    spot: f32,
+    strike: f32,
+    barrier: f32,
+    rebate: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    monitoring_interval: f32,
+) -> f32 {
+    let barrier = bgk_barrier(kind, barrier, volatility, monitoring_interval);
+    let breached = if kind.is_down() {
+        spot <= barrier
+    } else {
+        spot >= barrier
+    };
+    if breached {
+        return if kind.is_knock_in() {
+            bs_price(
+                option_dir,
+                spot,
+                strike,
+                years_to_expiry,
+                risk_free_rate,
+                volatility,
+                dividend_yield,
+            )
+        } else {
+            rebate
+        };
+    }
+    let terms = terms(
+        option_dir,
+        kind,
+        spot,
+        strike,
+        barrier,
+        rebate,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    );
+    let (above, below) = combine(kind, option_dir, &terms);
+    if strike >= barrier {
+        above
+    } else {
+        below
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn terms_f32x8(
+    option_dir: OptionDir,
+    kind: BarrierKind,
+    s: f32x8,
+    x: f32x8,
+    h: f32x8,
+    rebate: f32x8,
+    t: f32x8,
+    r: f32x8,
+    v: f32x8,
+    q: f32x8,
+) -> Terms<f32x8> {
+    let phi = f32x8::splat(match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    });
+    let eta = f32x8::splat(if kind.is_down() { 1.0 } else { -1.0 });
+    let pow = |x: f32x8, y: f32x8| (y * x.ln()).exp();
+    let b = r - q;
+    let v2 = v * v;
+    let mu = (b - v2 * 0.5) / v2;
+    let lambda = (mu * mu + 2.0 * r / v2).sqrt();
+    let sd = v * t.sqrt();
+    let x1 = (s / x).ln() / sd + (1.0 + mu) * sd;
+    let x2 = (s / h).ln() / sd + (1.0 + mu) * sd;
+    let y1 = (h * h / (s * x)).ln() / sd + (1.0 + mu) * sd;
+    let y2 = (h / s).ln() / sd + (1.0 + mu) * sd;
+    let z = (h / s).ln() / sd + lambda * sd;
+    let forward = s * ((b - r) * t).exp();
+    let discount = (-r * t).exp();
+    let hs = h / s;
+    let (hs_mu, hs_mu1) = (pow(hs, 2.0 * mu), pow(hs, 2.0 * (mu + 1.0)));
+    Terms {
+        a: phi * forward * ncd_f32x8(phi * x1) - phi * x * discount * ncd_f32x8(phi * (x1 - sd)),
+        b: phi * forward * ncd_f32x8(phi * x2) - phi * x * discount * ncd_f32x8(phi * (x2 - sd)),
+        c: phi * forward * hs_mu1 * ncd_f32x8(eta * y1)
+            - phi * x * discount * hs_mu * ncd_f32x8(eta * (y1 - sd)),
+        d: phi * forward * hs_mu1 * ncd_f32x8(eta * y2)
+            - phi * x * discount * hs_mu * ncd_f32x8(eta * (y2 - sd)),
+        e: rebate * discount * (ncd_f32x8(eta * (x2 - sd)) - hs_mu * ncd_f32x8(eta * (y2 - sd))),
+        f: rebate
+            * (pow(hs, mu + lambda) * ncd_f32x8(eta * z)
+                + pow(hs, mu - lambda) * ncd_f32x8(eta * (z - 2.0 * lambda * sd))),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn barrier_f32x8(
+    option_dir: OptionDir,
+    kind: BarrierKind,
+    s: f32x8,
+    x: f32x8,
+    h: f32x8,
+    rebate: f32x8,
+    t: f32x8,
+    r: f32x8,
+    v: f32x8,
+    q: f32x8,
+    monitoring_interval: f32,
+) -> f32x8 {
+    let shift = (BGK_BETA * v * monitoring_interval.sqrt()).exp();
+    let h = if kind.is_down() { h / shift } else { h * shift };
+    let terms = terms_f32x8(option_dir, kind, s, x, h, rebate, t, r, v, q);
+    let (above, below) = combine(kind, option_dir, &terms);
+    let price = x.cmp_ge(h).blend(above, below);
+    let breached = if kind.is_down() {
+        s.cmp_le(h)
+    } else {
+        s.cmp_ge(h)
+    };
+    let knocked = if kind.is_knock_in() {
+        price_f32x8(option_dir, s, x, t, r, v, q)
+    } else {
+        rebate
+    };
+    breached.blend(knocked, price)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn batch_price(
+    option_dir: OptionDir,
+    kind: BarrierKind,
+    spot: &[f32],
+    strike: &[f32],
+    barrier: &[f32],
+    rebate: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let spot = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let strike = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let barrier = f32x8::from(&barrier[i..std::cmp::min(max_idx, i + 8)]);
+        let rebate = f32x8::from(&rebate[i..std::cmp::min(max_idx, i + 8)]);
+        let years_to_expiry = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let risk_free_rate = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let volatility = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let dividend_yield = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let price: [f32; 8] = cast(barrier_f32x8(
+            option_dir,
+            kind,
+            spot,
+            strike,
+            barrier,
+            rebate,
+            years_to_expiry,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+            monitoring_interval,
+        ));
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Barrier call pricing. The results are at the same index as the inputs
+/// The barrier is monitored every `monitoring_interval` years, 0 for continuous monitoring
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn barrier_call(
+    kind: BarrierKind,
+    spot: &[f32],
+    strike: &[f32],
+    barrier: &[f32],
+    rebate: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::CALL,
+        kind,
+        spot,
+        strike,
+        barrier,
+        rebate,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+/// Barrier put pricing. The results are at the same index as the inputs
+/// The barrier is monitored every `monitoring_interval` years, 0 for continuous monitoring
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn barrier_put(
+    kind: BarrierKind,
+    spot: &[f32],
+    strike: &[f32],
+    barrier: &[f32],
+    rebate: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::PUT,
+        kind,
+        spot,
+        strike,
+        barrier,
+        rebate,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monte_carlo::{monte_carlo, Model, MonteCarloParams, Payoff, Sampler};
+    use crate::pde::{pde_price, Barrier, BarrierType, EarlyExercise, Exercise, PdeParams};
+
+    const KINDS: [BarrierKind; 4] = [
+        BarrierKind::DownAndIn,
+        BarrierKind::DownAndOut,
+        BarrierKind::UpAndIn,
+        BarrierKind::UpAndOut,
+    ];
+
+    #[test]
+    fn reference_values() {
+        // Haug, Table 4-13: S = 100, T = 0.5, r = 0.08, b = 0.04, vol = 0.25, rebate 3
+        let price = |dir, kind, strike, barrier| {
+            barrier_price(
+                dir, kind, 100.0, strike, barrier, 3.0, 0.5, 0.08, 0.25, 0.04, 0.0,
+            )
+        };
+        let cases = [
+            (
+                OptionDir::CALL,
+                BarrierKind::DownAndOut,
+                95.0,
+                [9.0246, 6.7924, 4.8759],
+            ),
+            (
+                OptionDir::CALL,
+                BarrierKind::DownAndIn,
+                95.0,
+                [7.7627, 4.0109, 2.0576],
+            ),
+            (
+                OptionDir::CALL,
+                BarrierKind::UpAndIn,
+                105.0,
+                [14.1112, 8.4482, 4.5910],
+            ),
+        ];
+        for (dir, kind, barrier, expected) in cases {
+            for (&strike, expected) in [90.0, 100.0, 110.0].iter().zip(expected) {
+                assert!((price(dir, kind, strike, barrier) - expected).abs() < 0.002);
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_parity() {
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            for strike in [90.0, 100.0, 110.0] {
+                let vanilla = bs_price(dir, 100.0, strike, 0.5, 0.05, 0.3, 0.02);
+                for (knock_in, knock_out, barrier) in [
+                    (BarrierKind::DownAndIn, BarrierKind::DownAndOut, 95.0),
+                    (BarrierKind::UpAndIn, BarrierKind::UpAndOut, 105.0),
+                ] {
+                    let price = |kind| {
+                        barrier_price(
+                            dir, kind, 100.0, strike, barrier, 0.0, 0.5, 0.05, 0.3, 0.02, 0.0,
+                        )
+                    };
+                    assert!((price(knock_in) + price(knock_out) - vanilla).abs() < 0.002);
+                }
+            }
+        }
+        // Without discounting the two rebates split the certain rebate between hitting and not hitting
+        for (knock_in, knock_out, barrier) in [
+            (BarrierKind::DownAndIn, BarrierKind::DownAndOut, 95.0),
+            (BarrierKind::UpAndIn, BarrierKind::UpAndOut, 105.0),
+        ] {
+            let rebate = |kind| {
+                barrier_price(
+                    OptionDir::CALL,
+                    kind,
+                    100.0,
+                    100.0,
+                    barrier,
+                    1.0,
+                    0.5,
+                    0.0,
+                    0.3,
+                    0.0,
+                    0.0,
+                ) - barrier_price(
+                    OptionDir::CALL,
+                    kind,
+                    100.0,
+                    100.0,
+                    barrier,
+                    0.0,
+                    0.5,
+                    0.0,
+                    0.3,
+                    0.0,
+                    0.0,
+                )
+            };
+            assert!((rebate(knock_in) + rebate(knock_out) - 1.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn knock_outs_against_pde() {
+        let params = PdeParams {
+            space_steps: 400,
+            time_steps: 200,
+            rannacher_steps: 2,
+            concentration: 0.1,
+            early_exercise: EarlyExercise::BrennanSchwartz,
+        };
+        for (dir, kind, barrier_type, level, strike) in [
+            (
+                OptionDir::CALL,
+                BarrierKind::DownAndOut,
+                BarrierType::DownAndOut,
+                90.0,
+                95.0,
+            ),
+            (
+                OptionDir::PUT,
+                BarrierKind::DownAndOut,
+                BarrierType::DownAndOut,
+                90.0,
+                105.0,
+            ),
+            (
+                OptionDir::CALL,
+                BarrierKind::UpAndOut,
+                BarrierType::UpAndOut,
+                120.0,
+                100.0,
+            ),
+            (
+                OptionDir::PUT,
+                BarrierKind::UpAndOut,
+                BarrierType::UpAndOut,
+                110.0,
+                115.0,
+            ),
+        ] {
+            let barrier = Barrier {
+                barrier_type,
+                level,
+                rebate: 2.0,
+            };
+            let pde = pde_price(
+                dir,
+                Exercise::European,
+                Some(barrier),
+                100.0,
+                strike,
+                1.0,
+                0.05,
+                0.25,
+                0.01,
+                &params,
+            );
+            let closed = barrier_price(
+                dir, kind, 100.0, strike, level, 2.0, 1.0, 0.05, 0.25, 0.01, 0.0,
+            );
+            assert!((pde.pv - closed).abs() < 0.02);
+        }
+    }
+
+    struct DiscreteDownAndOut {
+        strike: f32,
+        barrier: f32,
+    }
+
+    impl Payoff for DiscreteDownAndOut {
+        fn payoff(&self, path: &[f32]) -> f32 {
+            if path.iter().any(|&s| s <= self.barrier) {
+                0.0
+            } else {
+                (path[path.len() - 1] - self.strike).max(0.0)
+            }
+        }
+    }
+
+    #[test]
+    fn discrete_monitoring() {
+        let params = MonteCarloParams {
+            paths: 100_000,
+            steps: 25,
+            seed: 42,
+            antithetic: true,
+            sampler: Sampler::PseudoRandom,
+            brownian_bridge: false,
+        };
+        let payoff = DiscreteDownAndOut {
+            strike: 100.0,
+            barrier: 95.0,
+        };
+        let model = Model::BlackScholes { volatility: 0.25 };
+        let mc = monte_carlo(&payoff, &model, 100.0, 0.5, 0.05, 0.0, &params);
+        let price = |interval| {
+            barrier_price(
+                OptionDir::CALL,
+                BarrierKind::DownAndOut,
+                100.0,
+                100.0,
+                95.0,
+                0.0,
+                0.5,
+                0.05,
+                0.25,
+                0.0,
+                interval,
+            )
+        };
+        let discrete = price(0.5 / 25.0);
+        assert!((discrete - mc.price).abs() < 3.0 * mc.std_error + 0.02);
+        // The correction matters, continuous monitoring knocks out noticeably more often
+        assert!(discrete - price(0.0) > 0.2);
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        // Continuously monitored barriers close to the spot, where the reflected terms nearly cancel the vanilla,
+        // the last one on it
+        let spot = [100.0, 100.0, 100.0, 50.0, 250.0, 100.0, 100.0, 100.0, 100.0];
+        let strike = [100.0, 98.0, 105.0, 50.0, 240.0, 100.0, 95.0, 110.0, 100.0];
+        let distance = [0.999, 0.995, 0.99, 0.98, 0.995, 0.97, 0.999, 0.95, 1.0];
+        let barrier: Vec<f32> = spot.iter().zip(distance).map(|(s, d)| s * d).collect();
+        let rebate = [0.0, 1.0, 2.0, 0.5, 0.0, 3.0, 1.0, 0.0, 2.0];
+        let years_to_expiry = [0.05, 0.25, 1.0, 0.5, 2.0, 0.5, 0.02, 3.0, 0.75];
+        let risk_free_rate = [0.0, 0.02, 0.05, 0.08, 0.01, 0.03, 0.0, 0.04, 0.1];
+        let volatility = [0.1, 0.15, 0.3, 0.5, 0.2, 0.25, 0.6, 0.08, 0.35];
+        let dividend_yield = [0.0, 0.04, 0.0, 0.02, 0.05, 0.0, 0.01, 0.03, 0.1];
+        for kind in KINDS {
+            // Mirror the barriers above the spot for the up options
+            let barrier: Vec<f32> = if kind.is_down() {
+                barrier.clone()
+            } else {
+                barrier.iter().zip(spot).map(|(h, s)| 2.0 * s - h).collect()
+            };
+            for (dir, batch) in [
+                (
+                    OptionDir::CALL,
+                    barrier_call as fn(_, &_, &_, &_, &_, &_, &_, &_, &_, _) -> _,
+                ),
+                (OptionDir::PUT, barrier_put),
+            ] {
+                let prices = batch(
+                    kind,
+                    &spot,
+                    &strike,
+                    &barrier,
+                    &rebate,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &volatility,
+                    &dividend_yield,
+                    0.0,
+                );
+                assert_eq!(prices.len(), spot.len());
+                for i in 0..spot.len() {
+                    let scalar = barrier_price(
+                        dir,
+                        kind,
+                        spot[i],
+                        strike[i],
+                        barrier[i],
+                        rebate[i],
+                        years_to_expiry[i],
+                        risk_free_rate[i],
+                        volatility[i],
+                        dividend_yield[i],
+                        0.0,
+                    );
+                    assert!((prices[i] - scalar).abs() < 0.002);
+                }
+            }
+        }
+    }
+}
@@ -20,7 +20,7 @@ fn erf_f32x8(x: f32x8) -> f32x8 {
     t.blend(-m, m)
 }
 
-fn ncd_f32x8(e: f32x8) -> f32x8 {
+pub(crate) fn ncd_f32x8(e: f32x8) -> f32x8 {
     let v = f32x8::HALF * (f32x8::ONE + erf_f32x8(e / f32x8::SQRT_2));
     let min: f32x8 = f32x8::splat(-1.0e5);
     let max: f32x8 = f32x8::splat(1.0e5);
@@ -32,9 +32,9 @@ fn ncd_f32x8(e: f32x8) -> f32x8 {
     v
 }
 
-fn npd_f32x8(e: f32x8) -> f32x8 {
+pub(crate) fn npd_f32x8(e: f32x8) -> f32x8 {
     const C: f32 = 0.3989422804014330;
-    (0.5 * e * e).exp() * C
+    (-0.5 * e * e).exp() * C
 }
 
 // t - spot
@@ -124,7 +124,7 @@ pub(crate) fn gamma_f32x8(
     let d1 = f32x8::ONE / rd * (ssln + (il + vs2) * years_to_expiry);
     let v = npd_f32x8(d1);
     let la = (-dividend_yield * years_to_expiry).exp();
-    -la * v / (spot * volatility * d)
+    la * v / (spot * volatility * d)
 }
 
 pub(crate) fn vega_f32x8(
@@ -530,7 +530,7 @@ pub(crate) fn call_greeks_f32x8(
     let c = ncd_f32x8(d2);
     let pv = o * spot * la - c * g;
     let delta = la * o;
-    let gamma = -la * v / (spot * volatility * d);
+    let gamma = la * v / (spot * volatility * d);
     let vega = spot * la * v * d;
     let theta = -la * spot * v * volatility / (2.0 * d) - risk_free_rate * g * c
         + dividend_yield * spot * la * o;
@@ -571,7 +571,7 @@ pub(crate) fn put_greeks_f32x8(
     let c = ncd_f32x8(-d2);
     let pv = c * g - o * spot * la;
     let delta = -la * o;
-    let gamma = -la * v / (spot * volatility * d);
+    let gamma = la * v / (spot * volatility * d);
     let vega = spot * la * v * d;
     let theta = -la * spot * v * volatility / (2.0 * d) + risk_free_rate * g * c
         - dividend_yield * spot * la * o;
@@ -1069,6 +1069,51 @@ mod tests {
         let duration = now.elapsed().as_millis();
         println!("Time take {}ms", duration);
     }
+
+    #[test]
+    fn greeks_against_finite_differences() {
+        let (k, t, r, v, q) = (100.0f32, 0.5f32, 0.03f32, 0.25f32, 0.01f32);
+        for &spot in &[80.0f32, 100.0, 120.0] {
+            for &(greeks, price) in &[
+                (
+                    call_greeks_f32x8 as fn(f32x8, f32x8, f32x8, f32x8, f32x8, f32x8) -> Greek,
+                    call_f32x8 as fn(f32x8, f32x8, f32x8, f32x8, f32x8, f32x8) -> f32x8,
+                ),
+                (put_greeks_f32x8, put_f32x8),
+            ] {
+                let pv = |s: f32, t: f32, v: f32| -> f64 {
+                    let p: [f32; 8] = cast(price(
+                        s.into(),
+                        k.into(),
+                        t.into(),
+                        r.into(),
+                        v.into(),
+                        q.into(),
+                    ));
+                    p[0] as f64
+                };
+                let g = greeks(
+                    spot.into(),
+                    k.into(),
+                    t.into(),
+                    r.into(),
+                    v.into(),
+                    q.into(),
+                );
+                let (g_gamma, g_vega, g_theta): ([f32; 8], [f32; 8], [f32; 8]) =
+                    (cast(g.gamma), cast(g.vega), cast(g.theta));
+                let (h, dv, dt) = (1.0f32, 0.01f32, 0.01f32);
+                let fd_gamma = (pv(spot + h, t, v) - 2.0 * pv(spot, t, v) + pv(spot - h, t, v))
+                    / (h * h) as f64;
+                let fd_vega = (pv(spot, t, v + dv) - pv(spot, t, v - dv)) / (2.0 * dv) as f64;
+                let fd_theta = (pv(spot, t - dt, v) - pv(spot, t + dt, v)) / (2.0 * dt) as f64;
+                assert!(g_gamma[0] > 0.0);
+                assert!((g_gamma[0] as f64 - fd_gamma).abs() < 2.0e-3);
+                assert!((g_vega[0] as f64 - fd_vega).abs() < 0.05);
+                assert!((g_theta[0] as f64 - fd_theta).abs() < 0.05);
+            }
+        }
+    }
 }
 /*
     #[test]
@@ -33,7 +33,7 @@ pub(crate) fn ncd(e: f32) -> f32 {
 }
 
 pub(crate) fn npd(e: f32) -> f32 {
-    C * (0.5 * e * e).exp()
+    C * (-0.5 * e * e).exp()
 }
 
 /// Calculate call price of an option with dividends
@@ -131,7 +131,7 @@ pub fn gamma(
     let d1 = 1.0 / rd * (ssln + (il + vs2) * years_to_expiry);
     let v = npd(d1);
     let la = (-dividend_yield * years_to_expiry).exp();
-    -la * v / (spot * volatility * d)
+    la * v / (spot * volatility * d)
 }
 
 /// Calculate vega of an option with dividends
@@ -539,6 +539,46 @@ pub fn american_put(
     v[0]
 }
 
+/// Cox-Ross-Rubinstein binomial pricing of an American option
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Unlike `american_put` the number of steps is configurable, a few thousand steps gives a reference
+/// price accurate to around a cent
+#[allow(clippy::too_many_arguments)]
+pub fn american_binomial(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    steps: usize,
+) -> f32 {
+    let steps = steps.max(1);
+    let delta_t = (years_to_expiry / steps as f32) as f64;
+    let up = (volatility as f64 * delta_t.sqrt()).exp();
+    let down = 1.0 / up;
+    let growth = ((risk_free_rate - dividend_yield) as f64 * delta_t).exp();
+    let discount = (-risk_free_rate as f64 * delta_t).exp();
+    let pu = (growth - down) / (up - down);
+    let pd = 1.0 - pu;
+    let exercise = |s: f64| match option_dir {
+        OptionDir::CALL => (s - strike as f64).max(0.0),
+        OptionDir::PUT => (strike as f64 - s).max(0.0),
+    };
+    let spot = spot as f64;
+    let mut v: Vec<f64> = (0..=steps)
+        .map(|j| exercise(spot * up.powi(2 * j as i32 - steps as i32)))
+        .collect();
+    for i in (0..steps).rev() {
+        for j in 0..=i {
+            let continuation = discount * (pd * v[j] + pu * v[j + 1]);
+            v[j] = continuation.max(exercise(spot * up.powi(2 * j as i32 - i as i32)));
+        }
+    }
+    v[0] as f32
+}
+
 /// Calculate the call strike from a delta value
 pub fn call_strike_from_delta(
     delta: f32,
@@ -648,7 +688,7 @@ mod tests {
             volatility,
             dividend_yield,
         );
-        assert!((vega - 12.254598).abs() < 0.00001);
+        assert!((vega - 12.25168).abs() < 0.0001);
 
         let gamma = gamma(
             spot,
@@ -658,7 +698,7 @@ mod tests {
             volatility,
             dividend_yield,
         );
-        assert!((gamma - -0.064336635).abs() < 0.00001);
+        assert!((gamma - 0.06432132).abs() < 0.00001);
 
         let call_t = call_theta(
             spot,
@@ -676,8 +716,8 @@ mod tests {
             volatility,
             dividend_yield,
         );
-        assert!((call_t - -11.346551).abs() < 0.00001);
-        assert!((put_t - -14.326603).abs() < 0.00001);
+        assert!((call_t - -11.343489).abs() < 0.0001);
+        assert!((put_t - -14.323542).abs() < 0.0001);
 
         let call_r = call_rho(
             spot,
@@ -700,6 +740,17 @@ mod tests {
         assert!((put_r - -5.045131).abs() < 0.00001);
     }
 
+    #[test]
+    fn american_binomial_reference() {
+        // First case of Longstaff & Schwartz (2001) table 1, the converged tree value is 4.487
+        let put = american_binomial(OptionDir::PUT, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0, 2000);
+        assert!((put - 4.487).abs() < 0.01);
+        // Without dividends an American call is never exercised early
+        let call = american_binomial(OptionDir::CALL, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0, 2000);
+        let european = bs_price(OptionDir::CALL, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0);
+        assert!((call - european).abs() < 0.01);
+    }
+
     #[test]
     fn cdf_f32_single() {
         let now = std::time::Instant::now();
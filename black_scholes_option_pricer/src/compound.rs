@@ -0,0 +1,371 @@
+//! Options whose payoff is decided part way through their life
+//!
+//! * Geske (1979) compound options, a call or put expiring at the first date on a call or put expiring at the
+//!   second. The outer option is exercised when the underlying is beyond the critical price at which the inner
+//!   option is worth the outer strike, found with Brent's method, and the price is in terms of the bivariate normal
+//! * Rubinstein (1991) simple choosers, where the holder picks a call or a put with the same strike and expiry at
+//!   the choice date, and complex choosers, where the call and the put have their own strikes and expiries
+//! * Rubinstein (1990) forward start options, struck at a proportion of the spot on the start date, and cliquets
+//!   made of a strip of consecutive forward starts that each reset the strike to the spot of the previous period
+//!
+//! All are computed in f64 and returned as f32.
+use crate::bs::OptionDir;
+use crate::normal::{bivariate_ncd, european, ncd_f64, sign};
+use crate::solver::brent;
+use wasm_bindgen::prelude::*;
+
+const CRITICAL_TOLERANCE: f64 = 1.0e-10;
+const CRITICAL_MAX_ITER: usize = 200;
+
+/// Root of an increasing or decreasing function of the spot, searched for by doubling away from `scale`. When
+/// there is no root the end of the search where the function is closest to zero is returned
+fn critical_price<F: Fn(f64) -> f64>(f: F, scale: f64) -> f64 {
+    let (lower, mut upper) = (scale * 1.0e-6, scale);
+    while f(lower) * f(upper) > 0.0 && upper < scale * 1.0e6 {
+        upper *= 2.0;
+    }
+    brent(
+        &f,
+        lower,
+        upper,
+        CRITICAL_TOLERANCE * scale,
+        CRITICAL_MAX_ITER,
+    )
+    .unwrap_or(if f(lower).abs() < f(upper).abs() {
+        lower
+    } else {
+        upper
+    })
+}
+
+/// Compound option price, an option expiring at `outer_expiry` with strike `outer_strike` on an option expiring at
+/// `inner_expiry` with strike `inner_strike`
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn compound_price(
+    outer_dir: OptionDir,
+    inner_dir: OptionDir,
+    spot: f32,
+    outer_strike: f32,
+    inner_strike: f32,
+    outer_expiry: f32,
+    inner_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let (eta, phi) = (sign(outer_dir), sign(inner_dir));
+    let (s, k1, k2) = (spot as f64, outer_strike as f64, inner_strike as f64);
+    let (t1, t2) = (outer_expiry as f64, inner_expiry as f64);
+    let (r, v, q) = (
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    );
+    let critical = critical_price(|x| european(phi, x, k2, t2 - t1, r, v, q) - k1, k2);
+    let y1 = ((s / critical).ln() + (r - q + v * v / 2.0) * t1) / (v * t1.sqrt());
+    let y2 = y1 - v * t1.sqrt();
+    let z1 = ((s / k2).ln() + (r - q + v * v / 2.0) * t2) / (v * t2.sqrt());
+    let z2 = z1 - v * t2.sqrt();
+    let rho = (t1 / t2).sqrt();
+    (eta * (phi * s * (-q * t2).exp() * bivariate_ncd(phi * z1, eta * phi * y1, eta * rho)
+        - phi * k2 * (-r * t2).exp() * bivariate_ncd(phi * z2, eta * phi * y2, eta * rho)
+        - k1 * (-r * t1).exp() * ncd_f64(eta * phi * y2))) as f32
+}
+
+/// Simple chooser price, the better of a call and a put with the same strike and expiry chosen at `choice_time`
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn chooser_price(
+    spot: f32,
+    strike: f32,
+    choice_time: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let (s, k) = (spot as f64, strike as f64);
+    let (t1, t) = (choice_time as f64, years_to_expiry as f64);
+    let (r, v, q) = (
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    );
+    // By parity at the choice date the chooser is the call plus a put to the choice date on the forward strike
+    let call = european(1.0, s, k, t, r, v, q);
+    if t1 <= 0.0 {
+        return call.max(european(-1.0, s, k, t, r, v, q)) as f32;
+    }
+    let forward_strike = k * (-(r - q) * (t - t1)).exp();
+    (call + (-q * (t - t1)).exp() * european(-1.0, s, forward_strike, t1, r, v, q)) as f32
+}
+
+/// Complex chooser price, choosing at `choice_time` between a call with its own strike and expiry and a put with
+/// its own strike and expiry
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn complex_chooser_price(
+    spot: f32,
+    call_strike: f32,
+    put_strike: f32,
+    choice_time: f32,
+    call_expiry: f32,
+    put_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let (s, kc, kp) = (spot as f64, call_strike as f64, put_strike as f64);
+    let (t, tc, tp) = (choice_time as f64, call_expiry as f64, put_expiry as f64);
+    let (r, v, q) = (
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    );
+    // The spot at the choice date above which the call is worth more than the put
+    let critical = critical_price(
+        |x| european(1.0, x, kc, tc - t, r, v, q) - european(-1.0, x, kp, tp - t, r, v, q),
+        kc,
+    );
+    let d1 = ((s / critical).ln() + (r - q + v * v / 2.0) * t) / (v * t.sqrt());
+    let d2 = d1 - v * t.sqrt();
+    let y1 = ((s / kc).ln() + (r - q + v * v / 2.0) * tc) / (v * tc.sqrt());
+    let y2 = ((s / kp).ln() + (r - q + v * v / 2.0) * tp) / (v * tp.sqrt());
+    let (rho1, rho2) = ((t / tc).sqrt(), (t / tp).sqrt());
+    (s * (-q * tc).exp() * bivariate_ncd(d1, y1, rho1)
+        - kc * (-r * tc).exp() * bivariate_ncd(d2, y1 - v * tc.sqrt(), rho1)
+        - s * (-q * tp).exp() * bivariate_ncd(-d1, -y2, rho2)
+        + kp * (-r * tp).exp() * bivariate_ncd(-d2, -y2 + v * tp.sqrt(), rho2)) as f32
+}
+
+/// Forward start option price, starting at `start_time` with the strike set to `moneyness` times the spot then
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn forward_start_price(
+    option_dir: OptionDir,
+    spot: f32,
+    moneyness: f32,
+    start_time: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let (t1, t) = (start_time as f64, years_to_expiry as f64);
+    let (r, v, q) = (
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    );
+    // Black Scholes is homogeneous in the spot and the strike, so the option is a fixed number of shares at the start
+    let per_share = european(sign(option_dir), 1.0, moneyness as f64, t - t1, r, v, q);
+    (spot as f64 * (-q * t1).exp() * per_share) as f32
+}
+
+/// Cliquet price, a strip of `periods` equal forward starts to expiry, each struck at `moneyness` times the spot at
+/// the end of the previous period and paying out at the end of its own
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn cliquet_price(
+    option_dir: OptionDir,
+    spot: f32,
+    moneyness: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    periods: usize,
+) -> f32 {
+    let dt = years_to_expiry as f64 / periods as f64;
+    let (r, v, q) = (
+        risk_free_rate as f64,
+        volatility as f64,
+        dividend_yield as f64,
+    );
+    let per_share = european(sign(option_dir), 1.0, moneyness as f64, dt, r, v, q);
+    let shares: f64 = (0..periods).map(|i| (-q * dt * i as f64).exp()).sum();
+    (spot as f64 * shares * per_share) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+    use crate::monte_carlo::Rng;
+    use crate::normal::inverse_ncd;
+
+    #[test]
+    fn compound_reference_and_parity() {
+        // Haug, put on call
+        let price = compound_price(
+            OptionDir::PUT,
+            OptionDir::CALL,
+            500.0,
+            50.0,
+            520.0,
+            0.25,
+            0.5,
+            0.08,
+            0.35,
+            0.03,
+        );
+        assert!((price - 21.1965).abs() < 2.0e-3);
+
+        // A call less a put on the same option is the option less the discounted outer strike
+        for inner in [OptionDir::CALL, OptionDir::PUT] {
+            for (spot, k1, k2) in [(100.0, 5.0, 100.0), (90.0, 2.0, 110.0), (120.0, 12.0, 95.0)] {
+                let call = compound_price(
+                    OptionDir::CALL,
+                    inner,
+                    spot,
+                    k1,
+                    k2,
+                    0.3,
+                    1.0,
+                    0.05,
+                    0.25,
+                    0.02,
+                );
+                let put = compound_price(
+                    OptionDir::PUT,
+                    inner,
+                    spot,
+                    k1,
+                    k2,
+                    0.3,
+                    1.0,
+                    0.05,
+                    0.25,
+                    0.02,
+                );
+                let option = bs_price(inner, spot, k2, 1.0, 0.05, 0.25, 0.02);
+                let parity = option - k1 * (-0.05f32 * 0.3).exp();
+                assert!((call - put - parity).abs() < 2.0e-3);
+            }
+        }
+
+        // A call with a zero strike on an option is the option
+        let call = compound_price(
+            OptionDir::CALL,
+            OptionDir::PUT,
+            100.0,
+            0.0,
+            105.0,
+            0.5,
+            1.0,
+            0.05,
+            0.25,
+            0.0,
+        );
+        let put = bs_price(OptionDir::PUT, 100.0, 105.0, 1.0, 0.05, 0.25, 0.0);
+        assert!((call - put).abs() < 2.0e-3);
+    }
+
+    #[test]
+    fn compound_against_monte_carlo() {
+        let (s, k1, k2, t1, t2, r, v, q) = (
+            100.0f64, 6.0, 100.0, 0.4f64, 1.0f64, 0.05f64, 0.3f64, 0.01f64,
+        );
+        let mut rng = Rng::new(5);
+        let (mut call_on_call, mut put_on_put) = (0.0, 0.0);
+        let n = 200_000;
+        for _ in 0..n {
+            let z = inverse_ncd(rng.uniform());
+            for sign in [1.0, -1.0] {
+                let x = s * ((r - q - v * v / 2.0) * t1 + sign * v * t1.sqrt() * z).exp();
+                let call = european(1.0, x, k2, t2 - t1, r, v, q);
+                let put = european(-1.0, x, k2, t2 - t1, r, v, q);
+                call_on_call += (call - k1).max(0.0);
+                put_on_put += (k1 - put).max(0.0);
+            }
+        }
+        let discount = (-r * t1).exp() / (2 * n) as f64;
+        let price = |outer, inner| {
+            compound_price(
+                outer, inner, s as f32, k1 as f32, k2 as f32, t1 as f32, t2 as f32, r as f32,
+                v as f32, q as f32,
+            )
+        };
+        let mc = (call_on_call * discount) as f32;
+        assert!((price(OptionDir::CALL, OptionDir::CALL) - mc).abs() < 0.02);
+        let mc = (put_on_put * discount) as f32;
+        assert!((price(OptionDir::PUT, OptionDir::PUT) - mc).abs() < 0.02);
+    }
+
+    #[test]
+    fn choosers() {
+        // Haug references
+        let simple = chooser_price(50.0, 50.0, 0.25, 0.5, 0.08, 0.25, 0.0);
+        assert!((simple - 6.1071).abs() < 1.0e-3);
+        let complex = complex_chooser_price(50.0, 55.0, 48.0, 0.25, 0.5, 0.5833, 0.1, 0.35, 0.05);
+        assert!((complex - 6.0508).abs() < 2.0e-3);
+
+        // Choosing now takes the better option and choosing at expiry is a straddle
+        let call = bs_price(OptionDir::CALL, 100.0, 95.0, 1.0, 0.05, 0.2, 0.02);
+        let put = bs_price(OptionDir::PUT, 100.0, 95.0, 1.0, 0.05, 0.2, 0.02);
+        assert!(
+            (chooser_price(100.0, 95.0, 0.0, 1.0, 0.05, 0.2, 0.02) - call.max(put)).abs() < 1.0e-4
+        );
+        assert!(
+            (chooser_price(100.0, 95.0, 1.0, 1.0, 0.05, 0.2, 0.02) - call - put).abs() < 1.0e-4
+        );
+
+        // With a common strike and expiry the complex chooser is the simple one, and either is worth at least the
+        // better of the call and the put
+        for (spot, strike, t1) in [(100.0, 95.0, 0.3), (90.0, 100.0, 0.6), (110.0, 100.0, 0.1)] {
+            let simple = chooser_price(spot, strike, t1, 1.0, 0.05, 0.2, 0.02);
+            let complex =
+                complex_chooser_price(spot, strike, strike, t1, 1.0, 1.0, 0.05, 0.2, 0.02);
+            assert!((simple - complex).abs() < 2.0e-3);
+            let call = bs_price(OptionDir::CALL, spot, strike, 1.0, 0.05, 0.2, 0.02);
+            let put = bs_price(OptionDir::PUT, spot, strike, 1.0, 0.05, 0.2, 0.02);
+            assert!(simple >= call.max(put) - 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn forward_starts() {
+        // Starting now is a vanilla, and a single period cliquet is the same
+        let call = bs_price(OptionDir::CALL, 100.0, 105.0, 1.0, 0.05, 0.2, 0.02);
+        let forward = forward_start_price(OptionDir::CALL, 100.0, 1.05, 0.0, 1.0, 0.05, 0.2, 0.02);
+        assert!((forward - call).abs() < 1.0e-4);
+        let cliquet = cliquet_price(OptionDir::CALL, 100.0, 1.05, 1.0, 0.05, 0.2, 0.02, 1);
+        assert!((cliquet - call).abs() < 1.0e-4);
+
+        // Parity between forward start calls and puts, the difference being a forward struck at the start
+        let (s, alpha, t1, t, r, v, q) =
+            (100.0f32, 0.95f32, 0.25f32, 1.0f32, 0.05f32, 0.3f32, 0.02f32);
+        let call = forward_start_price(OptionDir::CALL, s, alpha, t1, t, r, v, q);
+        let put = forward_start_price(OptionDir::PUT, s, alpha, t1, t, r, v, q);
+        let forward = s * (-q * t1).exp() * ((-q * (t - t1)).exp() - alpha * (-r * (t - t1)).exp());
+        assert!((call - put - forward).abs() < 1.0e-4);
+
+        // Each cliquet period is a forward start from the previous reset
+        let cliquet = cliquet_price(OptionDir::PUT, s, alpha, t, r, v, q, 4);
+        let strip: f32 = (0..4)
+            .map(|i| {
+                forward_start_price(
+                    OptionDir::PUT,
+                    s,
+                    alpha,
+                    i as f32 * 0.25,
+                    (i + 1) as f32 * 0.25,
+                    r,
+                    v,
+                    q,
+                )
+            })
+            .sum();
+        assert!((cliquet - strip).abs() < 1.0e-4);
+    }
+}
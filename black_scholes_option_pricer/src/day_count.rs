@@ -0,0 +1,352 @@
+//! Calendar dates, holiday calendars and day count conventions to turn expiry dates into years to expiry
+//!
+//! * ACT/365F counts calendar time over 365 days a year
+//! * ACT/360 counts calendar time over 360 days a year
+//! * BUS/252 counts business days, weekdays which are not holidays of the calendar, over 252 a year
+//!
+//! Time to expiry runs from a valuation timestamp to an expiry date at a time of day, so options can be valued
+//! intraday up to their expiry time. Within a day time runs uniformly over the 24 hours, and under BUS/252 only
+//! on business days. Timestamps are seconds since 1970-01-01 00:00 and times of day seconds since midnight, both
+//! in the time zone of the expiry.
+use std::collections::BTreeSet;
+use wasm_bindgen::prelude::*;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// A day count convention
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DayCount {
+    Act365F = 0,
+    Act360 = 1,
+    Bus252 = 2,
+}
+
+/// A day of the proleptic Gregorian calendar. Days beyond the end of a month roll into the next one
+#[wasm_bindgen]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Parse an ISO 8601 date such as 2024-03-15
+    pub fn parse(date: &str) -> Option<Date> {
+        let mut parts = date.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month: u32 = parts.next()?.parse().ok()?;
+        let day: u32 = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+}
+
+#[wasm_bindgen]
+impl Date {
+    #[wasm_bindgen(constructor)]
+    pub fn new(year: i32, month: u32, day: u32) -> Date {
+        Date::from_days(days_from_civil(year, month, day))
+    }
+
+    /// The date a number of days after 1970-01-01
+    pub fn from_days(days: i32) -> Date {
+        // Hinnant's civil from days, with eras of 400 years starting on 1 March
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = yoe + era * 400 + (month <= 2) as i32;
+        Date { year, month, day }
+    }
+
+    /// The date of a timestamp
+    pub fn from_timestamp(timestamp: f64) -> Date {
+        Date::from_days((timestamp / SECONDS_PER_DAY).floor() as i32)
+    }
+
+    /// Days after 1970-01-01
+    pub fn days(&self) -> i32 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    /// Day of the week from 0 for Monday to 6 for Sunday
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (self.days() + 3).rem_euclid(7) as u32
+    }
+
+    pub fn is_weekend(&self) -> bool {
+        self.weekday() >= 5
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Hinnant's days from civil, counting days beyond the end of the month into the next
+fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    // Months beyond December roll into the following years
+    let year = year + (month.max(1) as i32 - 1) / 12;
+    let month = (month.max(1) - 1) % 12 + 1;
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month as i32 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i32 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Holidays on which an exchange is closed, besides weekends
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct HolidayCalendar {
+    holidays: BTreeSet<i32>,
+}
+
+#[wasm_bindgen]
+impl HolidayCalendar {
+    /// A calendar with weekends only
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> HolidayCalendar {
+        HolidayCalendar::default()
+    }
+
+    pub fn add_holiday(&mut self, date: Date) {
+        self.holidays.insert(date.days());
+    }
+
+    pub fn is_business_day(&self, date: Date) -> bool {
+        !date.is_weekend() && !self.holidays.contains(&date.days())
+    }
+
+    /// Business days from the start date up to but excluding the end date, negative when the end is first
+    pub fn business_days(&self, start: Date, end: Date) -> i32 {
+        let (from, to) = (start.days().min(end.days()), start.days().max(end.days()));
+        // Whole weeks have five weekdays, then count the days left over one by one
+        let weeks = (to - from) / 7;
+        let mut count = weeks * 5;
+        for day in from + weeks * 7..to {
+            count += (Date::from_days(day).weekday() < 5) as i32;
+        }
+        count -= self
+            .holidays
+            .range(from..to)
+            .filter(|&&day| Date::from_days(day).weekday() < 5)
+            .count() as i32;
+        if end < start {
+            -count
+        } else {
+            count
+        }
+    }
+}
+
+/// Year fraction between two dates under a day count convention
+#[wasm_bindgen]
+pub fn year_fraction(
+    day_count: DayCount,
+    start: Date,
+    end: Date,
+    calendar: &HolidayCalendar,
+) -> f32 {
+    match day_count {
+        DayCount::Act365F => (end.days() - start.days()) as f32 / 365.0,
+        DayCount::Act360 => (end.days() - start.days()) as f32 / 360.0,
+        DayCount::Bus252 => calendar.business_days(start, end) as f32 / 252.0,
+    }
+}
+
+/// Years to expiry from a valuation timestamp to the expiry time of day on the expiry date, negative once expired
+#[wasm_bindgen]
+pub fn time_to_expiry(
+    day_count: DayCount,
+    valuation_timestamp: f64,
+    expiry: Date,
+    expiry_time: u32,
+    calendar: &HolidayCalendar,
+) -> f32 {
+    let expiry_timestamp = expiry.days() as f64 * SECONDS_PER_DAY + expiry_time as f64;
+    match day_count {
+        DayCount::Act365F => {
+            ((expiry_timestamp - valuation_timestamp) / SECONDS_PER_DAY / 365.0) as f32
+        }
+        DayCount::Act360 => {
+            ((expiry_timestamp - valuation_timestamp) / SECONDS_PER_DAY / 360.0) as f32
+        }
+        DayCount::Bus252 => {
+            // Whole business days between the dates, less the part of the valuation day gone and plus the part
+            // of the expiry day before the expiry time, when they are business days
+            let valuation = Date::from_timestamp(valuation_timestamp);
+            let part = |date: Date, seconds: f64| {
+                if calendar.is_business_day(date) {
+                    seconds / SECONDS_PER_DAY
+                } else {
+                    0.0
+                }
+            };
+            let gone = valuation_timestamp - valuation.days() as f64 * SECONDS_PER_DAY;
+            let days = calendar.business_days(valuation, expiry) as f64 - part(valuation, gone)
+                + part(expiry, expiry_time as f64);
+            (days / 252.0) as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs::OptionDir;
+    use crate::bs_single::bs_price;
+
+    #[test]
+    fn dates() {
+        assert_eq!(Date::new(1970, 1, 1).days(), 0);
+        assert_eq!(Date::new(2000, 3, 1).days(), 11_017);
+        assert_eq!(Date::new(1969, 12, 31).days(), -1);
+        for days in -800_000..800_000 {
+            let date = Date::from_days(days);
+            assert_eq!(date.days(), days);
+            assert_eq!(Date::new(date.year, date.month, date.day), date);
+        }
+        // Days beyond the end of the month and months beyond the year roll over
+        assert_eq!(Date::new(2023, 2, 29), Date::new(2023, 3, 1));
+        assert_eq!(Date::new(2023, 13, 1), Date::new(2024, 1, 1));
+        assert_eq!(Date::new(2024, 1, 1).weekday(), 0);
+        assert!(Date::new(2024, 1, 6).is_weekend());
+        assert_eq!(Date::parse("2024-02-29"), Some(Date::new(2024, 2, 29)));
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2024-1"), None);
+        assert_eq!(
+            Date::from_timestamp(1_704_067_200.0 + 3600.0),
+            Date::new(2024, 1, 1)
+        );
+    }
+
+    #[test]
+    fn day_counts() {
+        let mut calendar = HolidayCalendar::new();
+        calendar.add_holiday(Date::new(2024, 1, 1));
+        calendar.add_holiday(Date::new(2024, 1, 15));
+        // A holiday on a weekend changes nothing
+        calendar.add_holiday(Date::new(2024, 1, 20));
+        let (start, end) = (Date::new(2024, 1, 1), Date::new(2024, 2, 1));
+        assert_eq!(calendar.business_days(start, end), 21);
+        assert_eq!(calendar.business_days(end, start), -21);
+        assert_eq!(HolidayCalendar::new().business_days(start, end), 23);
+        assert!(!calendar.is_business_day(Date::new(2024, 1, 15)));
+        assert!(
+            (year_fraction(DayCount::Bus252, start, end, &calendar) - 21.0 / 252.0).abs() < 1.0e-7
+        );
+        assert!(
+            (year_fraction(DayCount::Act360, start, end, &calendar) - 31.0 / 360.0).abs() < 1.0e-7
+        );
+        let year = year_fraction(DayCount::Act365F, start, Date::new(2025, 1, 1), &calendar);
+        assert!((year - 366.0 / 365.0).abs() < 1.0e-7);
+        // Against counting day by day
+        for offset in 0..40 {
+            let end = Date::from_days(start.days() + offset);
+            let count = (start.days()..end.days())
+                .filter(|&d| calendar.is_business_day(Date::from_days(d)))
+                .count() as i32;
+            assert_eq!(calendar.business_days(start, end), count);
+        }
+    }
+
+    #[test]
+    fn intraday() {
+        let calendar = HolidayCalendar::new();
+        let timestamp =
+            |date: Date, hours: f64| date.days() as f64 * SECONDS_PER_DAY + hours * 3600.0;
+        let (tuesday, friday, monday) = (
+            Date::new(2024, 1, 2),
+            Date::new(2024, 1, 5),
+            Date::new(2024, 1, 8),
+        );
+        let four_pm = 16 * 3600;
+        // Four hours on the day of expiry
+        let act = time_to_expiry(
+            DayCount::Act365F,
+            timestamp(tuesday, 12.0),
+            tuesday,
+            four_pm,
+            &calendar,
+        );
+        assert!((act - 4.0 / 24.0 / 365.0).abs() < 1.0e-8);
+        let bus = time_to_expiry(
+            DayCount::Bus252,
+            timestamp(tuesday, 12.0),
+            tuesday,
+            four_pm,
+            &calendar,
+        );
+        assert!((bus - 4.0 / 24.0 / 252.0).abs() < 1.0e-8);
+        // Over a weekend calendar time counts three days and business time one
+        let act = time_to_expiry(
+            DayCount::Act365F,
+            timestamp(friday, 16.0),
+            monday,
+            four_pm,
+            &calendar,
+        );
+        assert!((act - 3.0 / 365.0).abs() < 1.0e-7);
+        let bus = time_to_expiry(
+            DayCount::Bus252,
+            timestamp(friday, 16.0),
+            monday,
+            four_pm,
+            &calendar,
+        );
+        assert!((bus - 1.0 / 252.0).abs() < 1.0e-7);
+        let saturday = time_to_expiry(
+            DayCount::Bus252,
+            timestamp(Date::new(2024, 1, 6), 9.0),
+            monday,
+            four_pm,
+            &calendar,
+        );
+        assert!((saturday - 16.0 / 24.0 / 252.0).abs() < 1.0e-7);
+        let act = time_to_expiry(
+            DayCount::Act360,
+            timestamp(monday, 20.0),
+            monday,
+            four_pm,
+            &calendar,
+        );
+        assert!(act < 0.0);
+
+        // 20 business days to expiry at midnight is the 20/252 the pricers expect
+        let expiry = Date::new(2024, 1, 30);
+        let t = time_to_expiry(
+            DayCount::Bus252,
+            timestamp(tuesday, 0.0),
+            expiry,
+            0,
+            &calendar,
+        );
+        assert!((t - 20.0 / 252.0).abs() < 1.0e-7);
+        let price = bs_price(OptionDir::CALL, 100.0, 100.0, t, 0.03, 0.2, 0.0);
+        assert!(
+            (price - bs_price(OptionDir::CALL, 100.0, 100.0, 20.0 / 252.0, 0.03, 0.2, 0.0)).abs()
+                < 1.0e-5
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Risk neutral distribution of the underlying at expiry implied by a smile
+//!
+//! Breeden and Litzenberger (1978): the discounted density is the second derivative of the call price with respect
+//! to the strike, and the distribution function is one plus the discounted first derivative. Options are priced in
+//! batch at the strikes of the grid with their smile vols, and the derivatives are taken by finite differences on
+//! the grid, so the smile should be smooth and the grid fine compared to the width of the distribution.
+//!
+//! The implied moments are those of the log return to expiry, integrated over the density on the grid, so for a
+//! flat smile the volatility is the implied vol and the skewness and excess kurtosis are zero.
+//!
+//! For a single strike or barrier the probabilities of finishing in the money and of touching before expiry are
+//! given under Black Scholes, using the smile vol at that strike as the trader sees fit.
+use crate::bs::{bs_call, bs_put, OptionDir};
+use crate::normal::ncd_f64;
+use wasm_bindgen::prelude::*;
+
+/// Density and distribution function of the underlying at expiry at the strikes of the grid
+#[derive(Debug)]
+pub struct Density {
+    pub strikes: Vec<f32>,
+    pub pdf: Vec<f32>,
+    pub cdf: Vec<f32>,
+}
+
+/// Moments of the log return to expiry implied by a density
+#[derive(Debug, Copy, Clone)]
+pub struct DensityMoments {
+    /// Probability mass captured by the grid, close to 1 when the grid covers the distribution
+    pub mass: f32,
+    /// Mean of the underlying at expiry
+    pub forward: f32,
+    /// Annualised standard deviation of the log return
+    pub volatility: f32,
+    pub skewness: f32,
+    /// Excess kurtosis, 0 for the normal distribution
+    pub kurtosis: f32,
+}
+
+/// Risk neutral density from a smile of vols at ascending strikes. The end points of the grid take the value of
+/// their neighbours
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn implied_density(
+    spot: f32,
+    strikes: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatilities: &[f32],
+    dividend_yield: f32,
+) -> Density {
+    let n = strikes.len();
+    let (spots, t, r, q) = (
+        vec![spot; n],
+        vec![years_to_expiry; n],
+        vec![risk_free_rate; n],
+        vec![dividend_yield; n],
+    );
+    let calls = bs_call(&spots, strikes, &t, &r, volatilities, &q);
+    let puts = bs_put(&spots, strikes, &t, &r, volatilities, &q);
+    let (t, r, q) = (
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        dividend_yield as f64,
+    );
+    let forward = spot as f64 * ((r - q) * t).exp();
+    // Out of the money prices keep the rounding of the f32 pricer small, puts below the forward are turned into
+    // calls by parity, which is linear in the strike
+    let calls: Vec<f64> = (0..n)
+        .map(|i| {
+            let k = strikes[i] as f64;
+            if k < forward {
+                puts[i] as f64 + (forward - k) * (-r * t).exp()
+            } else {
+                calls[i] as f64
+            }
+        })
+        .collect();
+    let growth = (r * t).exp();
+    let (mut pdf, mut cdf) = (vec![0.0; n], vec![0.0; n]);
+    for i in 1..n.saturating_sub(1) {
+        let (h0, h1) = (
+            (strikes[i] - strikes[i - 1]) as f64,
+            (strikes[i + 1] - strikes[i]) as f64,
+        );
+        let (c0, c1, c2) = (calls[i - 1], calls[i], calls[i + 1]);
+        let (slope0, slope1) = ((c1 - c0) / h0, (c2 - c1) / h1);
+        pdf[i] = (growth * 2.0 * (slope1 - slope0) / (h0 + h1)) as f32;
+        // The slope at the strike, weighting each side by the distance to the other
+        cdf[i] = (1.0 + growth * (slope0 * h1 + slope1 * h0) / (h0 + h1)) as f32;
+    }
+    if n > 2 {
+        pdf[0] = pdf[1];
+        cdf[0] = cdf[1];
+        pdf[n - 1] = pdf[n - 2];
+        cdf[n - 1] = cdf[n - 2];
+    }
+    Density {
+        strikes: strikes.to_vec(),
+        pdf,
+        cdf,
+    }
+}
+
+/// Moments of the log return implied by a density, integrated by the trapezoidal rule and normalised by the mass
+/// on the grid
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+pub fn density_moments(density: &Density, years_to_expiry: f32) -> DensityMoments {
+    let integrate = |f: &dyn Fn(f64) -> f64| -> f64 {
+        density
+            .strikes
+            .windows(2)
+            .zip(density.pdf.windows(2))
+            .map(|(k, p)| {
+                let (k0, k1) = (k[0] as f64, k[1] as f64);
+                (k1 - k0) * (f(k0) * p[0] as f64 + f(k1) * p[1] as f64) / 2.0
+            })
+            .sum()
+    };
+    let mass = integrate(&|_| 1.0);
+    let forward = integrate(&|k| k) / mass;
+    let mean = integrate(&|k| (k / forward).ln()) / mass;
+    let central = |power: i32| integrate(&|k| ((k / forward).ln() - mean).powi(power)) / mass;
+    let variance = central(2);
+    DensityMoments {
+        mass: mass as f32,
+        forward: forward as f32,
+        volatility: (variance / years_to_expiry as f64).sqrt() as f32,
+        skewness: (central(3) / variance.powf(1.5)) as f32,
+        kurtosis: (central(4) / (variance * variance) - 3.0) as f32,
+    }
+}
+
+/// Risk neutral probability of finishing in the money, N(d2) for a call and N(-d2) for a put
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn probability_itm(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    let (t, v) = (years_to_expiry as f64, volatility as f64);
+    let drift = risk_free_rate as f64 - dividend_yield as f64 - v * v / 2.0;
+    let d2 = ((spot as f64 / strike as f64).ln() + drift * t) / (v * t.sqrt());
+    ncd_f64(phi * d2) as f32
+}
+
+/// Risk neutral probability of the underlying touching the barrier, above or below the spot, before expiry under
+/// continuous monitoring
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn probability_touch(
+    spot: f32,
+    barrier: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> f32 {
+    let (t, v) = (years_to_expiry as f64, volatility as f64);
+    // Distance to the barrier and drift of the log spot in units of vol
+    let b = (barrier as f64 / spot as f64).ln() / v;
+    if b == 0.0 {
+        return 1.0;
+    }
+    let nu = (risk_free_rate as f64 - dividend_yield as f64 - v * v / 2.0) / v;
+    let eta = b.signum();
+    (ncd_f64((-b.abs() + eta * nu * t) / t.sqrt())
+        + (2.0 * nu * b).exp() * ncd_f64((-b.abs() - eta * nu * t) / t.sqrt())) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monte_carlo::Rng;
+    use crate::normal::inverse_ncd;
+
+    fn grid() -> Vec<f32> {
+        (0..=560).map(|i| 20.0 + 0.5 * i as f32).collect()
+    }
+
+    #[test]
+    fn flat_smile_is_lognormal() {
+        let (s, t, r, v, q) = (100.0f32, 0.5f32, 0.03f32, 0.25f32, 0.01f32);
+        let strikes = grid();
+        let vols = vec![v; strikes.len()];
+        let density = implied_density(s, &strikes, t, r, &vols, q);
+        let sd = (v * t.sqrt()) as f64;
+        let mu = (s as f64).ln() + ((r - q) as f64 - sd * sd / 2.0 / t as f64) * t as f64;
+        for (i, &k) in strikes.iter().enumerate().step_by(20).skip(2) {
+            let k = k as f64;
+            let z = (k.ln() - mu) / sd;
+            let lognormal = (-z * z / 2.0).exp() / (k * sd * (2.0 * std::f64::consts::PI).sqrt());
+            assert!((density.pdf[i] as f64 - lognormal).abs() < 2.0e-4);
+            assert!((density.cdf[i] as f64 - ncd_f64(z)).abs() < 2.0e-3);
+            let itm = probability_itm(OptionDir::PUT, s, k as f32, t, r, v, q);
+            assert!((density.cdf[i] - itm).abs() < 2.0e-3);
+        }
+
+        let moments = density_moments(&density, t);
+        assert!((moments.mass - 1.0).abs() < 1.0e-3);
+        assert!((moments.forward - s * ((r - q) * t).exp()).abs() < 0.05);
+        assert!((moments.volatility - v).abs() < 2.0e-3);
+        assert!(moments.skewness.abs() < 0.02);
+        assert!(moments.kurtosis.abs() < 0.05);
+    }
+
+    #[test]
+    fn skewed_smile() {
+        let strikes = grid();
+        let vols: Vec<f32> = strikes
+            .iter()
+            .map(|&k| 0.25 - 0.1 * (k / 100.0).ln() + 0.05 * (k / 100.0).ln().powi(2))
+            .collect();
+        let density = implied_density(100.0, &strikes, 0.5, 0.03, &vols, 0.0);
+        // Up to the rounding of the f32 prices in the tails
+        assert!(density.pdf.iter().all(|&p| p > -5.0e-5));
+        assert!(density.cdf.windows(2).all(|c| c[1] >= c[0] - 1.0e-4));
+        let moments = density_moments(&density, 0.5);
+        assert!((moments.mass - 1.0).abs() < 5.0e-3);
+        assert!(moments.skewness < -0.2);
+        assert!(moments.kurtosis > 0.1);
+    }
+
+    #[test]
+    fn touch() {
+        // Without drift in the log spot the reflection principle makes touching twice as likely as finishing beyond
+        let (t, v) = (0.5, 0.3);
+        let r = v * v / 2.0;
+        for barrier in [80.0, 120.0] {
+            let dir = if barrier > 100.0 {
+                OptionDir::CALL
+            } else {
+                OptionDir::PUT
+            };
+            let itm = probability_itm(dir, 100.0, barrier, t, r, v, 0.0);
+            assert!((probability_touch(100.0, barrier, t, r, v, 0.0) - 2.0 * itm).abs() < 1.0e-5);
+        }
+        assert_eq!(probability_touch(100.0, 100.0, t, 0.05, v, 0.0), 1.0);
+
+        // With drift, against a finely monitored simulation
+        let (steps, paths) = (2000, 10_000);
+        let dt = t as f64 / steps as f64;
+        let drift = (0.08 - 0.01 - v as f64 * v as f64 / 2.0) * dt;
+        let mut rng = Rng::new(23);
+        let (mut up, mut down) = (0, 0);
+        for _ in 0..paths {
+            let (mut x, mut max, mut min) = (0.0f64, 0.0f64, 0.0f64);
+            for _ in 0..steps {
+                x += drift + v as f64 * dt.sqrt() * inverse_ncd(rng.uniform());
+                max = max.max(x);
+                min = min.min(x);
+            }
+            up += (max >= (115.0f64 / 100.0).ln()) as usize;
+            down += (min <= (90.0f64 / 100.0).ln()) as usize;
+        }
+        let touch_up = probability_touch(100.0, 115.0, t, 0.08, v, 0.01);
+        let touch_down = probability_touch(100.0, 90.0, t, 0.08, v, 0.01);
+        // Discrete monitoring misses some touches
+        assert!((touch_up - up as f32 / paths as f32).abs() < 0.025);
+        assert!((touch_down - down as f32 / paths as f32).abs() < 0.025);
+    }
+}
@@ -0,0 +1,639 @@
+//! Digital and gap options with analytic greeks
+//!
+//! Every option here pays, when it finishes in the money relative to the trigger strike, a fixed number of units
+//! of the underlying plus a fixed amount of cash:
+//!
+//! * cash-or-nothing pays the cash amount
+//! * asset-or-nothing pays one unit of the underlying
+//! * gap pays the spot less the strike for a call, the strike less the spot for a put, but is triggered by a
+//!   second strike. With the trigger equal to the strike it is a vanilla option
+//!
+//! so the price and greeks are linear combinations of those of an asset digital S exp(-qT) N(phi d1) and a cash
+//! digital exp(-rT) N(phi d2). Theta is the change in value per year as time passes, like the other greeks in the
+//! crate.
+use crate::bs::{Greeks, OptionDir};
+use crate::bs_f32x8_::{ncd_f32x8, npd_f32x8, Greek};
+use crate::bs_single::{ncd, npd};
+use bytemuck::cast;
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// Price and greeks of a single option
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone)]
+pub struct DigitalGreeks {
+    pub pv: f32,
+    pub delta: f32,
+    pub theta: f32,
+    pub gamma: f32,
+    pub rho: f32,
+    pub vega: f32,
+}
+
+/// `asset` units of the underlying plus `cash` paid when the option finishes beyond `trigger`
+#[allow(clippy::too_many_arguments)]
+fn digital(
+    option_dir: OptionDir,
+    asset: f32,
+    cash: f32,
+    s: f32,
+    trigger: f32,
+    t: f32,
+    r: f32,
+    v: f32,
+    q: f32,
+) -> DigitalGreeks {
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    let sd = v * t.sqrt();
+    let d1 = ((s / trigger).ln() + (r - q + v * v / 2.0) * t) / sd;
+    let d2 = d1 - sd;
+    let (asset_discount, cash_discount) = ((-q * t).exp(), (-r * t).exp());
+    // The asset digital and the cash digital, then their densities scaled by the sign of the option
+    let asset_pv = s * asset_discount * ncd(phi * d1);
+    let cash_pv = cash_discount * ncd(phi * d2);
+    let asset_n = phi * s * asset_discount * npd(d1);
+    let cash_n = phi * cash_discount * npd(d2);
+    // d d1 / dT and d d2 / dT
+    let dd1 = (r - q + v * v / 2.0) / sd - d1 / (2.0 * t);
+    let dd2 = (r - q - v * v / 2.0) / sd - d2 / (2.0 * t);
+    DigitalGreeks {
+        pv: asset * asset_pv + cash * cash_pv,
+        delta: asset * (asset_pv + asset_n / sd) / s + cash * cash_n / (s * sd),
+        theta: asset * (q * asset_pv - asset_n * dd1) + cash * (r * cash_pv - cash_n * dd2),
+        gamma: -(asset * asset_n * d2 + cash * cash_n * d1) / (s * s * sd * sd),
+        rho: asset * asset_n * t / sd + cash * (cash_n * t / sd - t * cash_pv),
+        vega: -(asset * asset_n * d2 + cash * cash_n * d1) / v,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn digital_f32x8(
+    option_dir: OptionDir,
+    asset: f32x8,
+    cash: f32x8,
+    s: f32x8,
+    trigger: f32x8,
+    t: f32x8,
+    r: f32x8,
+    v: f32x8,
+    q: f32x8,
+) -> Greek {
+    let phi = f32x8::splat(match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    });
+    let sd = v * t.sqrt();
+    let d1 = ((s / trigger).ln() + (r - q + v * v * 0.5) * t) / sd;
+    let d2 = d1 - sd;
+    let (asset_discount, cash_discount) = ((-q * t).exp(), (-r * t).exp());
+    let asset_pv = s * asset_discount * ncd_f32x8(phi * d1);
+    let cash_pv = cash_discount * ncd_f32x8(phi * d2);
+    let asset_n = phi * s * asset_discount * npd_f32x8(d1);
+    let cash_n = phi * cash_discount * npd_f32x8(d2);
+    let dd1 = (r - q + v * v * 0.5) / sd - d1 / (2.0 * t);
+    let dd2 = (r - q - v * v * 0.5) / sd - d2 / (2.0 * t);
+    Greek {
+        pv: asset * asset_pv + cash * cash_pv,
+        delta: asset * (asset_pv + asset_n / sd) / s + cash * cash_n / (s * sd),
+        theta: asset * (q * asset_pv - asset_n * dd1) + cash * (r * cash_pv - cash_n * dd2),
+        gamma: -(asset * asset_n * d2 + cash * cash_n * d1) / (s * s * sd * sd),
+        rho: asset * asset_n * t / sd + cash * (cash_n * t / sd - t * cash_pv),
+        vega: -(asset * asset_n * d2 + cash * cash_n * d1) / v,
+    }
+}
+
+/// Cash-or-nothing option paying `cash` when the spot finishes beyond the strike
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn cash_or_nothing(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    cash: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> DigitalGreeks {
+    digital(
+        option_dir,
+        0.0,
+        cash,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Asset-or-nothing option delivering one unit of the underlying when the spot finishes beyond the strike
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn asset_or_nothing(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> DigitalGreeks {
+    digital(
+        option_dir,
+        1.0,
+        0.0,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Gap option paying the vanilla payoff on `strike` when the spot finishes beyond `trigger`.
+/// The payoff can be negative when the strikes are on the wrong side of each other
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn gap(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    trigger: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+) -> DigitalGreeks {
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    digital(
+        option_dir,
+        phi,
+        -phi * strike,
+        spot,
+        trigger,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Greeks of arrays eight at a time, paying `asset` units plus `cash_scale * cash[i]` beyond `trigger[i]`
+#[allow(clippy::too_many_arguments)]
+fn batch_greeks(
+    option_dir: OptionDir,
+    asset: f32,
+    cash_scale: f32,
+    cash: &[f32],
+    spot: &[f32],
+    trigger: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Greeks {
+    let max_idx = spot.len();
+    let mut greeks = Greeks {
+        pv: Vec::with_capacity(max_idx + 8),
+        delta: Vec::with_capacity(max_idx + 8),
+        theta: Vec::with_capacity(max_idx + 8),
+        gamma: Vec::with_capacity(max_idx + 8),
+        rho: Vec::with_capacity(max_idx + 8),
+        vega: Vec::with_capacity(max_idx + 8),
+    };
+    for i in (0..max_idx).step_by(8) {
+        let cash = f32x8::from(&cash[i..std::cmp::min(max_idx, i + 8)]) * cash_scale;
+        let spot = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let trigger = f32x8::from(&trigger[i..std::cmp::min(max_idx, i + 8)]);
+        let years_to_expiry = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let risk_free_rate = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let volatility = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let dividend_yield = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let greek = digital_f32x8(
+            option_dir,
+            f32x8::splat(asset),
+            cash,
+            spot,
+            trigger,
+            years_to_expiry,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+        );
+        let pv: [f32; 8] = cast(greek.pv);
+        let delta: [f32; 8] = cast(greek.delta);
+        let theta: [f32; 8] = cast(greek.theta);
+        let gamma: [f32; 8] = cast(greek.gamma);
+        let rho: [f32; 8] = cast(greek.rho);
+        let vega: [f32; 8] = cast(greek.vega);
+        greeks.pv.extend(&pv);
+        greeks.delta.extend(&delta);
+        greeks.theta.extend(&theta);
+        greeks.gamma.extend(&gamma);
+        greeks.rho.extend(&rho);
+        greeks.vega.extend(&vega);
+    }
+    for values in [
+        &mut greeks.pv,
+        &mut greeks.delta,
+        &mut greeks.theta,
+        &mut greeks.gamma,
+        &mut greeks.rho,
+        &mut greeks.vega,
+    ] {
+        values.truncate(max_idx);
+    }
+    greeks
+}
+
+/// Calculate all the greeks of cash-or-nothing options in a single step. The results are at the same index as
+/// the inputs
+#[allow(clippy::too_many_arguments)]
+pub fn cash_or_nothing_greeks(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    cash: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Greeks {
+    batch_greeks(
+        option_dir,
+        0.0,
+        1.0,
+        cash,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Calculate all the greeks of asset-or-nothing options in a single step. The results are at the same index as
+/// the inputs
+pub fn asset_or_nothing_greeks(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Greeks {
+    batch_greeks(
+        option_dir,
+        1.0,
+        0.0,
+        strike,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Calculate all the greeks of gap options in a single step. The results are at the same index as the inputs
+#[allow(clippy::too_many_arguments)]
+pub fn gap_greeks(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    trigger: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Greeks {
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    batch_greeks(
+        option_dir,
+        phi,
+        -phi,
+        strike,
+        spot,
+        trigger,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+}
+
+/// Cash-or-nothing call pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn cash_or_nothing_call(
+    spot: &[f32],
+    strike: &[f32],
+    cash: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    cash_or_nothing_greeks(
+        OptionDir::CALL,
+        spot,
+        strike,
+        cash,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+/// Cash-or-nothing put pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn cash_or_nothing_put(
+    spot: &[f32],
+    strike: &[f32],
+    cash: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    cash_or_nothing_greeks(
+        OptionDir::PUT,
+        spot,
+        strike,
+        cash,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+/// Asset-or-nothing call pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn asset_or_nothing_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    asset_or_nothing_greeks(
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+/// Asset-or-nothing put pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn asset_or_nothing_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    asset_or_nothing_greeks(
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+/// Gap call pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn gap_call(
+    spot: &[f32],
+    strike: &[f32],
+    trigger: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    gap_greeks(
+        OptionDir::CALL,
+        spot,
+        strike,
+        trigger,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+/// Gap put pricing. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn gap_put(
+    spot: &[f32],
+    strike: &[f32],
+    trigger: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+) -> Vec<f32> {
+    gap_greeks(
+        OptionDir::PUT,
+        spot,
+        strike,
+        trigger,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    )
+    .pv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::{bs_price, delta};
+
+    #[test]
+    fn reference_values() {
+        // Haug, The Complete Guide to Option Pricing Formulas
+        let put = cash_or_nothing(OptionDir::PUT, 100.0, 80.0, 10.0, 0.75, 0.06, 0.35, 0.06);
+        assert!((put.pv - 2.6710).abs() < 0.001);
+        let call = gap(OptionDir::CALL, 50.0, 57.0, 50.0, 0.5, 0.09, 0.2, 0.0);
+        assert!((call.pv + 0.0053).abs() < 0.001);
+    }
+
+    #[test]
+    fn replication() {
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            for strike in [90.0, 100.0, 115.0] {
+                let vanilla = bs_price(dir, 100.0, strike, 0.5, 0.04, 0.25, 0.02);
+                let phi = if dir == OptionDir::CALL { 1.0 } else { -1.0 };
+                // A vanilla is an asset digital less strike cash digitals, and a gap triggered at its strike
+                let asset = asset_or_nothing(dir, 100.0, strike, 0.5, 0.04, 0.25, 0.02);
+                let cash = cash_or_nothing(dir, 100.0, strike, strike, 0.5, 0.04, 0.25, 0.02);
+                assert!((phi * (asset.pv - cash.pv) - vanilla).abs() < 0.002);
+                let gap = gap(dir, 100.0, strike, strike, 0.5, 0.04, 0.25, 0.02);
+                assert!((gap.pv - vanilla).abs() < 0.002);
+                assert!(
+                    (gap.delta - delta(dir, 100.0, strike, 0.5, 0.04, 0.25, 0.02)).abs() < 0.001
+                );
+                // A tight call spread is the cash digital
+                let width = 0.5;
+                let spread = phi
+                    * (bs_price(dir, 100.0, strike - width, 0.5, 0.04, 0.25, 0.02)
+                        - bs_price(dir, 100.0, strike + width, 0.5, 0.04, 0.25, 0.02))
+                    / (2.0 * width);
+                let digital = cash_or_nothing(dir, 100.0, strike, 1.0, 0.5, 0.04, 0.25, 0.02);
+                assert!((spread - digital.pv).abs() < 0.002);
+            }
+        }
+        // Digital calls and puts add up to a bond and a discounted unit of the underlying
+        let call = cash_or_nothing(OptionDir::CALL, 100.0, 105.0, 3.0, 0.5, 0.04, 0.25, 0.02);
+        let put = cash_or_nothing(OptionDir::PUT, 100.0, 105.0, 3.0, 0.5, 0.04, 0.25, 0.02);
+        assert!((call.pv + put.pv - 3.0 * (-0.04f32 * 0.5).exp()).abs() < 1.0e-4);
+        let call = asset_or_nothing(OptionDir::CALL, 100.0, 105.0, 0.5, 0.04, 0.25, 0.02);
+        let put = asset_or_nothing(OptionDir::PUT, 100.0, 105.0, 0.5, 0.04, 0.25, 0.02);
+        assert!((call.pv + put.pv - 100.0 * (-0.02f32 * 0.5).exp()).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn greeks_against_finite_differences() {
+        type Pricer = fn(f32, f32, f32, f32, f32) -> DigitalGreeks;
+        let pricers: [Pricer; 4] = [
+            |s, t, r, v, q| cash_or_nothing(OptionDir::CALL, s, 100.0, 10.0, t, r, v, q),
+            |s, t, r, v, q| asset_or_nothing(OptionDir::PUT, s, 95.0, t, r, v, q),
+            |s, t, r, v, q| gap(OptionDir::CALL, s, 100.0, 105.0, t, r, v, q),
+            |s, t, r, v, q| gap(OptionDir::PUT, s, 100.0, 90.0, t, r, v, q),
+        ];
+        let (s, t, r, v, q) = (100.0, 0.75, 0.05, 0.3, 0.02);
+        for pricer in pricers {
+            let greeks = pricer(s, t, r, v, q);
+            let pv = |s, t, r, v| pricer(s, t, r, v, q).pv;
+            let ds = 0.5;
+            let delta = (pv(s + ds, t, r, v) - pv(s - ds, t, r, v)) / (2.0 * ds);
+            assert!((greeks.delta - delta).abs() < 1.0e-3);
+            let gamma =
+                (pricer(s + ds, t, r, v, q).delta - pricer(s - ds, t, r, v, q).delta) / (2.0 * ds);
+            assert!((greeks.gamma - gamma).abs() < 1.0e-4);
+            let h = 0.002;
+            let vega = (pv(s, t, r, v + h) - pv(s, t, r, v - h)) / (2.0 * h);
+            assert!((greeks.vega - vega).abs() < 0.01);
+            let rho = (pv(s, t, r + h, v) - pv(s, t, r - h, v)) / (2.0 * h);
+            assert!((greeks.rho - rho).abs() < 0.01);
+            let theta = (pv(s, t - h, r, v) - pv(s, t + h, r, v)) / (2.0 * h);
+            assert!((greeks.theta - theta).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        // At the strike, where the payoff jumps and gamma changes sign, some of them close to expiry. The gap
+        // options are triggered at the spot and pay off against a strike away from it
+        let spot = [100.0, 100.0, 100.0, 40.0, 300.0, 100.0, 100.0, 100.0, 100.0];
+        let strike = spot;
+        let cash = [1.0, 10.0, 100.0, 2.0, 50.0, 1.0, 5.0, 100.0, 25.0];
+        let gap_strike = [95.0, 105.0, 100.0, 41.0, 290.0, 110.0, 90.0, 101.0, 99.0];
+        let years_to_expiry = [0.01, 0.05, 0.25, 1.0, 0.5, 2.0, 0.02, 5.0, 0.1];
+        let risk_free_rate = [0.0, 0.03, 0.05, 0.01, 0.08, 0.04, 0.0, 0.02, 0.06];
+        let volatility = [0.1, 0.3, 0.2, 0.6, 0.15, 0.25, 0.45, 0.12, 0.35];
+        let dividend_yield = [0.0, 0.0, 0.02, 0.03, 0.01, 0.05, 0.0, 0.04, 0.02];
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            let batches = [
+                cash_or_nothing_greeks(
+                    dir,
+                    &spot,
+                    &strike,
+                    &cash,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &volatility,
+                    &dividend_yield,
+                ),
+                asset_or_nothing_greeks(
+                    dir,
+                    &spot,
+                    &strike,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &volatility,
+                    &dividend_yield,
+                ),
+                gap_greeks(
+                    dir,
+                    &spot,
+                    &gap_strike,
+                    &spot,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &volatility,
+                    &dividend_yield,
+                ),
+            ];
+            for (j, batch) in batches.iter().enumerate() {
+                assert_eq!(batch.pv.len(), spot.len());
+                for i in 0..spot.len() {
+                    let args = (spot[i], strike[i], cash[i], years_to_expiry[i]);
+                    let (r, v, q) = (risk_free_rate[i], volatility[i], dividend_yield[i]);
+                    let scalar = match j {
+                        0 => cash_or_nothing(dir, args.0, args.1, args.2, args.3, r, v, q),
+                        1 => asset_or_nothing(dir, args.0, args.1, args.3, r, v, q),
+                        _ => gap(dir, args.0, gap_strike[i], args.0, args.3, r, v, q),
+                    };
+                    let close = |a: f32, b: f32| (a - b).abs() < 2.0e-3 * (1.0 + b.abs());
+                    assert!(close(batch.pv[i], scalar.pv));
+                    assert!(close(batch.delta[i], scalar.delta));
+                    assert!(close(batch.gamma[i], scalar.gamma));
+                    assert!(close(batch.vega[i], scalar.vega));
+                    assert!(close(batch.theta[i], scalar.theta));
+                    assert!(close(batch.rho[i], scalar.rho));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Fourier pricing of European options from a characteristic function
+use crate::bs::OptionDir;
+use crate::complex::Complex;
+
+/// Simpson step in the frequency domain
+const STEP: f64 = 0.05;
+/// Upper limit of the integral, the integrand of any realistic model is negligible well before this
+const MAX_FREQUENCY: f64 = 1000.0;
+/// Truncation tolerance on the integrand envelope
+const TOLERANCE: f64 = 1.0e-12;
+
+/// Lewis (2001) single integral pricing of a strike strip
+/// `cf` is the characteristic function of ln(S_T / F) where F is the forward, so E[exp(X)] = 1.
+/// The characteristic function is evaluated once per frequency and shared across all the strikes
+pub(crate) fn lewis_prices<F: Fn(Complex) -> Complex>(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    cf: F,
+) -> Vec<f32> {
+    let spot = spot as f64;
+    let years_to_expiry = years_to_expiry as f64;
+    let risk_free_rate = risk_free_rate as f64;
+    let dividend_yield = dividend_yield as f64;
+    let forward = spot * ((risk_free_rate - dividend_yield) * years_to_expiry).exp();
+    let df = (-risk_free_rate * years_to_expiry).exp();
+
+    // Integrand without the strike dependent phase, (u, simpson weight, phi(u - i/2) / (u^2 + 1/4))
+    let mut nodes = Vec::new();
+    let mut u = 0.0;
+    while u < MAX_FREQUENCY {
+        // One Simpson panel of 20 steps
+        for j in 0..=20 {
+            let w = match j {
+                0 | 20 => 1.0,
+                j if j % 2 == 1 => 4.0,
+                _ => 2.0,
+            };
+            let x = u + j as f64 * STEP;
+            let psi = cf(Complex::new(x, -0.5)) / (x * x + 0.25);
+            nodes.push((x, w * STEP / 3.0, psi));
+        }
+        u += 20.0 * STEP;
+        let (_, _, last) = nodes[nodes.len() - 1];
+        if last.norm() < TOLERANCE {
+            break;
+        }
+    }
+
+    strike
+        .iter()
+        .map(|&k| {
+            let k = k as f64;
+            let x = (forward / k).ln();
+            let integral: f64 = nodes
+                .iter()
+                .map(|&(u, w, psi)| w * (Complex::new(0.0, u * x).exp() * psi).re)
+                .sum();
+            let call = df * (forward - (forward * k).sqrt() * integral / std::f64::consts::PI);
+            let price = match option_dir {
+                OptionDir::CALL => call,
+                OptionDir::PUT => call - df * (forward - k),
+            };
+            price.max(0.0) as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+
+    #[test]
+    fn black_scholes_characteristic_function() {
+        let volatility = 0.25f32;
+        let years_to_expiry = 0.5f32;
+        let strike = [80.0, 90.0, 100.0, 110.0, 120.0];
+        let w = (volatility * volatility * years_to_expiry) as f64;
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            let prices = lewis_prices(dir, 100.0, &strike, years_to_expiry, 0.03, 0.01, |u| {
+                (-0.5 * w * (Complex::I * u + u * u)).exp()
+            });
+            for (k, p) in strike.iter().zip(prices) {
+                let expected = bs_price(dir, 100.0, *k, years_to_expiry, 0.03, volatility, 0.01);
+                assert!((p - expected).abs() < 0.001);
+            }
+        }
+    }
+}
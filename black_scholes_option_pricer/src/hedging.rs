@@ -0,0 +1,386 @@
+//! Discrete delta hedging of an option position along simulated or historical paths of the underlying
+//!
+//! The position is bought at its Black Scholes price at the implied volatility, and hedged by holding minus its
+//! delta, at the same implied volatility, in the underlying. Spots are at equally spaced times from the start to
+//! expiry. The hedge is checked every few steps and traded back to the target delta when it has drifted beyond a
+//! band, paying a proportional cost on the notional traded. Cash accrues at the risk free rate and the shares held
+//! earn the dividend yield. At expiry the option pays off and the hedge is unwound, without cost as against
+//! physical settlement.
+//!
+//! The hedging P&L is measured at expiry. Hedged at every step without costs, its mean is close to the value of
+//! the option at the realised volatility less its value at the implied volatility, and its spread comes from
+//! hedging discretely. The deltas of all paths at a step are computed in one batch.
+use crate::bs::{call_delta, put_delta, OptionDir};
+use crate::bs_single::bs_price;
+use crate::monte_carlo::Rng;
+use crate::normal::inverse_ncd;
+use wasm_bindgen::prelude::*;
+
+/// Rebalancing rules and costs of the hedge
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct HedgingParams {
+    /// Steps between checks of the hedge, 1 to check at every step
+    pub rebalance_every: usize,
+    /// Drift of the hedge from the target delta per option tolerated before trading, 0.0 to always trade
+    pub delta_band: f32,
+    /// Cost as a fraction of the notional traded, 0.001 = 10bp
+    pub transaction_cost: f32,
+}
+
+#[wasm_bindgen]
+impl HedgingParams {
+    #[wasm_bindgen(constructor)]
+    pub fn new(rebalance_every: usize, delta_band: f32, transaction_cost: f32) -> HedgingParams {
+        HedgingParams {
+            rebalance_every,
+            delta_band,
+            transaction_cost,
+        }
+    }
+}
+
+/// Result of hedging along one path
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct HedgeOutcome {
+    /// P&L at expiry of the option and its hedge, after costs
+    pub pnl: f32,
+    /// Transaction costs paid, valued at expiry
+    pub costs: f32,
+    pub trades: u32,
+}
+
+/// Distribution of the hedging P&L
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct HedgingStats {
+    pub mean: f32,
+    pub std_dev: f32,
+    /// 5th percentile
+    pub lower: f32,
+    /// 95th percentile
+    pub upper: f32,
+}
+
+/// Hedge along each path of spots, all of the same length
+#[allow(clippy::too_many_arguments)]
+fn hedge(
+    option_dir: OptionDir,
+    paths: &[Vec<f32>],
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    quantity: f32,
+    params: &HedgingParams,
+) -> Vec<HedgeOutcome> {
+    let n = paths.len();
+    let steps = paths.first().map_or(0, |p| p.len().saturating_sub(1));
+    if steps == 0 {
+        return vec![];
+    }
+    let dt = years_to_expiry / steps as f32;
+    let (growth, carry) = (
+        ((risk_free_rate * dt) as f64).exp(),
+        ((dividend_yield * dt) as f64).exp() - 1.0,
+    );
+    let premium = bs_price(
+        option_dir,
+        paths[0][0],
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+    );
+    let mut cash = vec![-(quantity * premium) as f64; n];
+    let mut costs = vec![0.0f64; n];
+    let mut held = vec![0.0f32; n];
+    let mut trades = vec![0u32; n];
+    let (k, r, v, q) = (
+        vec![strike; n],
+        vec![risk_free_rate; n],
+        vec![volatility; n],
+        vec![dividend_yield; n],
+    );
+    let every = params.rebalance_every.max(1);
+    for step in 0..steps {
+        let spots: Vec<f32> = paths.iter().map(|p| p[step]).collect();
+        if step % every == 0 {
+            let t = vec![years_to_expiry - step as f32 * dt; n];
+            let delta = match option_dir {
+                OptionDir::CALL => call_delta(&spots, &k, &t, &r, &v, &q),
+                OptionDir::PUT => put_delta(&spots, &k, &t, &r, &v, &q),
+            };
+            for i in 0..n {
+                let trade = -quantity * delta[i] - held[i];
+                if trade.abs() > params.delta_band * quantity.abs() {
+                    let notional = (trade * spots[i]) as f64;
+                    let cost = params.transaction_cost as f64 * notional.abs();
+                    cash[i] -= notional + cost;
+                    costs[i] += cost;
+                    held[i] += trade;
+                    trades[i] += 1;
+                }
+            }
+        }
+        for i in 0..n {
+            cash[i] = cash[i] * growth + held[i] as f64 * spots[i] as f64 * carry;
+            costs[i] *= growth;
+        }
+    }
+    let phi = match option_dir {
+        OptionDir::CALL => 1.0,
+        OptionDir::PUT => -1.0,
+    };
+    (0..n)
+        .map(|i| {
+            let s = paths[i][steps];
+            let payoff = (phi * (s - strike)).max(0.0);
+            HedgeOutcome {
+                pnl: (cash[i] + (quantity * payoff + held[i] * s) as f64) as f32,
+                costs: costs[i] as f32,
+                trades: trades[i],
+            }
+        })
+        .collect()
+}
+
+/// Delta hedge a position along a historical path of spots at equally spaced times from now to expiry
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn hedge_path(
+    option_dir: OptionDir,
+    path: &[f32],
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    quantity: f32,
+    params: &HedgingParams,
+) -> HedgeOutcome {
+    hedge(
+        option_dir,
+        &[path.to_vec()],
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        quantity,
+        params,
+    )
+    .pop()
+    .unwrap_or(HedgeOutcome {
+        pnl: f32::NAN,
+        costs: f32::NAN,
+        trades: 0,
+    })
+}
+
+/// Delta hedge a position priced and hedged at the implied volatility along paths simulated under Black Scholes at
+/// the realised volatility
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn simulate_hedging(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    implied_volatility: f32,
+    dividend_yield: f32,
+    realised_volatility: f32,
+    quantity: f32,
+    steps: usize,
+    paths: usize,
+    seed: u64,
+    params: &HedgingParams,
+) -> Vec<HedgeOutcome> {
+    let dt = years_to_expiry as f64 / steps as f64;
+    let sigma = realised_volatility as f64;
+    let drift = (risk_free_rate as f64 - dividend_yield as f64 - sigma * sigma / 2.0) * dt;
+    let mut rng = Rng::new(seed);
+    let simulated: Vec<Vec<f32>> = (0..paths)
+        .map(|_| {
+            let mut x = (spot as f64).ln();
+            std::iter::once(spot)
+                .chain((0..steps).map(|_| {
+                    x += drift + sigma * dt.sqrt() * inverse_ncd(rng.uniform());
+                    x.exp() as f32
+                }))
+                .collect()
+        })
+        .collect();
+    hedge(
+        option_dir,
+        &simulated,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        implied_volatility,
+        dividend_yield,
+        quantity,
+        params,
+    )
+}
+
+/// Mean, standard deviation and 5th and 95th percentiles of hedging P&Ls
+#[wasm_bindgen]
+pub fn hedging_stats(pnl: &[f32]) -> HedgingStats {
+    let n = pnl.len() as f64;
+    let mean = pnl.iter().map(|&p| p as f64).sum::<f64>() / n;
+    let variance = pnl.iter().map(|&p| (p as f64 - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let mut sorted = pnl.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let percentile = |p: f64| {
+        sorted
+            .get(((n - 1.0) * p).round() as usize)
+            .copied()
+            .unwrap_or(f32::NAN)
+    };
+    HedgingStats {
+        mean: mean as f32,
+        std_dev: variance.sqrt() as f32,
+        lower: percentile(0.05),
+        upper: percentile(0.95),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pnl(outcomes: &[HedgeOutcome]) -> Vec<f32> {
+        outcomes.iter().map(|o| o.pnl).collect()
+    }
+
+    #[test]
+    fn hedging_error_shrinks_with_frequency() {
+        let params = HedgingParams::new(1, 0.0, 0.0);
+        let (s, k, t, r, v, q) = (100.0, 100.0, 0.25, 0.03, 0.2, 0.01);
+        let price = bs_price(OptionDir::CALL, s, k, t, r, v, q);
+        let coarse = simulate_hedging(
+            OptionDir::CALL,
+            s,
+            k,
+            t,
+            r,
+            v,
+            q,
+            v,
+            1.0,
+            16,
+            4000,
+            1,
+            &params,
+        );
+        let fine = simulate_hedging(
+            OptionDir::CALL,
+            s,
+            k,
+            t,
+            r,
+            v,
+            q,
+            v,
+            1.0,
+            64,
+            4000,
+            1,
+            &params,
+        );
+        let (coarse, fine) = (hedging_stats(&pnl(&coarse)), hedging_stats(&pnl(&fine)));
+        // Hedged at the realised vol the P&L is noise around zero, halving with four times the steps
+        assert!(coarse.mean.abs() < 0.05 * price);
+        assert!(fine.mean.abs() < 0.03 * price);
+        assert!((coarse.std_dev / fine.std_dev - 2.0).abs() < 0.2);
+        assert!(fine.lower < 0.0 && fine.upper > 0.0);
+    }
+
+    #[test]
+    fn realised_against_implied() {
+        let params = HedgingParams::new(1, 0.0, 0.0);
+        let (s, k, t, r, q) = (100.0, 100.0, 0.5, 0.03, 0.0);
+        for &(dir, implied, realised) in &[
+            (OptionDir::CALL, 0.2, 0.3),
+            (OptionDir::PUT, 0.3, 0.2),
+            (OptionDir::PUT, 0.25, 0.35),
+        ] {
+            let outcomes = simulate_hedging(
+                dir, s, k, t, r, implied, q, realised, 1.0, 126, 2000, 5, &params,
+            );
+            let stats = hedging_stats(&pnl(&outcomes));
+            let edge = (bs_price(dir, s, k, t, r, realised, q)
+                - bs_price(dir, s, k, t, r, implied, q))
+                * (r * t).exp();
+            // Long the option makes money when it realises more than it was bought at
+            assert!(stats.mean * edge > 0.0);
+            assert!((stats.mean - edge).abs() < 0.15 * edge.abs());
+        }
+    }
+
+    #[test]
+    fn costs_and_rebalancing_rules() {
+        let (s, k, t, r, v, q) = (100.0, 95.0, 0.25, 0.03, 0.25, 0.0);
+        let run = |params: HedgingParams| {
+            simulate_hedging(
+                OptionDir::PUT,
+                s,
+                k,
+                t,
+                r,
+                v,
+                q,
+                v,
+                -10.0,
+                63,
+                500,
+                9,
+                &params,
+            )
+        };
+        let free = run(HedgingParams::new(1, 0.0, 0.0));
+        let costly = run(HedgingParams::new(1, 0.0, 0.002));
+        let weekly = run(HedgingParams::new(5, 0.0, 0.002));
+        let banded = run(HedgingParams::new(1, 0.05, 0.002));
+        // Paths far out of the money may have nothing left to trade
+        let most = |o: &[HedgeOutcome]| o.iter().map(|o| o.trades).max().unwrap();
+        assert!(free.iter().all(|o| o.costs == 0.0));
+        assert_eq!(most(&free), 63);
+        assert_eq!(most(&weekly), 13);
+        for i in 0..free.len() {
+            assert!(costly[i].costs > 0.0);
+            assert!((free[i].pnl - costly[i].costs - costly[i].pnl).abs() < 1.0e-2);
+            assert!(banded[i].trades <= costly[i].trades);
+        }
+        let mean_cost =
+            |o: &[HedgeOutcome]| o.iter().map(|o| o.costs).sum::<f32>() / o.len() as f32;
+        assert!(mean_cost(&banded) < mean_cost(&costly));
+        assert!(mean_cost(&weekly) < mean_cost(&costly));
+
+        // Along a historical path that never moves, a long call only loses its time value
+        let path = vec![100.0; 21];
+        let params = HedgingParams::new(1, 0.0, 0.0);
+        let outcome = hedge_path(
+            OptionDir::CALL,
+            &path,
+            100.0,
+            20.0 / 252.0,
+            0.0,
+            0.2,
+            0.0,
+            1.0,
+            &params,
+        );
+        let price = bs_price(OptionDir::CALL, 100.0, 100.0, 20.0 / 252.0, 0.0, 0.2, 0.0);
+        assert!((outcome.pnl + price).abs() < 1.0e-3);
+    }
+}
@@ -0,0 +1,525 @@
+//! Heston (1993) stochastic volatility pricing
+//!
+//! European prices come from the Lewis single integral over the "little trap" form of the
+//! characteristic function (Albrecher et al. 2007), which does not suffer from the branch cut
+//! discontinuity of the original formulation for long expiries.
+//! Greeks are calculated by central finite differences and the model can be calibrated to a surface of implied vols.
+use crate::bs::{Greeks, OptionDir};
+use crate::bs_single::{bs_price, vega};
+use crate::complex::Complex;
+use crate::fourier::lewis_prices;
+
+/// Heston model parameters. Variances are expressed as f32 with 0.04 = 20% vol
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct HestonParams {
+    /// Initial variance
+    pub v0: f32,
+    /// Mean reversion speed of the variance
+    pub kappa: f32,
+    /// Long run variance
+    pub theta: f32,
+    /// Volatility of the variance, must be > 0
+    pub sigma: f32,
+    /// Correlation between the spot and variance processes
+    pub rho: f32,
+}
+
+/// Characteristic function of ln(S_T / F) in the little trap formulation
+pub(crate) fn characteristic_function(
+    params: &HestonParams,
+    years_to_expiry: f64,
+    u: Complex,
+) -> Complex {
+    let v0 = params.v0 as f64;
+    let kappa = params.kappa as f64;
+    let theta = params.theta as f64;
+    let sigma = params.sigma as f64;
+    let rho = params.rho as f64;
+    let s2 = sigma * sigma;
+
+    let iu = Complex::I * u;
+    let xi = kappa - sigma * rho * iu;
+    let d = (xi * xi + s2 * (u * u + iu)).sqrt();
+    let g = (xi - d) / (xi + d);
+    let e = (-d * years_to_expiry).exp();
+    let c =
+        kappa * theta / s2 * ((xi - d) * years_to_expiry - 2.0 * ((1.0 - g * e) / (1.0 - g)).ln());
+    let v = (xi - d) / s2 * (1.0 - e) / (1.0 - g * e);
+    (c + v * v0).exp()
+}
+
+/// Heston price of a single option
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn heston_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &HestonParams,
+) -> f32 {
+    heston_prices(
+        option_dir,
+        spot,
+        &[strike],
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        params,
+    )[0]
+}
+
+/// Heston prices across a strike strip with a common expiry. The results are at the same index as the strikes
+/// This is much faster than pricing each strike individually as the characteristic function is shared
+pub fn heston_prices(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &HestonParams,
+) -> Vec<f32> {
+    let t = years_to_expiry as f64;
+    lewis_prices(
+        option_dir,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        |u| characteristic_function(params, t, u),
+    )
+}
+
+/// Calculate all the greeks across a strike strip by central finite differences
+/// Vega is the sensitivity to a parallel shift of the initial and long run vol (sqrt of the variances)
+/// so it is comparable with the Black Scholes vega
+pub fn heston_greeks(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &HestonParams,
+) -> Greeks {
+    let price = |spot: f32, years_to_expiry: f32, risk_free_rate: f32, params: &HestonParams| {
+        heston_prices(
+            option_dir,
+            spot,
+            strike,
+            years_to_expiry,
+            risk_free_rate,
+            dividend_yield,
+            params,
+        )
+    };
+    let pv = price(spot, years_to_expiry, risk_free_rate, params);
+
+    let ds = spot * 0.01;
+    let up = price(spot + ds, years_to_expiry, risk_free_rate, params);
+    let down = price(spot - ds, years_to_expiry, risk_free_rate, params);
+    let delta = central(&up, &down, ds);
+    let gamma = pv
+        .iter()
+        .zip(up.iter().zip(down.iter()))
+        .map(|(p, (u, d))| (u - 2.0 * p + d) / (ds * ds))
+        .collect();
+
+    const DV: f32 = 0.01;
+    let shifted = |dv: f32| HestonParams {
+        v0: (params.v0.sqrt() + dv).powi(2),
+        theta: (params.theta.sqrt() + dv).powi(2),
+        ..*params
+    };
+    let up = price(spot, years_to_expiry, risk_free_rate, &shifted(DV));
+    let down = price(spot, years_to_expiry, risk_free_rate, &shifted(-DV));
+    let vega = central(&up, &down, DV);
+
+    const DT: f32 = 1.0 / 365.0;
+    let theta = if years_to_expiry > DT {
+        let up = price(spot, years_to_expiry + DT, risk_free_rate, params);
+        let down = price(spot, years_to_expiry - DT, risk_free_rate, params);
+        central(&down, &up, DT)
+    } else {
+        let up = price(spot, years_to_expiry + DT, risk_free_rate, params);
+        central(&pv, &up, DT / 2.0)
+    };
+
+    const DR: f32 = 0.0001;
+    let up = price(spot, years_to_expiry, risk_free_rate + DR, params);
+    let down = price(spot, years_to_expiry, risk_free_rate - DR, params);
+    let rho = central(&up, &down, DR);
+
+    Greeks {
+        pv,
+        delta,
+        theta,
+        gamma,
+        rho,
+        vega,
+    }
+}
+
+fn central(up: &[f32], down: &[f32], h: f32) -> Vec<f32> {
+    up.iter()
+        .zip(down.iter())
+        .map(|(u, d)| (u - d) / (2.0 * h))
+        .collect()
+}
+
+/// Largest correlation magnitude of the initial guess, so that its atanh is finite
+const MAX_INITIAL_RHO: f64 = 1.0 - 1.0e-6;
+
+/// Calibrate the Heston parameters to a surface of implied vols
+/// Each point of the surface is given by the strike, years to expiry and implied vol at the same index.
+/// The fit minimises the squared vega weighted price errors, which to first order is the squared implied vol error,
+/// using Nelder-Mead from the initial guess given. Points with the same expiry are priced together so surfaces
+/// should be grouped by expiry for best performance. An initial correlation of +/-1 starts just inside the bounds
+pub fn heston_calibrate(
+    spot: f32,
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    implied_vol: &[f32],
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    initial: &HestonParams,
+) -> HestonParams {
+    // Out of the money options are used as they carry all the time value
+    let quotes: Vec<(OptionDir, f32, f32)> = (0..strike.len())
+        .map(|i| {
+            let t = years_to_expiry[i];
+            let forward = spot * ((risk_free_rate - dividend_yield) * t).exp();
+            let dir = if strike[i] >= forward {
+                OptionDir::CALL
+            } else {
+                OptionDir::PUT
+            };
+            let price = bs_price(
+                dir,
+                spot,
+                strike[i],
+                t,
+                risk_free_rate,
+                implied_vol[i],
+                dividend_yield,
+            );
+            let vega = vega(
+                spot,
+                strike[i],
+                t,
+                risk_free_rate,
+                implied_vol[i],
+                dividend_yield,
+            );
+            (dir, price, vega.max(spot * 1.0e-4))
+        })
+        .collect();
+
+    // Unconstrained parameterisation: positive parameters through exp and the correlation through tanh
+    let to_params = |x: &[f64]| HestonParams {
+        v0: x[0].exp() as f32,
+        kappa: x[1].exp() as f32,
+        theta: x[2].exp() as f32,
+        sigma: x[3].exp() as f32,
+        rho: x[4].tanh() as f32,
+    };
+    let objective = |x: &[f64]| {
+        let params = to_params(x);
+        let mut err = 0.0f64;
+        let mut i = 0;
+        while i < strike.len() {
+            // Price a run of points sharing the same expiry together
+            let t = years_to_expiry[i];
+            let mut j = i;
+            while j < strike.len() && years_to_expiry[j] == t {
+                j += 1;
+            }
+            for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+                let idx: Vec<usize> = (i..j).filter(|&k| quotes[k].0 == dir).collect();
+                if idx.is_empty() {
+                    continue;
+                }
+                let strikes: Vec<f32> = idx.iter().map(|&k| strike[k]).collect();
+                let model = heston_prices(
+                    dir,
+                    spot,
+                    &strikes,
+                    t,
+                    risk_free_rate,
+                    dividend_yield,
+                    &params,
+                );
+                for (m, &k) in model.iter().zip(idx.iter()) {
+                    let e = ((m - quotes[k].1) / quotes[k].2) as f64;
+                    err += e * e;
+                }
+            }
+            i = j;
+        }
+        err
+    };
+
+    let x0 = vec![
+        (initial.v0 as f64).ln(),
+        (initial.kappa as f64).ln(),
+        (initial.theta as f64).ln(),
+        (initial.sigma as f64).ln(),
+        (initial.rho as f64)
+            .clamp(-MAX_INITIAL_RHO, MAX_INITIAL_RHO)
+            .atanh(),
+    ];
+    to_params(&nelder_mead(objective, x0, 0.25, 1.0e-10, 2000))
+}
+
+/// Nelder-Mead downhill simplex minimisation
+fn nelder_mead<F: Fn(&[f64]) -> f64>(
+    f: F,
+    x0: Vec<f64>,
+    step: f64,
+    tolerance: f64,
+    max_iter: usize,
+) -> Vec<f64> {
+    let n = x0.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=n)
+        .map(|i| {
+            let mut x = x0.clone();
+            if i > 0 {
+                x[i - 1] += step;
+            }
+            let fx = f(&x);
+            (x, fx)
+        })
+        .collect();
+    let point = |a: &[f64], b: &[f64], t: f64| -> Vec<f64> {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| a + t * (b - a))
+            .collect()
+    };
+
+    for _ in 0..max_iter {
+        simplex.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        if (simplex[n].1 - simplex[0].1).abs() < tolerance {
+            break;
+        }
+        let centroid: Vec<f64> = (0..n)
+            .map(|j| simplex[..n].iter().map(|(x, _)| x[j]).sum::<f64>() / n as f64)
+            .collect();
+        let worst = simplex[n].0.clone();
+
+        let reflected = point(&centroid, &worst, -1.0);
+        let fr = f(&reflected);
+        if fr < simplex[0].1 {
+            let expanded = point(&centroid, &worst, -2.0);
+            let fe = f(&expanded);
+            simplex[n] = if fe < fr {
+                (expanded, fe)
+            } else {
+                (reflected, fr)
+            };
+        } else if fr < simplex[n - 1].1 {
+            simplex[n] = (reflected, fr);
+        } else {
+            let contracted = if fr < simplex[n].1 {
+                point(&centroid, &worst, -0.5)
+            } else {
+                point(&centroid, &worst, 0.5)
+            };
+            let fc = f(&contracted);
+            if fc < fr.min(simplex[n].1) {
+                simplex[n] = (contracted, fc);
+            } else {
+                // Shrink towards the best point
+                let best = simplex[0].0.clone();
+                for v in simplex.iter_mut().skip(1) {
+                    let x = point(&best, &v.0, 0.5);
+                    let fx = f(&x);
+                    *v = (x, fx);
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    simplex.swap_remove(0).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: HestonParams = HestonParams {
+        v0: 0.04,
+        kappa: 1.5,
+        theta: 0.06,
+        sigma: 0.6,
+        rho: -0.7,
+    };
+
+    #[test]
+    fn reference_price() {
+        // Fang & Oosterlee (2008) COS method paper, reference value 5.785155450
+        let params = HestonParams {
+            v0: 0.0175,
+            kappa: 1.5768,
+            theta: 0.0398,
+            sigma: 0.5751,
+            rho: -0.5711,
+        };
+        let call = heston_price(OptionDir::CALL, 100.0, 100.0, 1.0, 0.0, 0.0, &params);
+        assert!((call - 5.7851555).abs() < 0.0001);
+    }
+
+    #[test]
+    fn black_scholes_limit() {
+        // With a tiny vol of vol and theta = v0 the variance is constant
+        let params = HestonParams {
+            v0: 0.04,
+            kappa: 1.0,
+            theta: 0.04,
+            sigma: 0.0001,
+            rho: 0.0,
+        };
+        for i in (70..140).step_by(10) {
+            let strike = i as f32;
+            let h = heston_price(OptionDir::CALL, 100.0, strike, 0.75, 0.02, 0.01, &params);
+            let bs = bs_price(OptionDir::CALL, 100.0, strike, 0.75, 0.02, 0.2, 0.01);
+            assert!((h - bs).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn put_call_parity() {
+        let strike = [80.0, 95.0, 100.0, 105.0, 130.0];
+        let (spot, t, r, q) = (100.0f32, 2.0f32, 0.03f32, 0.01f32);
+        let calls = heston_prices(OptionDir::CALL, spot, &strike, t, r, q, &PARAMS);
+        let puts = heston_prices(OptionDir::PUT, spot, &strike, t, r, q, &PARAMS);
+        for i in 0..strike.len() {
+            let parity = spot * (-q * t).exp() - strike[i] * (-r * t).exp();
+            assert!((calls[i] - puts[i] - parity).abs() < 0.001);
+        }
+        // Negative correlation gives a downward sloping smile so the OTM put is worth more than in
+        // Black Scholes at the ATM vol
+        let atm = bs_price(OptionDir::PUT, spot, 80.0, t, r, 0.2, q);
+        assert!(puts[0] > atm);
+    }
+
+    #[test]
+    fn greeks() {
+        let strike = [90.0, 100.0, 110.0];
+        let greeks = heston_greeks(OptionDir::CALL, 100.0, &strike, 0.5, 0.02, 0.0, &PARAMS);
+        for i in 0..strike.len() {
+            assert!(greeks.delta[i] > 0.0 && greeks.delta[i] < 1.0);
+            assert!(greeks.gamma[i] > 0.0);
+            assert!(greeks.vega[i] > 0.0);
+            assert!(greeks.theta[i] < 0.0);
+            assert!(greeks.rho[i] > 0.0);
+        }
+        assert!(greeks.delta[0] > greeks.delta[1] && greeks.delta[1] > greeks.delta[2]);
+    }
+
+    #[test]
+    fn calibrate_from_perfect_correlation() {
+        let initial = HestonParams {
+            rho: -1.0,
+            ..PARAMS
+        };
+        let fitted = heston_calibrate(
+            100.0,
+            &[90.0, 100.0, 110.0],
+            &[0.5; 3],
+            &[0.24, 0.2, 0.18],
+            0.02,
+            0.0,
+            &initial,
+        );
+        assert!(fitted.rho.is_finite() && fitted.rho.abs() <= 1.0);
+        assert!(fitted.v0.is_finite() && fitted.sigma.is_finite());
+    }
+
+    #[test]
+    fn calibrate_to_own_surface() {
+        let spot = 100.0;
+        let mut strike = Vec::new();
+        let mut years_to_expiry = Vec::new();
+        for &t in [0.25f32, 1.0].iter() {
+            for k in (80..=120).step_by(10) {
+                strike.push(k as f32);
+                years_to_expiry.push(t);
+            }
+        }
+        // Implied vols of the model prices by bisection
+        let implied_vol: Vec<f32> = (0..strike.len())
+            .map(|i| {
+                let price = heston_price(
+                    OptionDir::CALL,
+                    spot,
+                    strike[i],
+                    years_to_expiry[i],
+                    0.02,
+                    0.0,
+                    &PARAMS,
+                );
+                let (mut lo, mut hi) = (0.01f32, 1.0f32);
+                for _ in 0..40 {
+                    let mid = 0.5 * (lo + hi);
+                    let p = bs_price(
+                        OptionDir::CALL,
+                        spot,
+                        strike[i],
+                        years_to_expiry[i],
+                        0.02,
+                        mid,
+                        0.0,
+                    );
+                    if p > price {
+                        hi = mid;
+                    } else {
+                        lo = mid;
+                    }
+                }
+                0.5 * (lo + hi)
+            })
+            .collect();
+        let initial = HestonParams {
+            v0: 0.05,
+            kappa: 1.0,
+            theta: 0.05,
+            sigma: 0.4,
+            rho: -0.3,
+        };
+        let fitted = heston_calibrate(
+            spot,
+            &strike,
+            &years_to_expiry,
+            &implied_vol,
+            0.02,
+            0.0,
+            &initial,
+        );
+        for i in 0..strike.len() {
+            let expected = heston_price(
+                OptionDir::CALL,
+                spot,
+                strike[i],
+                years_to_expiry[i],
+                0.02,
+                0.0,
+                &PARAMS,
+            );
+            let actual = heston_price(
+                OptionDir::CALL,
+                spot,
+                strike[i],
+                years_to_expiry[i],
+                0.02,
+                0.0,
+                &fitted,
+            );
+            assert!((expected - actual).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! Jump diffusion pricers
+//!
+//! * Merton (1976) lognormal jumps, priced with the Poisson weighted series of Black Scholes prices
+//! * Kou (2002) double exponential jumps, priced by Fourier inversion of the characteristic function
+//!
+//! The batch functions follow the layout of `bs.rs`, with the jump parameters shared across the batch.
+//! The implied vol functions turn the prices back into Black Scholes vols so the skew generated by
+//! the jumps can be compared to the market.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_;
+use crate::complex::Complex;
+use crate::fourier::lewis_prices;
+use crate::solver::black_scholes_implied_vol;
+use bytemuck::cast;
+use wide::*;
+
+/// Upper bound on the number of jumps summed in the Merton series
+const MAX_JUMPS: usize = 100;
+
+/// Merton jump parameters
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct MertonParams {
+    /// Expected number of jumps per year
+    pub jump_intensity: f32,
+    /// Mean of the log jump size
+    pub jump_mean: f32,
+    /// Standard deviation of the log jump size
+    pub jump_vol: f32,
+}
+
+/// Kou double exponential jump parameters
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct KouParams {
+    /// Expected number of jumps per year
+    pub jump_intensity: f32,
+    /// Probability a jump is upwards
+    pub up_probability: f32,
+    /// Rate of the exponential distribution of up jumps, must be > 1 for the stock to have a finite mean
+    pub up_rate: f32,
+    /// Rate of the exponential distribution of down jumps
+    pub down_rate: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merton_f32x8(
+    option_dir: OptionDir,
+    spot: f32x8,
+    strike: f32x8,
+    years_to_expiry: f32x8,
+    risk_free_rate: f32x8,
+    volatility: f32x8,
+    dividend_yield: f32x8,
+    params: &MertonParams,
+) -> f32x8 {
+    // Mean relative jump size
+    let k = (params.jump_mean + params.jump_vol * params.jump_vol / 2.0).exp() - 1.0;
+    let lambda_t = years_to_expiry * (params.jump_intensity * (1.0 + k));
+    let max_lambda_t = cast::<f32x8, [f32; 8]>(lambda_t)
+        .iter()
+        .cloned()
+        .fold(0.0, f32::max);
+    let variance = volatility * volatility;
+    let jump_variance = f32x8::splat(params.jump_vol * params.jump_vol) / years_to_expiry;
+    let jump_drift = f32x8::splat((1.0 + k).ln()) / years_to_expiry;
+    let drift = risk_free_rate - params.jump_intensity * k;
+
+    let mut weight = (-lambda_t).exp();
+    let mut price = f32x8::ZERO;
+    for n in 0..MAX_JUMPS {
+        let nf = f32x8::splat(n as f32);
+        let volatility_n = nf.mul_add(jump_variance, variance).sqrt();
+        let risk_free_rate_n = nf.mul_add(jump_drift, drift);
+        let p = match option_dir {
+            OptionDir::CALL => bs_f32x8_::call_f32x8(
+                spot,
+                strike,
+                years_to_expiry,
+                risk_free_rate_n,
+                volatility_n,
+                dividend_yield,
+            ),
+            OptionDir::PUT => bs_f32x8_::put_f32x8(
+                spot,
+                strike,
+                years_to_expiry,
+                risk_free_rate_n,
+                volatility_n,
+                dividend_yield,
+            ),
+        };
+        price = weight.mul_add(p, price);
+        weight = weight * lambda_t / (n as f32 + 1.0);
+        if n as f32 > max_lambda_t && weight.cmp_lt(f32x8::splat(1.0e-9)).all() {
+            break;
+        }
+    }
+    price
+}
+
+/// Merton jump diffusion price of a single option
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+/// The volatility is that of the diffusion part only
+#[allow(clippy::too_many_arguments)]
+pub fn merton_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    params: &MertonParams,
+) -> f32 {
+    let price: [f32; 8] = cast(merton_f32x8(
+        option_dir,
+        spot.into(),
+        strike.into(),
+        years_to_expiry.into(),
+        risk_free_rate.into(),
+        volatility.into(),
+        dividend_yield.into(),
+        params,
+    ));
+    price[0]
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merton_batch(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &MertonParams,
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(spot.len());
+    for i in (0..spot.len()).step_by(8) {
+        let spot = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let strike = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let years_to_expiry = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let risk_free_rate = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let volatility = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let dividend_yield = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let price: [f32; 8] = cast(merton_f32x8(
+            option_dir,
+            spot,
+            strike,
+            years_to_expiry,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+            params,
+        ));
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Merton jump diffusion call pricing for arrays. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn merton_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &MertonParams,
+) -> Vec<f32> {
+    merton_batch(
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        params,
+    )
+}
+
+/// Merton jump diffusion put pricing for arrays. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn merton_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &MertonParams,
+) -> Vec<f32> {
+    merton_batch(
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        params,
+    )
+}
+
+/// Black Scholes implied vols of Merton prices for arrays. The results are at the same index as the inputs
+/// The out of the money option is inverted at each strike, by put call parity both give the same vol
+pub fn merton_implied_vol(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &MertonParams,
+) -> Vec<f32> {
+    (0..spot.len())
+        .map(|i| {
+            let dir = otm_dir(
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                dividend_yield[i],
+            );
+            let price = merton_price(
+                dir,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                volatility[i],
+                dividend_yield[i],
+                params,
+            );
+            black_scholes_implied_vol(
+                dir,
+                price,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                dividend_yield[i],
+            )
+        })
+        .collect()
+}
+
+/// Characteristic function of ln(S_T / F) under Kou's model
+fn kou_characteristic_function(
+    years_to_expiry: f64,
+    volatility: f64,
+    params: &KouParams,
+    u: Complex,
+) -> Complex {
+    let lambda = params.jump_intensity as f64;
+    let p = params.up_probability as f64;
+    let eta1 = params.up_rate as f64;
+    let eta2 = params.down_rate as f64;
+    let iu = Complex::I * u;
+    let jump = p * eta1 / (eta1 - iu) + (1.0 - p) * eta2 / (iu + eta2);
+    // Martingale correction so that E[exp(X)] = 1
+    let compensator = p * eta1 / (eta1 - 1.0) + (1.0 - p) * eta2 / (eta2 + 1.0) - 1.0;
+    let variance = volatility * volatility;
+    let exponent =
+        -0.5 * variance * (iu + u * u) + lambda * (jump - 1.0) - iu * (lambda * compensator);
+    (exponent * years_to_expiry).exp()
+}
+
+/// Kou double exponential jump diffusion price of a single option
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+/// The volatility is that of the diffusion part only
+#[allow(clippy::too_many_arguments)]
+pub fn kou_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    params: &KouParams,
+) -> f32 {
+    lewis_prices(
+        option_dir,
+        spot,
+        &[strike],
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        |u| kou_characteristic_function(years_to_expiry as f64, volatility as f64, params, u),
+    )[0]
+}
+
+/// Kou double exponential jump diffusion call pricing for arrays. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn kou_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &KouParams,
+) -> Vec<f32> {
+    (0..spot.len())
+        .map(|i| {
+            kou_price(
+                OptionDir::CALL,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                volatility[i],
+                dividend_yield[i],
+                params,
+            )
+        })
+        .collect()
+}
+
+/// Kou double exponential jump diffusion put pricing for arrays. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn kou_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &KouParams,
+) -> Vec<f32> {
+    (0..spot.len())
+        .map(|i| {
+            kou_price(
+                OptionDir::PUT,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                volatility[i],
+                dividend_yield[i],
+                params,
+            )
+        })
+        .collect()
+}
+
+/// Black Scholes implied vols of Kou prices for arrays. The results are at the same index as the inputs
+/// The out of the money option is inverted at each strike, by put call parity both give the same vol
+pub fn kou_implied_vol(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &KouParams,
+) -> Vec<f32> {
+    (0..spot.len())
+        .map(|i| {
+            let dir = otm_dir(
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                dividend_yield[i],
+            );
+            let price = kou_price(
+                dir,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                volatility[i],
+                dividend_yield[i],
+                params,
+            );
+            black_scholes_implied_vol(
+                dir,
+                price,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                dividend_yield[i],
+            )
+        })
+        .collect()
+}
+
+/// The out of the money side relative to the forward
+fn otm_dir(
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+) -> OptionDir {
+    let forward = spot * ((risk_free_rate - dividend_yield) * years_to_expiry).exp();
+    if strike >= forward {
+        OptionDir::CALL
+    } else {
+        OptionDir::PUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+
+    const MERTON: MertonParams = MertonParams {
+        jump_intensity: 1.0,
+        jump_mean: -0.1,
+        jump_vol: 0.15,
+    };
+
+    const KOU: KouParams = KouParams {
+        jump_intensity: 1.0,
+        up_probability: 0.4,
+        up_rate: 10.0,
+        down_rate: 5.0,
+    };
+
+    #[test]
+    fn no_jumps_is_black_scholes() {
+        let params = MertonParams {
+            jump_intensity: 0.0,
+            ..MERTON
+        };
+        let m = merton_price(OptionDir::CALL, 100.0, 105.0, 0.5, 0.02, 0.2, 0.01, &params);
+        let bs = bs_price(OptionDir::CALL, 100.0, 105.0, 0.5, 0.02, 0.2, 0.01);
+        assert!((m - bs).abs() < 0.0001);
+
+        let params = KouParams {
+            jump_intensity: 0.0,
+            ..KOU
+        };
+        let k = kou_price(OptionDir::PUT, 100.0, 95.0, 0.5, 0.02, 0.2, 0.01, &params);
+        let bs = bs_price(OptionDir::PUT, 100.0, 95.0, 0.5, 0.02, 0.2, 0.01);
+        assert!((k - bs).abs() < 0.001);
+    }
+
+    #[test]
+    fn merton_reference() {
+        // Series evaluated in f64 to 60 terms
+        let call = merton_price(OptionDir::CALL, 100.0, 100.0, 0.25, 0.05, 0.2, 0.0, &MERTON);
+        assert!((call - 5.598139).abs() < 0.001);
+        let put = merton_price(OptionDir::PUT, 100.0, 100.0, 0.25, 0.05, 0.2, 0.0, &MERTON);
+        let parity = 100.0 - 100.0 * (-0.05f32 * 0.25).exp();
+        assert!((call - put - parity).abs() < 0.001);
+    }
+
+    #[test]
+    fn batch_matches_single() {
+        let n = 11;
+        let spot = vec![100.0; n];
+        let strike: Vec<f32> = (0..n).map(|i| 75.0 + 5.0 * i as f32).collect();
+        let years_to_expiry = vec![0.1; n];
+        let risk_free_rate = vec![0.02; n];
+        let volatility = vec![0.25; n];
+        let dividend_yield = vec![0.0; n];
+        let calls = merton_call(
+            &spot,
+            &strike,
+            &years_to_expiry,
+            &risk_free_rate,
+            &volatility,
+            &dividend_yield,
+            &MERTON,
+        );
+        let puts = kou_put(
+            &spot,
+            &strike,
+            &years_to_expiry,
+            &risk_free_rate,
+            &volatility,
+            &dividend_yield,
+            &KOU,
+        );
+        assert_eq!(calls.len(), n);
+        assert_eq!(puts.len(), n);
+        for i in 0..n {
+            let c = merton_price(
+                OptionDir::CALL,
+                100.0,
+                strike[i],
+                0.1,
+                0.02,
+                0.25,
+                0.0,
+                &MERTON,
+            );
+            let p = kou_price(OptionDir::PUT, 100.0, strike[i], 0.1, 0.02, 0.25, 0.0, &KOU);
+            assert!((calls[i] - c).abs() < 0.0001);
+            assert!((puts[i] - p).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn kou_put_call_parity() {
+        let call = kou_price(OptionDir::CALL, 100.0, 110.0, 1.0, 0.03, 0.2, 0.01, &KOU);
+        let put = kou_price(OptionDir::PUT, 100.0, 110.0, 1.0, 0.03, 0.2, 0.01, &KOU);
+        let parity = 100.0 * (-0.01f32).exp() - 110.0 * (-0.03f32).exp();
+        assert!((call - put - parity).abs() < 0.001);
+    }
+
+    #[test]
+    fn jumps_generate_skew() {
+        let n = 5;
+        let spot = vec![100.0; n];
+        let strike = [80.0, 90.0, 100.0, 110.0, 120.0];
+        let years_to_expiry = vec![0.1; n];
+        let risk_free_rate = vec![0.02; n];
+        let volatility = vec![0.2; n];
+        let dividend_yield = vec![0.0; n];
+        let merton = merton_implied_vol(
+            &spot,
+            &strike,
+            &years_to_expiry,
+            &risk_free_rate,
+            &volatility,
+            &dividend_yield,
+            &MERTON,
+        );
+        let kou = kou_implied_vol(
+            &spot,
+            &strike,
+            &years_to_expiry,
+            &risk_free_rate,
+            &volatility,
+            &dividend_yield,
+            &KOU,
+        );
+        // Negative mean jumps give a smile steeper on the downside, and all vols are above the diffusion vol
+        for vols in [merton, kou].iter() {
+            assert!(vols.iter().all(|&v| v > 0.2));
+            assert!(vols[0] > vols[2]);
+            assert!(vols[0] > vols[4]);
+        }
+    }
+}
@@ -7,6 +7,31 @@
 ///! * Implied vol
 ///! * Implied Interest rates
 ///! * Strike from delta
+///! * Heston stochastic volatility pricing and calibration
+///! * Merton and Kou jump diffusion
+///! * Monte Carlo with pluggable payoffs and variance reduction
+///! * Quasi Monte Carlo with Sobol sequences and Brownian bridge
+///! * Least squares Monte Carlo for American and Bermudan options
+///! * Crank-Nicolson PDE solver for European, American and barrier options
+///! * Barone-Adesi-Whaley and Bjerksund-Stensland American approximations
+///! * Trinomial trees with term structures, early exercise, barriers and lattice greeks
+///! * Reiner-Rubinstein barrier options with rebates and discrete monitoring correction
+///! * Digital and gap options with analytic greeks
+///! * Geometric and arithmetic Asian options with Monte Carlo control variates
+///! * Floating and fixed strike lookback options
+///! * Margrabe exchange, Kirk and Bjerksund-Stensland spread and Stulz min/max options
+///! * Quanto and composite options on foreign underlyings with fx and correlation greeks
+///! * Geske compound, simple and complex chooser, forward start and cliquet options
+///! * Variance swap replication, a VIX style index and convexity adjusted volatility swaps
+///! * Breeden-Litzenberger risk neutral densities, implied moments and probabilities of touching or finishing in the money
+///! * Dupire local volatility from implied vols or call prices, with Monte Carlo and PDE pricing
+///! * Positions and portfolios with batch priced greeks netted per underlying and expiry bucket
+///! * Scenario P&L ladders over spot, vol and skew shocks and time decay rolls
+///! * Historical, delta-gamma-normal and delta-gamma Monte Carlo value at risk and expected shortfall
+///! * P&L attribution between market snapshots into delta, gamma, vega, theta, rho, vanna and volga
+///! * Discrete delta hedging simulation with rebalancing rules and transaction costs
+///! * Multi-leg strategies with net greeks, payoff diagrams, breakevens, max profit and loss and probability of profit
+///! * Dates, holiday calendars and ACT/365F, ACT/360 and BUS/252 day counts for intraday time to expiry
 ///!
 ///! This library depends on the [wide](https://crates.io/crates/wide) library which provides the crucial math functions exp/log/pow/cdf in vectorised versions. This makes the difference of over 50%
 ///! compared to the serial versions of this function.
@@ -18,8 +43,37 @@
 ///! On an i5 7300HQ I'm seeing 100,000,000 prices calculated per second.  YMMV
 ///!
 ///! Compared to a serialised version of around 1800ms
+pub mod american;
+pub mod asian;
+pub mod attribution;
+pub mod barrier;
 pub mod bs;
 mod bs_f32x8_;
 pub mod bs_single;
+mod complex;
+pub mod compound;
+pub mod day_count;
+pub mod density;
+pub mod digital;
+mod fourier;
+pub mod hedging;
+pub mod heston;
+pub mod jumps;
+pub mod local_vol;
+pub mod lookback;
+pub mod lsm;
+pub mod monte_carlo;
+mod normal;
+pub mod pde;
+pub mod portfolio;
+pub mod quanto;
+pub mod quasi_random;
+pub mod scenario;
+mod solver;
+pub mod strategy;
+pub mod trinomial;
+pub mod two_asset;
+pub mod value_at_risk;
+pub mod variance;
 pub use bs::*;
 pub use bs_single::*;
@@ -0,0 +1,505 @@
+//! Dupire (1994) local volatility
+//!
+//! The local vol surface reproduces the vanilla prices it was built from when the spot follows
+//! dS / S = (r - q) dt + sigma(S, t) dW. It is built on a grid of strikes and expiries either
+//!
+//! * from implied vols, by Gatheral's form of the Dupire equation in the total implied variance w(y, T) at the log
+//!   moneyness y = ln(K / F(T)), with the time derivative taken at constant moneyness, or
+//! * from call prices, through their implied vols. Differentiating the prices directly as
+//!   sigma^2 = (C_T + (r - q) K C_K + q C) / (0.5 K^2 C_KK) is the same equation, but the prices vary like the
+//!   square root of time near the money and are rounded to f32, while the total variance is smooth
+//!
+//! and is read with bilinear interpolation in the spot and time, flat beyond the grid. The time derivative at the
+//! first expiry looks back to zero total variance at time zero. With fewer than three strikes there is no smile to
+//! differentiate, the strike derivatives are taken as zero and each strike follows its own term structure. A
+//! surface without strikes or expiries reads NaN.
+//!
+//! The equation breaks down where the prices admit arbitrage, a density (the denominator) that is not positive or a
+//! calendar spread (the numerator) that is negative, and is unstable where the density is tiny in the wings. Such
+//! points are dropped and refilled by linear interpolation in strike from the valid points of the same expiry, or
+//! copied from the previous expiry when none are valid, as are prices with no implied vol, and every local vol is
+//! clamped to
+//! [`MIN_LOCAL_VOL`, `MAX_LOCAL_VOL`].
+//!
+//! Exotics can then be priced consistently with the vanilla surface by Monte Carlo, with `Model::LocalVol`, or on the
+//! finite difference grid of the PDE solver.
+use crate::bs::OptionDir;
+use crate::pde::{solve, Barrier, Exercise, PdeParams, PdeResult};
+use crate::solver::black_scholes_implied_vol;
+
+pub const MIN_LOCAL_VOL: f32 = 0.01;
+pub const MAX_LOCAL_VOL: f32 = 3.0;
+/// Densities below this, relative to the strike, are treated as zero
+const DENSITY_FLOOR: f64 = 1.0e-7;
+
+/// Local vols on a grid of ascending strikes and expiries, stored expiry by expiry
+#[derive(PartialEq, Debug, Clone)]
+pub struct LocalVolSurface {
+    pub strikes: Vec<f32>,
+    pub expiries: Vec<f32>,
+    pub vols: Vec<f32>,
+}
+
+/// Derivative at the middle of three points from the slopes either side, each weighted by the distance to the other
+fn first_derivative(x: [f64; 3], y: [f64; 3]) -> f64 {
+    let (h0, h1) = (x[1] - x[0], x[2] - x[1]);
+    ((y[1] - y[0]) / h0 * h1 + (y[2] - y[1]) / h1 * h0) / (h0 + h1)
+}
+
+fn second_derivative(x: [f64; 3], y: [f64; 3]) -> f64 {
+    let (h0, h1) = (x[1] - x[0], x[2] - x[1]);
+    2.0 * ((y[2] - y[1]) / h1 - (y[1] - y[0]) / h0) / (h0 + h1)
+}
+
+/// First and second derivatives along a row, the end points taking those of their neighbours. Zero for rows of
+/// fewer than three points
+fn row_derivatives(x: &[f64], y: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let n = x.len();
+    let (mut first, mut second) = (vec![0.0; n], vec![0.0; n]);
+    if n < 3 {
+        return (first, second);
+    }
+    for i in 1..n - 1 {
+        let (xs, ys) = ([x[i - 1], x[i], x[i + 1]], [y[i - 1], y[i], y[i + 1]]);
+        first[i] = first_derivative(xs, ys);
+        second[i] = second_derivative(xs, ys);
+    }
+    first[0] = first[1];
+    second[0] = second[1];
+    first[n - 1] = first[n - 2];
+    second[n - 1] = second[n - 2];
+    (first, second)
+}
+
+/// Linear interpolation, flat beyond the ends
+fn interpolate(x: &[f32], y: &[f32], at: f32) -> f32 {
+    let i = x.partition_point(|&x| x < at);
+    if i == 0 {
+        y[0]
+    } else if i == x.len() {
+        y[x.len() - 1]
+    } else {
+        let w = (at - x[i - 1]) / (x[i] - x[i - 1]);
+        y[i - 1] + w * (y[i] - y[i - 1])
+    }
+}
+
+/// Time derivative at expiry i from the values at the expiries before and after, the first expiry looking back to
+/// time zero
+fn time_derivative(expiries: &[f64], before: f64, value: f64, after: Option<f64>, i: usize) -> f64 {
+    let (t0, t) = (if i == 0 { 0.0 } else { expiries[i - 1] }, expiries[i]);
+    match after {
+        Some(after) => first_derivative([t0, t, expiries[i + 1]], [before, value, after]),
+        None => (value - before) / (t - t0),
+    }
+}
+
+/// Expiry by expiry, replace the missing values by linear interpolation in strike from the others, or by the
+/// previous expiry when there are none, and clamp
+fn fill(strikes: &[f32], values: Vec<Option<f64>>, min: f32, max: f32) -> Vec<f32> {
+    let n = strikes.len();
+    if n == 0 {
+        return vec![];
+    }
+    let mut filled: Vec<f32> = Vec::with_capacity(values.len());
+    for (i, row) in values.chunks(n).enumerate() {
+        let (x, y): (Vec<f32>, Vec<f32>) = strikes
+            .iter()
+            .zip(row)
+            .filter_map(|(&k, v)| v.map(|v| (k, v as f32)))
+            .unzip();
+        let row: Vec<f32> = if !x.is_empty() {
+            strikes.iter().map(|&k| interpolate(&x, &y, k)).collect()
+        } else if i > 0 {
+            filled[(i - 1) * n..i * n].to_vec()
+        } else {
+            vec![min; n]
+        };
+        filled.extend(row.iter().map(|v| v.clamp(min, max)));
+    }
+    filled
+}
+
+impl LocalVolSurface {
+    /// Local vols from implied vols at ascending strikes and expiries, given expiry by expiry
+    /// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+    pub fn from_implied_vols(
+        spot: f32,
+        strikes: &[f32],
+        expiries: &[f32],
+        implied_vols: &[f32],
+        risk_free_rate: f32,
+        dividend_yield: f32,
+    ) -> LocalVolSurface {
+        let n = strikes.len();
+        let carry = (risk_free_rate - dividend_yield) as f64;
+        let forward = |t: f64| spot as f64 * (carry * t).exp();
+        let times: Vec<f64> = expiries.iter().map(|&t| t as f64).collect();
+        let row = |i: usize| &implied_vols[i * n..(i + 1) * n];
+        // Total implied variance at expiry i for the moneyness of strike k at expiry j
+        let total_variance = |i: usize, k: f64, j: usize| {
+            let moved = k * forward(times[i]) / forward(times[j]);
+            let v = interpolate(strikes, row(i), moved as f32) as f64;
+            v * v * times[i]
+        };
+        let mut local = Vec::with_capacity(implied_vols.len());
+        for (i, &t) in times.iter().enumerate() {
+            let y: Vec<f64> = strikes
+                .iter()
+                .map(|&k| (k as f64 / forward(t)).ln())
+                .collect();
+            let w: Vec<f64> = row(i).iter().map(|&v| v as f64 * v as f64 * t).collect();
+            let (wy, wyy) = row_derivatives(&y, &w);
+            local.extend((0..n).map(|j| {
+                let k = strikes[j] as f64;
+                let before = i.checked_sub(1).map_or(0.0, |p| total_variance(p, k, i));
+                let after = (i + 1 < times.len()).then(|| total_variance(i + 1, k, i));
+                let wt = time_derivative(&times, before, w[j], after, i);
+                let denominator = 1.0 - y[j] / w[j] * wy[j]
+                    + 0.25 * (-0.25 - 1.0 / w[j] + y[j] * y[j] / (w[j] * w[j])) * wy[j] * wy[j]
+                    + 0.5 * wyy[j];
+                (wt > 0.0 && denominator > DENSITY_FLOOR).then(|| (wt / denominator).sqrt())
+            }));
+        }
+        LocalVolSurface::from_valid(strikes, expiries, local)
+    }
+
+    /// Local vols from call prices at ascending strikes and expiries, given expiry by expiry. The prices are turned
+    /// into implied vols, below the forward through the put by parity, which keeps the total variance smooth in
+    /// time where differentiating the prices directly is not
+    /// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+    pub fn from_call_prices(
+        spot: f32,
+        strikes: &[f32],
+        expiries: &[f32],
+        call_prices: &[f32],
+        risk_free_rate: f32,
+        dividend_yield: f32,
+    ) -> LocalVolSurface {
+        let n = strikes.len();
+        let (r, q) = (risk_free_rate as f64, dividend_yield as f64);
+        let implied: Vec<Option<f64>> = call_prices
+            .iter()
+            .enumerate()
+            .map(|(index, &call)| {
+                let (t, k) = (expiries[index / n], strikes[index % n]);
+                let forward = spot as f64 * ((r - q) * t as f64).exp();
+                let (dir, price) = if (k as f64) < forward {
+                    let parity = (forward - k as f64) * (-r * t as f64).exp();
+                    (OptionDir::PUT, (call as f64 - parity) as f32)
+                } else {
+                    (OptionDir::CALL, call)
+                };
+                let v = black_scholes_implied_vol(
+                    dir,
+                    price,
+                    spot,
+                    k,
+                    t,
+                    risk_free_rate,
+                    dividend_yield,
+                );
+                v.is_finite().then_some(v as f64)
+            })
+            .collect();
+        let implied = fill(strikes, implied, 0.0, f32::MAX);
+        LocalVolSurface::from_implied_vols(
+            spot,
+            strikes,
+            expiries,
+            &implied,
+            risk_free_rate,
+            dividend_yield,
+        )
+    }
+
+    /// Fill the points where the Dupire equation failed and clamp
+    fn from_valid(strikes: &[f32], expiries: &[f32], local: Vec<Option<f64>>) -> LocalVolSurface {
+        LocalVolSurface {
+            strikes: strikes.to_vec(),
+            expiries: expiries.to_vec(),
+            vols: fill(strikes, local, MIN_LOCAL_VOL, MAX_LOCAL_VOL),
+        }
+    }
+
+    /// Local vol at a spot and a time from now
+    pub fn vol(&self, spot: f32, time: f32) -> f32 {
+        if self.vols.is_empty() {
+            return f32::NAN;
+        }
+        let n = self.strikes.len();
+        let at = |i: usize| interpolate(&self.strikes, &self.vols[i * n..(i + 1) * n], spot);
+        let i = self.expiries.partition_point(|&t| t < time);
+        if i == 0 {
+            at(0)
+        } else if i == self.expiries.len() {
+            at(i - 1)
+        } else {
+            let w = (time - self.expiries[i - 1]) / (self.expiries[i] - self.expiries[i - 1]);
+            at(i - 1) + w * (at(i) - at(i - 1))
+        }
+    }
+}
+
+/// Finite difference price and greeks of a European or American option, optionally with a barrier, under local
+/// volatility
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn local_vol_pde_price(
+    option_dir: OptionDir,
+    exercise: Exercise,
+    barrier: Option<Barrier>,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    surface: &LocalVolSurface,
+    params: &PdeParams,
+) -> PdeResult {
+    // Wide enough for the highest vol near the money
+    let grid_volatility = [0.8, 1.0, 1.25]
+        .iter()
+        .map(|m| surface.vol(spot * m, years_to_expiry))
+        .fold(0.0f32, f32::max) as f64;
+    solve(
+        option_dir,
+        exercise,
+        barrier,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        grid_volatility,
+        &|s, t| surface.vol(s as f32, t as f32) as f64,
+        params,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs::bs_call;
+    use crate::bs_single::bs_price;
+    use crate::monte_carlo::{monte_carlo, EuropeanPayoff, Model, MonteCarloParams, Sampler};
+    use crate::pde::EarlyExercise;
+
+    const SPOT: f32 = 100.0;
+    const R: f32 = 0.03;
+    const Q: f32 = 0.01;
+
+    fn strikes() -> Vec<f32> {
+        (0..=80).map(|i| 40.0 + 2.5 * i as f32).collect()
+    }
+
+    fn expiries() -> Vec<f32> {
+        vec![0.1, 0.25, 0.5, 0.75, 1.0, 1.5]
+    }
+
+    /// A smile with a downward skew flattening with expiry
+    fn implied_vol(strike: f32, t: f32) -> f32 {
+        let y = (strike / (SPOT * ((R - Q) * t).exp())).ln() / t.sqrt();
+        (0.22 - 0.04 * y + 0.02 * y * y).max(0.05)
+    }
+
+    /// The vanilla surface as the crate prices it
+    fn call_prices(implied_vols: &[f32]) -> Vec<f32> {
+        call_prices_at(&strikes(), implied_vols)
+    }
+
+    /// The same at other strikes
+    fn call_prices_at(strikes: &[f32], implied_vols: &[f32]) -> Vec<f32> {
+        let expiries = expiries();
+        let size = implied_vols.len();
+        let strike_grid: Vec<f32> = expiries.iter().flat_map(|_| strikes.to_vec()).collect();
+        let expiry_grid: Vec<f32> = expiries
+            .iter()
+            .flat_map(|&t| vec![t; strikes.len()])
+            .collect();
+        let mut calls = bs_call(
+            &vec![SPOT; size],
+            &strike_grid,
+            &expiry_grid,
+            &vec![R; size],
+            implied_vols,
+            &vec![Q; size],
+        );
+        calls.truncate(size);
+        calls
+    }
+
+    fn surface() -> Vec<f32> {
+        expiries()
+            .iter()
+            .flat_map(|&t| strikes().into_iter().map(move |k| implied_vol(k, t)))
+            .collect()
+    }
+
+    const PDE: PdeParams = PdeParams {
+        space_steps: 300,
+        time_steps: 200,
+        rannacher_steps: 2,
+        concentration: 0.2,
+        early_exercise: EarlyExercise::BrennanSchwartz,
+    };
+
+    #[test]
+    fn flat_surface() {
+        let (strikes, expiries) = (strikes(), expiries());
+        let flat = vec![0.25; strikes.len() * expiries.len()];
+        let from_vols = LocalVolSurface::from_implied_vols(SPOT, &strikes, &expiries, &flat, R, Q);
+        let from_prices =
+            LocalVolSurface::from_call_prices(SPOT, &strikes, &expiries, &call_prices(&flat), R, Q);
+        for s in [70.0, 90.0, 100.0, 115.0, 140.0] {
+            for t in [0.05, 0.3, 0.8, 2.0] {
+                assert!((from_vols.vol(s, t) - 0.25).abs() < 1.0e-3);
+                // Far in the wings the f32 prices are too small for their implied vols to be smooth
+                let deviations = (s / SPOT).ln().abs() / (0.25 * t.max(expiries[0]).sqrt());
+                if deviations < 3.0 {
+                    assert!((from_prices.vol(s, t) - 0.25).abs() < 0.005);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skew_is_steeper_than_implied() {
+        // For a smile fixed in moneyness the local vol skew near the money is about twice the implied skew
+        let (strikes, expiries) = (strikes(), expiries());
+        let smile = |k: f32, t: f32| 0.22 - 0.08 * (k / (SPOT * ((R - Q) * t).exp())).ln();
+        let implied: Vec<f32> = expiries
+            .iter()
+            .flat_map(|&t| strikes.iter().map(move |&k| smile(k, t)))
+            .collect();
+        let local = LocalVolSurface::from_implied_vols(SPOT, &strikes, &expiries, &implied, R, Q);
+        for t in [0.25, 1.0] {
+            let forward = SPOT * ((R - Q) * t).exp();
+            let slope = |f: &dyn Fn(f32) -> f32| {
+                (f(forward * 1.05) - f(forward / 1.05)) / (2.0 * 1.05f32.ln())
+            };
+            let ratio = slope(&|s| local.vol(s, t)) / slope(&|k| smile(k, t));
+            assert!((ratio - 2.0).abs() < 0.2);
+        }
+    }
+
+    #[test]
+    fn reprices_vanillas() {
+        let (strikes, expiries) = (strikes(), expiries());
+        let implied = surface();
+        let from_vols =
+            LocalVolSurface::from_implied_vols(SPOT, &strikes, &expiries, &implied, R, Q);
+        let from_prices = LocalVolSurface::from_call_prices(
+            SPOT,
+            &strikes,
+            &expiries,
+            &call_prices(&implied),
+            R,
+            Q,
+        );
+        for (dir, strike, t) in [
+            (OptionDir::PUT, 80.0, 1.0),
+            (OptionDir::PUT, 95.0, 0.5),
+            (OptionDir::CALL, 100.0, 1.0),
+            (OptionDir::CALL, 120.0, 1.5),
+        ] {
+            let bs = bs_price(dir, SPOT, strike, t, R, implied_vol(strike, t), Q);
+            for surface in [&from_vols, &from_prices] {
+                let pde = local_vol_pde_price(
+                    dir,
+                    Exercise::European,
+                    None,
+                    SPOT,
+                    strike,
+                    t,
+                    R,
+                    Q,
+                    surface,
+                    &PDE,
+                );
+                assert!((pde.pv - bs).abs() < 0.03);
+            }
+        }
+
+        let params = MonteCarloParams {
+            paths: 20_000,
+            steps: 50,
+            seed: 7,
+            antithetic: true,
+            sampler: Sampler::PseudoRandom,
+            brownian_bridge: false,
+        };
+        let model = Model::LocalVol(from_vols);
+        for strike in [85.0, 100.0, 115.0] {
+            let payoff = EuropeanPayoff {
+                option_dir: OptionDir::CALL,
+                strike,
+            };
+            let mc = monte_carlo(&payoff, &model, SPOT, 1.0, R, Q, &params);
+            let bs = bs_price(
+                OptionDir::CALL,
+                SPOT,
+                strike,
+                1.0,
+                R,
+                implied_vol(strike, 1.0),
+                Q,
+            );
+            assert!((mc.price - bs).abs() < 3.0 * mc.std_error + 0.05);
+        }
+    }
+
+    #[test]
+    fn safeguards() {
+        // Total variance falling between the last two expiries is a calendar arbitrage, and a spike in the smile
+        // makes the density negative either side of it
+        let (strikes, expiries) = (strikes(), expiries());
+        let mut implied = surface();
+        let n = strikes.len();
+        for v in implied[4 * n..5 * n].iter_mut() {
+            *v = 0.3;
+        }
+        for v in implied[5 * n..].iter_mut() {
+            *v = 0.15;
+        }
+        implied[2 * n + 24] += 0.1;
+        for surface in [
+            LocalVolSurface::from_implied_vols(SPOT, &strikes, &expiries, &implied, R, Q),
+            LocalVolSurface::from_call_prices(
+                SPOT,
+                &strikes,
+                &expiries,
+                &call_prices(&implied),
+                R,
+                Q,
+            ),
+        ] {
+            assert!(surface
+                .vols
+                .iter()
+                .all(|v| v.is_finite() && (MIN_LOCAL_VOL..=MAX_LOCAL_VOL).contains(v)));
+        }
+
+        // Too few strikes to differentiate in, a flat smile keeps its level
+        for strikes in [vec![100.0], vec![90.0, 110.0]] {
+            let flat = vec![0.25; strikes.len() * expiries.len()];
+            let surface =
+                LocalVolSurface::from_implied_vols(SPOT, &strikes, &expiries, &flat, R, Q);
+            assert!((surface.vol(100.0, 0.5) - 0.25).abs() < 1.0e-3);
+            let surface = LocalVolSurface::from_call_prices(
+                SPOT,
+                &strikes,
+                &expiries,
+                &call_prices_at(&strikes, &flat),
+                R,
+                Q,
+            );
+            assert!((surface.vol(100.0, 0.5) - 0.25).abs() < 1.0e-3);
+        }
+        let empty = LocalVolSurface::from_implied_vols(SPOT, &[], &expiries, &[], R, Q);
+        assert!(empty.vol(100.0, 0.5).is_nan());
+        let empty = LocalVolSurface::from_call_prices(SPOT, &[], &expiries, &[], R, Q);
+        assert!(empty.vol(100.0, 0.5).is_nan());
+    }
+}
@@ -0,0 +1,529 @@
+//! Lookback options on the maximum or minimum of the spot
+//!
+//! * Floating strike, Goldman, Sosin and Gatto (1979): a call pays the spot at expiry less the minimum, a put the
+//!   maximum less the spot at expiry
+//! * Fixed strike, Conze and Viswanathan (1991): a call pays the maximum less the strike, a put the strike less the
+//!   minimum
+//!
+//! Seasoned trades pass the running minimum or maximum observed so far, new trades the spot. All four reduce to
+//! the expected excess of the future maximum over a level (or of a level over the future minimum), which for
+//! discrete monitoring is corrected after Broadie, Glasserman and Kou (1999) by shifting the level by
+//! exp(0.5826 vol sqrt(monitoring interval)).
+//!
+//! The scalar versions are computed in f64, the batch versions eight at a time in f32x8.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_::{ncd_f32x8, npd_f32x8};
+use crate::normal::{ncd_f64, npd_f64};
+use bytemuck::cast;
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// -zeta(1/2) / sqrt(2 pi), the Broadie-Glasserman-Kou shift
+const BGK_BETA: f64 = 0.5826;
+
+/// Discounted expected excess of the maximum over `h` for eta = 1, of `h` over the minimum for eta = -1, with the
+/// level on the far side of the spot. `shift` is the discrete monitoring correction, 0 when continuous
+#[allow(clippy::too_many_arguments)]
+fn excess(eta: f64, s: f64, h: f64, t: f64, r: f64, b: f64, v: f64, shift: f64) -> f64 {
+    let h = h * (eta * shift).exp();
+    let sd = v * t.sqrt();
+    let d1 = ((s / h).ln() + (b + v * v / 2.0) * t) / sd;
+    let d2 = d1 - sd;
+    let vanilla = eta
+        * (s * ((b - r) * t).exp() * ncd_f64(eta * d1) - h * (-r * t).exp() * ncd_f64(eta * d2));
+    // The v^2 / 2b term tends to a finite limit as the carry vanishes
+    let path = if b.abs() < 1.0e-6 {
+        eta * (-r * t).exp()
+            * s
+            * (ncd_f64(eta * d1) * ((s / h).ln() + v * v * t / 2.0) + eta * sd * npd_f64(d1))
+    } else {
+        eta * (-r * t).exp() * s * v * v / (2.0 * b)
+            * ((b * t).exp() * ncd_f64(eta * d1)
+                - (s / h).powf(-2.0 * b / (v * v)) * ncd_f64(eta * (d1 - 2.0 * b * t.sqrt() / v)))
+    };
+    (-eta * shift).exp() * (vanilla + path)
+}
+
+fn shift(volatility: f32, monitoring_interval: f32) -> f64 {
+    BGK_BETA * volatility as f64 * (monitoring_interval as f64).sqrt()
+}
+
+/// Floating strike lookback price. `running_extreme` is the minimum observed so far for a call and the maximum
+/// for a put, the spot for a new trade. The extreme is monitored every `monitoring_interval` years, 0 for
+/// continuous monitoring
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn floating_lookback_price(
+    option_dir: OptionDir,
+    spot: f32,
+    running_extreme: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    monitoring_interval: f32,
+) -> f32 {
+    let (s, t, r, v) = (
+        spot as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        volatility as f64,
+    );
+    let b = r - dividend_yield as f64;
+    let shift = shift(volatility, monitoring_interval);
+    let forward = s * ((b - r) * t).exp();
+    let discount = (-r * t).exp();
+    let price = match option_dir {
+        OptionDir::CALL => {
+            let minimum = (running_extreme as f64).min(s);
+            forward - minimum * discount + excess(-1.0, s, minimum, t, r, b, v, shift)
+        }
+        OptionDir::PUT => {
+            let maximum = (running_extreme as f64).max(s);
+            maximum * discount - forward + excess(1.0, s, maximum, t, r, b, v, shift)
+        }
+    };
+    price as f32
+}
+
+/// Fixed strike lookback price. `running_extreme` is the maximum observed so far for a call and the minimum for a
+/// put, the spot for a new trade. The extreme is monitored every `monitoring_interval` years, 0 for continuous
+/// monitoring
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn fixed_lookback_price(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    running_extreme: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    monitoring_interval: f32,
+) -> f32 {
+    let (s, k, t, r, v) = (
+        spot as f64,
+        strike as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        volatility as f64,
+    );
+    let b = r - dividend_yield as f64;
+    let shift = shift(volatility, monitoring_interval);
+    let discount = (-r * t).exp();
+    let price = match option_dir {
+        OptionDir::CALL => {
+            let maximum = (running_extreme as f64).max(s);
+            discount * (maximum - k).max(0.0) + excess(1.0, s, k.max(maximum), t, r, b, v, shift)
+        }
+        OptionDir::PUT => {
+            let minimum = (running_extreme as f64).min(s);
+            discount * (k - minimum).max(0.0) + excess(-1.0, s, k.min(minimum), t, r, b, v, shift)
+        }
+    };
+    price as f32
+}
+
+/// `excess` eight at a time. In f32 the v^2 / 2b term loses its digits well before the carry reaches 0, so the
+/// limit takes over from a larger carry than in f64
+#[allow(clippy::too_many_arguments)]
+fn excess_f32x8(
+    eta: f32,
+    s: f32x8,
+    h: f32x8,
+    t: f32x8,
+    r: f32x8,
+    b: f32x8,
+    v: f32x8,
+    shift: f32x8,
+) -> f32x8 {
+    let eta = f32x8::splat(eta);
+    let h = h * (eta * shift).exp();
+    let sd = v * t.sqrt();
+    let d1 = ((s / h).ln() + (b + v * v * 0.5) * t) / sd;
+    let d2 = d1 - sd;
+    let discount = (-r * t).exp();
+    let vanilla =
+        eta * (s * ((b - r) * t).exp() * ncd_f32x8(eta * d1) - h * discount * ncd_f32x8(eta * d2));
+    let limit = eta
+        * discount
+        * s
+        * (ncd_f32x8(eta * d1) * ((s / h).ln() + v * v * t * 0.5) + eta * sd * npd_f32x8(d1));
+    let power = (-2.0 * b / (v * v) * (s / h).ln()).exp();
+    let path = eta * discount * s * v * v / (2.0 * b)
+        * ((b * t).exp() * ncd_f32x8(eta * d1)
+            - power * ncd_f32x8(eta * (d1 - 2.0 * b * t.sqrt() / v)));
+    let path = b.abs().cmp_lt(f32x8::splat(3.0e-4)).blend(limit, path);
+    (-eta * shift).exp() * (vanilla + path)
+}
+
+/// Lookback prices eight at a time, `strike` is None for floating strikes
+#[allow(clippy::too_many_arguments)]
+fn batch_price(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: Option<&[f32]>,
+    running_extreme: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    let root_interval = BGK_BETA as f32 * monitoring_interval.sqrt();
+    for i in (0..max_idx).step_by(8) {
+        let s = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let extreme = f32x8::from(&running_extreme[i..std::cmp::min(max_idx, i + 8)]);
+        let t = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let r = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let v = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let q = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let b = r - q;
+        let shift = v * root_interval;
+        let discount = (-r * t).exp();
+        let forward = s * (-q * t).exp();
+        let price = match (strike, option_dir) {
+            (None, OptionDir::CALL) => {
+                let minimum = extreme.min(s);
+                forward - minimum * discount + excess_f32x8(-1.0, s, minimum, t, r, b, v, shift)
+            }
+            (None, OptionDir::PUT) => {
+                let maximum = extreme.max(s);
+                maximum * discount - forward + excess_f32x8(1.0, s, maximum, t, r, b, v, shift)
+            }
+            (Some(strike), OptionDir::CALL) => {
+                let k = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+                let maximum = extreme.max(s);
+                discount * (maximum - k).max(f32x8::ZERO)
+                    + excess_f32x8(1.0, s, k.max(maximum), t, r, b, v, shift)
+            }
+            (Some(strike), OptionDir::PUT) => {
+                let k = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+                let minimum = extreme.min(s);
+                discount * (k - minimum).max(f32x8::ZERO)
+                    + excess_f32x8(-1.0, s, k.min(minimum), t, r, b, v, shift)
+            }
+        };
+        let price: [f32; 8] = cast(price);
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Floating strike lookback call pricing, paying the spot at expiry less the minimum. The results are at the same
+/// index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn floating_lookback_call(
+    spot: &[f32],
+    running_min: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::CALL,
+        spot,
+        None,
+        running_min,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+/// Floating strike lookback put pricing, paying the maximum less the spot at expiry. The results are at the same
+/// index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn floating_lookback_put(
+    spot: &[f32],
+    running_max: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::PUT,
+        spot,
+        None,
+        running_max,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+/// Fixed strike lookback call pricing, paying the maximum less the strike. The results are at the same index as
+/// the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn fixed_lookback_call(
+    spot: &[f32],
+    strike: &[f32],
+    running_max: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::CALL,
+        spot,
+        Some(strike),
+        running_max,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+/// Fixed strike lookback put pricing, paying the strike less the minimum. The results are at the same index as
+/// the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn fixed_lookback_put(
+    spot: &[f32],
+    strike: &[f32],
+    running_min: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    monitoring_interval: f32,
+) -> Vec<f32> {
+    batch_price(
+        OptionDir::PUT,
+        spot,
+        Some(strike),
+        running_min,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        monitoring_interval,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monte_carlo::{monte_carlo, Model, MonteCarloParams, Payoff, Sampler};
+
+    struct LookbackPayoff {
+        option_dir: OptionDir,
+        strike: Option<f32>,
+        spot: f32,
+    }
+
+    impl Payoff for LookbackPayoff {
+        fn payoff(&self, path: &[f32]) -> f32 {
+            let maximum = path.iter().fold(self.spot, |m, &s| m.max(s));
+            let minimum = path.iter().fold(self.spot, |m, &s| m.min(s));
+            let last = path[path.len() - 1];
+            match (self.strike, self.option_dir) {
+                (None, OptionDir::CALL) => last - minimum,
+                (None, OptionDir::PUT) => maximum - last,
+                (Some(k), OptionDir::CALL) => (maximum - k).max(0.0),
+                (Some(k), OptionDir::PUT) => (k - minimum).max(0.0),
+            }
+        }
+    }
+
+    #[test]
+    fn reference_values() {
+        // Goldman-Sosin-Gatto's formula as printed in Haug, for a floating strike call with a running minimum
+        let call = floating_lookback_price(OptionDir::CALL, 120.0, 100.0, 0.5, 0.1, 0.3, 0.04, 0.0);
+        assert!((call - 26.2842).abs() < 0.001);
+        // A fixed strike call struck at the running maximum has the floating put's optionality
+        let fixed = fixed_lookback_price(
+            OptionDir::CALL,
+            100.0,
+            110.0,
+            110.0,
+            1.0,
+            0.05,
+            0.25,
+            0.01,
+            0.0,
+        );
+        let floating =
+            floating_lookback_price(OptionDir::PUT, 100.0, 110.0, 1.0, 0.05, 0.25, 0.01, 0.0);
+        let forward = 100.0 * (-0.01f32).exp() - 110.0 * (-0.05f32).exp();
+        assert!((fixed - (floating + forward)).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn discrete_monitoring_against_monte_carlo() {
+        let params = MonteCarloParams {
+            paths: 40_000,
+            steps: 50,
+            seed: 5,
+            antithetic: true,
+            sampler: Sampler::PseudoRandom,
+            brownian_bridge: false,
+        };
+        let model = Model::BlackScholes { volatility: 0.3 };
+        let interval = 1.0 / 50.0;
+        for (dir, strike) in [
+            (OptionDir::CALL, None),
+            (OptionDir::PUT, None),
+            (OptionDir::CALL, Some(105.0)),
+            (OptionDir::PUT, Some(95.0)),
+        ] {
+            let payoff = LookbackPayoff {
+                option_dir: dir,
+                strike,
+                spot: 100.0,
+            };
+            let mc = monte_carlo(&payoff, &model, 100.0, 1.0, 0.05, 0.02, &params);
+            let price = |interval| match strike {
+                None => floating_lookback_price(dir, 100.0, 100.0, 1.0, 0.05, 0.3, 0.02, interval),
+                Some(k) => {
+                    fixed_lookback_price(dir, 100.0, k, 100.0, 1.0, 0.05, 0.3, 0.02, interval)
+                }
+            };
+            let discrete = price(interval);
+            assert!((discrete - mc.price).abs() < 3.0 * mc.std_error + 0.05);
+            // Continuous monitoring sees more extreme extremes
+            assert!(price(0.0) - discrete > 0.5);
+        }
+    }
+
+    #[test]
+    fn zero_carry_limit() {
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            let at = |q| floating_lookback_price(dir, 100.0, 100.0, 1.0, 0.04, 0.25, q, 0.0);
+            assert!(((at(0.04) - (at(0.0399) + at(0.0401)) / 2.0).abs()) < 1.0e-3);
+            let fixed = |q| fixed_lookback_price(dir, 100.0, 100.0, 100.0, 1.0, 0.04, 0.25, q, 0.0);
+            assert!(((fixed(0.04) - (fixed(0.0399) + fixed(0.0401)) / 2.0).abs()) < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        // Zero and nearly zero carry, on both sides of where the batch switches to the limit of the v^2 / 2b term
+        let spot = [100.0, 100.0, 60.0, 100.0, 150.0, 100.0, 100.0, 80.0, 100.0];
+        let strike = [100.0, 95.0, 60.0, 110.0, 140.0, 100.0, 105.0, 85.0, 90.0];
+        let years_to_expiry = [1.0, 0.25, 2.0, 0.5, 0.05, 3.0, 0.75, 1.5, 0.1];
+        let risk_free_rate = [0.0, 0.05, 0.03, 0.02, 0.04, 0.01, 0.06, 0.0, 0.08];
+        let carry = [
+            0.0, 0.0, 1.0e-5, -1.0e-5, 1.0e-4, -2.0e-4, 5.0e-4, -1.0e-3, 0.0,
+        ];
+        let dividend_yield: Vec<f32> = risk_free_rate
+            .iter()
+            .zip(carry)
+            .map(|(r, b)| r - b)
+            .collect();
+        let volatility = [0.2, 0.1, 0.35, 0.25, 0.5, 0.15, 0.3, 0.4, 0.6];
+        let running_min: Vec<f32> = spot.iter().map(|s| s * 0.95).collect();
+        let running_max: Vec<f32> = spot.iter().map(|s| s * 1.05).collect();
+        for interval in [0.0, 1.0 / 252.0] {
+            let batches = [
+                (
+                    OptionDir::CALL,
+                    None,
+                    &running_min,
+                    floating_lookback_call(
+                        &spot,
+                        &running_min,
+                        &years_to_expiry,
+                        &risk_free_rate,
+                        &volatility,
+                        &dividend_yield,
+                        interval,
+                    ),
+                ),
+                (
+                    OptionDir::PUT,
+                    None,
+                    &running_max,
+                    floating_lookback_put(
+                        &spot,
+                        &running_max,
+                        &years_to_expiry,
+                        &risk_free_rate,
+                        &volatility,
+                        &dividend_yield,
+                        interval,
+                    ),
+                ),
+                (
+                    OptionDir::CALL,
+                    Some(strike),
+                    &running_max,
+                    fixed_lookback_call(
+                        &spot,
+                        &strike,
+                        &running_max,
+                        &years_to_expiry,
+                        &risk_free_rate,
+                        &volatility,
+                        &dividend_yield,
+                        interval,
+                    ),
+                ),
+                (
+                    OptionDir::PUT,
+                    Some(strike),
+                    &running_min,
+                    fixed_lookback_put(
+                        &spot,
+                        &strike,
+                        &running_min,
+                        &years_to_expiry,
+                        &risk_free_rate,
+                        &volatility,
+                        &dividend_yield,
+                        interval,
+                    ),
+                ),
+            ];
+            for (dir, strike, extreme, prices) in batches.iter() {
+                assert_eq!(prices.len(), spot.len());
+                for i in 0..spot.len() {
+                    let (t, r, v, q) = (
+                        years_to_expiry[i],
+                        risk_free_rate[i],
+                        volatility[i],
+                        dividend_yield[i],
+                    );
+                    let scalar = match strike {
+                        None => {
+                            floating_lookback_price(*dir, spot[i], extreme[i], t, r, v, q, interval)
+                        }
+                        Some(k) => fixed_lookback_price(
+                            *dir, spot[i], k[i], extreme[i], t, r, v, q, interval,
+                        ),
+                    };
+                    assert!((prices[i] - scalar).abs() < 2.0e-3 * (1.0 + scalar));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! Longstaff-Schwartz (2001) least squares Monte Carlo for American and Bermudan options
+//!
+//! The continuation value at each exercise date is estimated by regressing the discounted future cash flows of the
+//! in the money paths on a set of basis functions of the spot (and the vol under stochastic vol models).
+//! The exercise rule can be estimated on one set of paths and applied to an independent set, which gives a low
+//! biased estimate of the price, i.e. a lower bound.
+use crate::bs::OptionDir;
+use crate::monte_carlo::{generate_paths, Model, MonteCarloParams, MonteCarloResult, Paths};
+
+/// Basis functions of the regression, in terms of moneyness x = spot / strike
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Basis {
+    /// 1, x, x^2, ...
+    Monomial,
+    /// 1 and the weighted Laguerre polynomials exp(-x/2) L_n(x) used in the original paper
+    Laguerre,
+}
+
+/// Least squares settings
+#[derive(Debug, Copy, Clone)]
+pub struct LsmParams {
+    pub basis: Basis,
+    /// Number of basis functions of the spot besides the constant
+    pub degree: usize,
+    /// Paths used to estimate the exercise rule. When 0 the rule is estimated on the pricing paths themselves,
+    /// otherwise on an independent set of paths so the price is a lower bound
+    pub regression_paths: usize,
+}
+
+/// Regression features of a path at one date
+fn features(x: f64, vol: Option<f64>, lsm: &LsmParams, row: &mut Vec<f64>) {
+    row.clear();
+    row.push(1.0);
+    match lsm.basis {
+        Basis::Monomial => {
+            let mut p = 1.0;
+            for _ in 0..lsm.degree {
+                p *= x;
+                row.push(p);
+            }
+        }
+        Basis::Laguerre => {
+            let w = (-x / 2.0).exp();
+            let (mut l0, mut l1) = (1.0, 1.0 - x);
+            for n in 0..lsm.degree {
+                row.push(w * l0);
+                let l2 =
+                    ((2.0 * n as f64 + 3.0 - x) * l1 - (n as f64 + 1.0) * l0) / (n as f64 + 2.0);
+                l0 = l1;
+                l1 = l2;
+            }
+        }
+    }
+    if let Some(vol) = vol {
+        row.push(vol);
+        row.push(vol * vol);
+        row.push(vol * x);
+    }
+}
+
+/// Ordinary least squares through the normal equations and a Cholesky factorisation.
+/// Returns None when the system is singular
+fn least_squares(rows: &[Vec<f64>], y: &[f64]) -> Option<Vec<f64>> {
+    let n = rows.first()?.len();
+    let mut a = vec![vec![0.0; n]; n];
+    let mut b = vec![0.0; n];
+    for (row, y) in rows.iter().zip(y.iter()) {
+        for i in 0..n {
+            b[i] += row[i] * y;
+            for j in 0..=i {
+                a[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    // Cholesky, lower triangle in place
+    for i in 0..n {
+        for j in 0..=i {
+            let sum = a[i][j] - (0..j).map(|k| a[i][k] * a[j][k]).sum::<f64>();
+            if i == j {
+                if sum <= 1.0e-12 * (1.0 + a[i][i].abs()) {
+                    return None;
+                }
+                a[i][i] = sum.sqrt();
+            } else {
+                a[i][j] = sum / a[j][j];
+            }
+        }
+    }
+    // Forward then back substitution
+    for i in 0..n {
+        let sum: f64 = (0..i).map(|k| a[i][k] * b[k]).sum();
+        b[i] = (b[i] - sum) / a[i][i];
+    }
+    for i in (0..n).rev() {
+        let sum: f64 = (i + 1..n).map(|k| a[k][i] * b[k]).sum();
+        b[i] = (b[i] - sum) / a[i][i];
+    }
+    Some(b)
+}
+
+struct Exercise<'a> {
+    option_dir: OptionDir,
+    strike: f32,
+    /// Path step index of each exercise date, the last one is expiry
+    dates: Vec<usize>,
+    /// Discount factor from one exercise date to the previous one, the first entry discounts to today
+    discount: Vec<f64>,
+    lsm: &'a LsmParams,
+}
+
+impl Exercise<'_> {
+    fn payoff(&self, spot: f32) -> f64 {
+        (match self.option_dir {
+            OptionDir::CALL => spot - self.strike,
+            OptionDir::PUT => self.strike - spot,
+        })
+        .max(0.0) as f64
+    }
+
+    fn vol(&self, paths: &Paths, p: usize, step: usize) -> Option<f64> {
+        paths
+            .variance
+            .get(p * paths.steps + step)
+            .map(|v| (*v as f64).sqrt())
+    }
+
+    /// Backward induction, returns the regression coefficients at each exercise date before expiry
+    /// and the cash flows of each path discounted to today
+    fn regress(&self, paths: &Paths) -> (Vec<Option<Vec<f64>>>, Vec<f64>) {
+        let last = self.dates.len() - 1;
+        let mut cash: Vec<f64> = (0..paths.len())
+            .map(|p| self.payoff(paths.spot[p * paths.steps + self.dates[last]]))
+            .collect();
+        let mut coefficients = vec![None; last];
+        let mut row = Vec::new();
+        for k in (0..last).rev() {
+            let step = self.dates[k];
+            for c in cash.iter_mut() {
+                *c *= self.discount[k + 1];
+            }
+            let mut rows = Vec::new();
+            let mut y = Vec::new();
+            let mut itm = Vec::new();
+            for (p, c) in cash.iter().enumerate() {
+                let spot = paths.spot[p * paths.steps + step];
+                if self.payoff(spot) > 0.0 {
+                    features(
+                        (spot / self.strike) as f64,
+                        self.vol(paths, p, step),
+                        self.lsm,
+                        &mut row,
+                    );
+                    rows.push(row.clone());
+                    y.push(*c);
+                    itm.push(p);
+                }
+            }
+            if rows.len() <= 2 * row.len() {
+                continue;
+            }
+            let beta = least_squares(&rows, &y);
+            if let Some(beta) = &beta {
+                for (r, p) in rows.iter().zip(itm.iter()) {
+                    let continuation: f64 = r.iter().zip(beta.iter()).map(|(a, b)| a * b).sum();
+                    let exercise = self.payoff(paths.spot[p * paths.steps + step]);
+                    if exercise > continuation {
+                        cash[*p] = exercise;
+                    }
+                }
+            }
+            coefficients[k] = beta;
+        }
+        for c in cash.iter_mut() {
+            *c *= self.discount[0];
+        }
+        (coefficients, cash)
+    }
+
+    /// Apply an estimated exercise rule to the paths, returns the cash flows discounted to today
+    fn apply(&self, paths: &Paths, coefficients: &[Option<Vec<f64>>]) -> Vec<f64> {
+        let last = self.dates.len() - 1;
+        let mut row = Vec::new();
+        (0..paths.len())
+            .map(|p| {
+                let mut df = 1.0;
+                for (k, &step) in self.dates.iter().enumerate() {
+                    df *= self.discount[k];
+                    let spot = paths.spot[p * paths.steps + step];
+                    let exercise = self.payoff(spot);
+                    if k == last {
+                        return df * exercise;
+                    }
+                    if let (true, Some(beta)) = (exercise > 0.0, &coefficients[k]) {
+                        features(
+                            (spot / self.strike) as f64,
+                            self.vol(paths, p, step),
+                            self.lsm,
+                            &mut row,
+                        );
+                        let continuation: f64 =
+                            row.iter().zip(beta.iter()).map(|(a, b)| a * b).sum();
+                        if exercise > continuation {
+                            return df * exercise;
+                        }
+                    }
+                }
+                0.0
+            })
+            .collect()
+    }
+}
+
+/// Least squares Monte Carlo price of a Bermudan option
+/// `exercise_times` are the years at which the option can be exercised, rounded to the nearest time step of the
+/// simulation. Expiry is always an exercise date.
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn lsm_bermudan(
+    option_dir: OptionDir,
+    strike: f32,
+    exercise_times: &[f32],
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    mc: &MonteCarloParams,
+    lsm: &LsmParams,
+) -> MonteCarloResult {
+    let steps = mc.steps.max(1);
+    let dt = years_to_expiry / steps as f32;
+    let mut dates: Vec<usize> = exercise_times
+        .iter()
+        .filter(|&&t| t > 0.0 && t <= years_to_expiry)
+        .map(|t| ((t / dt).round() as usize).clamp(1, steps) - 1)
+        .chain(std::iter::once(steps - 1))
+        .collect();
+    dates.sort_unstable();
+    dates.dedup();
+    let mut previous = 0;
+    let discount = dates
+        .iter()
+        .map(|&step| {
+            let periods = (step + 1 - previous) as f64;
+            previous = step + 1;
+            (-(risk_free_rate * dt) as f64 * periods).exp()
+        })
+        .collect();
+    let exercise = Exercise {
+        option_dir,
+        strike,
+        dates,
+        discount,
+        lsm,
+    };
+
+    let paths = generate_paths(
+        model,
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        mc,
+    );
+    let cash = if lsm.regression_paths == 0 {
+        exercise.regress(&paths).1
+    } else {
+        let regression = MonteCarloParams {
+            paths: lsm.regression_paths,
+            seed: mc.seed.wrapping_add(1),
+            ..*mc
+        };
+        let regression = generate_paths(
+            model,
+            spot,
+            years_to_expiry,
+            risk_free_rate,
+            dividend_yield,
+            &regression,
+        );
+        let (coefficients, _) = exercise.regress(&regression);
+        exercise.apply(&paths, &coefficients)
+    };
+    MonteCarloResult::from_samples(&cash, mc.antithetic)
+}
+
+/// Least squares Monte Carlo price of an American option, exercisable at every time step of the simulation
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn lsm_american(
+    option_dir: OptionDir,
+    strike: f32,
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    mc: &MonteCarloParams,
+    lsm: &LsmParams,
+) -> MonteCarloResult {
+    let steps = mc.steps.max(1);
+    let exercise_times: Vec<f32> = (1..=steps)
+        .map(|i| years_to_expiry * i as f32 / steps as f32)
+        .collect();
+    lsm_bermudan(
+        option_dir,
+        strike,
+        &exercise_times,
+        model,
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        mc,
+        lsm,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::{american_binomial, bs_price};
+    use crate::heston::{heston_price, HestonParams};
+    use crate::monte_carlo::Sampler;
+
+    const MC: MonteCarloParams = MonteCarloParams {
+        paths: 20_000,
+        steps: 50,
+        seed: 11,
+        antithetic: true,
+        sampler: Sampler::PseudoRandom,
+        brownian_bridge: false,
+    };
+
+    const LSM: LsmParams = LsmParams {
+        basis: Basis::Laguerre,
+        degree: 3,
+        regression_paths: 0,
+    };
+
+    #[test]
+    fn american_put_against_binomial() {
+        // First case of Longstaff & Schwartz (2001) table 1
+        let model = Model::BlackScholes { volatility: 0.2 };
+        let reference = american_binomial(OptionDir::PUT, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0, 2000);
+        for &basis in [Basis::Laguerre, Basis::Monomial].iter() {
+            let lsm = LsmParams { basis, ..LSM };
+            let res = lsm_american(
+                OptionDir::PUT,
+                40.0,
+                &model,
+                36.0,
+                1.0,
+                0.06,
+                0.0,
+                &MC,
+                &lsm,
+            );
+            assert!((res.price - reference).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn out_of_sample_is_lower_bound() {
+        let model = Model::BlackScholes { volatility: 0.4 };
+        let reference = american_binomial(OptionDir::PUT, 40.0, 40.0, 2.0, 0.06, 0.4, 0.0, 2000);
+        let lsm = LsmParams {
+            regression_paths: 10_000,
+            ..LSM
+        };
+        let res = lsm_american(
+            OptionDir::PUT,
+            40.0,
+            &model,
+            40.0,
+            2.0,
+            0.06,
+            0.0,
+            &MC,
+            &lsm,
+        );
+        assert!(res.price < reference + 2.0 * res.std_error);
+        assert!(res.price > reference - 0.1);
+        let european = bs_price(OptionDir::PUT, 40.0, 40.0, 2.0, 0.06, 0.4, 0.0);
+        assert!(res.lower > european);
+    }
+
+    #[test]
+    fn single_exercise_is_european() {
+        let model = Model::BlackScholes { volatility: 0.25 };
+        let res = lsm_bermudan(
+            OptionDir::PUT,
+            100.0,
+            &[],
+            &model,
+            95.0,
+            0.5,
+            0.04,
+            0.01,
+            &MC,
+            &LSM,
+        );
+        let expected = bs_price(OptionDir::PUT, 95.0, 100.0, 0.5, 0.04, 0.25, 0.01);
+        assert!((res.price - expected).abs() < 3.0 * res.std_error + 0.01);
+
+        // More exercise dates are worth more
+        let quarterly = lsm_bermudan(
+            OptionDir::PUT,
+            100.0,
+            &[0.125, 0.25, 0.375],
+            &model,
+            95.0,
+            0.5,
+            0.04,
+            0.01,
+            &MC,
+            &LSM,
+        );
+        assert!(quarterly.price > res.price);
+    }
+
+    #[test]
+    fn heston_american_put() {
+        let params = HestonParams {
+            v0: 0.04,
+            kappa: 2.0,
+            theta: 0.04,
+            sigma: 0.4,
+            rho: -0.7,
+        };
+        let res = lsm_american(
+            OptionDir::PUT,
+            100.0,
+            &Model::Heston(params),
+            90.0,
+            1.0,
+            0.05,
+            0.0,
+            &MC,
+            &LSM,
+        );
+        let european = heston_price(OptionDir::PUT, 90.0, 100.0, 1.0, 0.05, 0.0, &params);
+        assert!(res.price > european);
+        assert!(res.price < european + 2.0);
+    }
+}
@@ -0,0 +1,883 @@
+//! Monte Carlo pricing of path dependent payoffs
+//!
+//! Paths are simulated eight at a time in `f32x8` lanes from a seeded xoshiro256** generator, so a run
+//! is reproducible for a given seed. Payoffs are supplied through the `Payoff` trait and see one path at a time.
+//! Antithetic sampling and a control variate can be used to reduce the variance of the estimate.
+use crate::bs::{bs_call, OptionDir};
+use crate::heston::{heston_price, HestonParams};
+use crate::local_vol::{local_vol_pde_price, LocalVolSurface};
+use crate::normal::inverse_ncd;
+use crate::pde::{EarlyExercise, Exercise, PdeParams};
+use crate::quasi_random::{BrownianBridge, Sobol, SOBOL_DIMENSIONS};
+use bytemuck::cast;
+use wide::*;
+
+/// Finite difference grid pricing the call control variate under local volatility
+const CONTROL_GRID: PdeParams = PdeParams {
+    space_steps: 400,
+    time_steps: 200,
+    rannacher_steps: 2,
+    concentration: 0.2,
+    early_exercise: EarlyExercise::BrennanSchwartz,
+};
+
+/// Seeded pseudo random number generator (xoshiro256**)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    /// The state is expanded from the seed with splitmix64 so any seed, including 0, is fine
+    pub fn new(seed: u64) -> Rng {
+        let mut x = seed;
+        let mut s = [0u64; 4];
+        for v in s.iter_mut() {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *v = z ^ (z >> 31);
+        }
+        Rng { s }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform in the open interval (0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    /// Eight uniforms in the open interval (0, 1)
+    pub fn uniform_f32x8(&mut self) -> f32x8 {
+        let mut u = [0.0f32; 8];
+        for pair in u.chunks_mut(2) {
+            let x = self.next_u64();
+            pair[0] = ((x >> 40) as f32 + 0.5) / (1u32 << 24) as f32;
+            pair[1] = (((x >> 8) & 0xff_ffff) as f32 + 0.5) / (1u32 << 24) as f32;
+        }
+        f32x8::from(u)
+    }
+
+    /// Sixteen standard normals by the Box-Muller transform
+    pub fn normal_f32x8(&mut self) -> (f32x8, f32x8) {
+        let u1 = self.uniform_f32x8();
+        let u2 = self.uniform_f32x8();
+        let r = (f32x8::splat(-2.0) * u1.ln()).sqrt();
+        let (s, c) = (f32x8::TAU * u2).sin_cos();
+        (r * c, r * s)
+    }
+}
+
+/// Payoff of a (possibly path dependent) derivative
+pub trait Payoff {
+    /// Undiscounted payoff of a single path. `path` holds the spot at each time step, the last value being
+    /// the spot at expiry
+    fn payoff(&self, path: &[f32]) -> f32;
+}
+
+/// European option, only the spot at expiry is used
+#[derive(Debug, Copy, Clone)]
+pub struct EuropeanPayoff {
+    pub option_dir: OptionDir,
+    pub strike: f32,
+}
+
+impl Payoff for EuropeanPayoff {
+    fn payoff(&self, path: &[f32]) -> f32 {
+        let spot = path[path.len() - 1];
+        match self.option_dir {
+            OptionDir::CALL => (spot - self.strike).max(0.0),
+            OptionDir::PUT => (self.strike - spot).max(0.0),
+        }
+    }
+}
+
+/// Arithmetic average rate option, averaging over every time step of the path
+#[derive(Debug, Copy, Clone)]
+pub struct AsianPayoff {
+    pub option_dir: OptionDir,
+    pub strike: f32,
+}
+
+impl Payoff for AsianPayoff {
+    fn payoff(&self, path: &[f32]) -> f32 {
+        let average = path.iter().sum::<f32>() / path.len() as f32;
+        match self.option_dir {
+            OptionDir::CALL => (average - self.strike).max(0.0),
+            OptionDir::PUT => (self.strike - average).max(0.0),
+        }
+    }
+}
+
+/// Dynamics of the underlying
+#[derive(Debug, Clone)]
+pub enum Model {
+    /// Geometric Brownian motion with a constant volatility
+    BlackScholes { volatility: f32 },
+    /// Heston stochastic volatility, simulated with a full truncation Euler scheme
+    Heston(HestonParams),
+    /// Dupire local volatility, simulated with a log Euler scheme reading the vol at the start of each step
+    LocalVol(LocalVolSurface),
+}
+
+/// Source of the normals driving the paths
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Sampler {
+    /// xoshiro256** uniforms with Box-Muller normals
+    PseudoRandom,
+    /// Sobol points through the inverse normal, randomised by linear matrix scrambling and a digital shift from the
+    /// seed when scrambled.
+    /// Dimensions beyond `SOBOL_DIMENSIONS` are filled with pseudo random numbers
+    Sobol { scrambled: bool },
+}
+
+/// Simulation settings
+#[derive(Debug, Copy, Clone)]
+pub struct MonteCarloParams {
+    /// Number of paths, rounded up to a multiple of 8 (16 with antithetic sampling)
+    pub paths: usize,
+    /// Number of equally spaced time steps to expiry
+    pub steps: usize,
+    pub seed: u64,
+    /// Pair every path with its mirror image
+    pub antithetic: bool,
+    pub sampler: Sampler,
+    /// Build the Brownian paths from the terminal value down rather than step by step.
+    /// Combined with Sobol sampling this puts the most important part of the path on the best dimensions
+    pub brownian_bridge: bool,
+}
+
+/// Monte Carlo estimate with its 95% confidence interval
+#[derive(Debug, Copy, Clone)]
+pub struct MonteCarloResult {
+    pub price: f32,
+    pub std_error: f32,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+impl MonteCarloResult {
+    fn new(mean: f64, std_error: f64) -> MonteCarloResult {
+        MonteCarloResult {
+            price: mean as f32,
+            std_error: std_error as f32,
+            lower: (mean - 1.96 * std_error) as f32,
+            upper: (mean + 1.96 * std_error) as f32,
+        }
+    }
+
+    /// Estimate from the discounted sample of each path in the order of `generate_paths`. Antithetic paths are
+    /// averaged with their mirrors so that each pair counts as one independent sample
+    pub(crate) fn from_samples(samples: &[f64], antithetic: bool) -> MonteCarloResult {
+        let mut moments = Moments::default();
+        if antithetic {
+            for batch in samples.chunks(16) {
+                let (paths, mirrors) = batch.split_at(batch.len() / 2);
+                for (y, m) in paths.iter().zip(mirrors) {
+                    moments.add((y + m) / 2.0, 0.0);
+                }
+            }
+        } else {
+            for y in samples {
+                moments.add(*y, 0.0);
+            }
+        }
+        let (mean, variance) = moments.estimate(None);
+        MonteCarloResult::new(mean, (variance / moments.n).sqrt())
+    }
+}
+
+/// Generates paths eight at a time
+struct PathGenerator {
+    model: Model,
+    spot: f32,
+    dt: f32,
+    drift: f32,
+}
+
+impl PathGenerator {
+    fn new(
+        model: Model,
+        spot: f32,
+        years_to_expiry: f32,
+        risk_free_rate: f32,
+        dividend_yield: f32,
+        steps: usize,
+    ) -> PathGenerator {
+        PathGenerator {
+            model,
+            spot,
+            dt: years_to_expiry / steps as f32,
+            drift: risk_free_rate - dividend_yield,
+        }
+    }
+
+    /// Number of normals needed per time step
+    fn factors(&self) -> usize {
+        match self.model {
+            Model::BlackScholes { .. } | Model::LocalVol(_) => 1,
+            Model::Heston(_) => 2,
+        }
+    }
+
+    /// Fill `path` with the spot at each step from the normals, laid out step by step then factor by factor.
+    /// The variance at each step is written to `variance` for stochastic vol models unless it is empty
+    fn generate(&self, z: &[f32x8], path: &mut [f32x8], variance: &mut [f32x8]) {
+        let dt = f32x8::splat(self.dt);
+        let sqrt_dt = dt.sqrt();
+        let mut log_spot = f32x8::splat(self.spot.ln());
+        match &self.model {
+            &Model::BlackScholes { volatility } => {
+                let mu = f32x8::splat((self.drift - volatility * volatility / 2.0) * self.dt);
+                let sigma = f32x8::splat(volatility) * sqrt_dt;
+                for (s, z) in path.iter_mut().zip(z.iter()) {
+                    log_spot = z.mul_add(sigma, log_spot + mu);
+                    *s = log_spot.exp();
+                }
+            }
+            Model::Heston(p) => {
+                let mut v = f32x8::splat(p.v0);
+                let rho_c = (1.0 - p.rho * p.rho).sqrt();
+                for (i, s) in path.iter_mut().enumerate() {
+                    let z1 = z[2 * i];
+                    let z2 = z[2 * i + 1] * rho_c + z1 * p.rho;
+                    let vp = v.max(f32x8::ZERO);
+                    let sd = (vp * dt).sqrt();
+                    log_spot = log_spot + (f32x8::splat(self.drift) - vp * 0.5) * dt + sd * z1;
+                    v = v + (f32x8::splat(p.theta) - vp) * p.kappa * dt + sd * z2 * p.sigma;
+                    *s = log_spot.exp();
+                    if let Some(out) = variance.get_mut(i) {
+                        *out = v.max(f32x8::ZERO);
+                    }
+                }
+            }
+            Model::LocalVol(surface) => {
+                for (i, (s, z)) in path.iter_mut().zip(z.iter()).enumerate() {
+                    let spots: [f32; 8] = cast(log_spot.exp());
+                    let vol = f32x8::from(spots.map(|s| surface.vol(s, i as f32 * self.dt)));
+                    log_spot = log_spot
+                        + (f32x8::splat(self.drift) - vol * vol * 0.5) * dt
+                        + vol * sqrt_dt * z;
+                    *s = log_spot.exp();
+                }
+            }
+        }
+    }
+}
+
+/// Draws the normals for eight paths at a time
+struct Normals {
+    rng: Rng,
+    sobol: Option<Sobol>,
+    bridge: Option<BrownianBridge>,
+    steps: usize,
+    factors: usize,
+    point: Vec<f64>,
+    /// Normals in order of importance, the bridge construction order then factor
+    lanes: Vec<[f32; 8]>,
+}
+
+impl Normals {
+    fn new(params: &MonteCarloParams, steps: usize, factors: usize) -> Normals {
+        let dimensions = steps * factors;
+        let sobol = match params.sampler {
+            Sampler::PseudoRandom => None,
+            Sampler::Sobol { scrambled } => Some(Sobol::new(
+                dimensions.min(SOBOL_DIMENSIONS),
+                if scrambled { Some(params.seed) } else { None },
+            )),
+        };
+        Normals {
+            rng: Rng::new(params.seed),
+            sobol,
+            bridge: if params.brownian_bridge {
+                Some(BrownianBridge::new(steps))
+            } else {
+                None
+            },
+            steps,
+            factors,
+            point: vec![0.0; dimensions],
+            lanes: vec![[0.0; 8]; dimensions],
+        }
+    }
+
+    /// Fill `z` with standard normal increments laid out step by step then factor by factor
+    fn fill(&mut self, z: &mut [f32x8]) {
+        match self.sobol.as_mut() {
+            None => {
+                for pair in self.lanes.chunks_mut(2) {
+                    let (a, b) = self.rng.normal_f32x8();
+                    pair[0] = cast(a);
+                    if pair.len() > 1 {
+                        pair[1] = cast(b);
+                    }
+                }
+            }
+            Some(sobol) => {
+                let low = self.point.len().min(SOBOL_DIMENSIONS);
+                for j in 0..8 {
+                    sobol.next_point(&mut self.point[..low]);
+                    for p in self.point[low..].iter_mut() {
+                        *p = self.rng.uniform();
+                    }
+                    for (lane, p) in self.lanes.iter_mut().zip(self.point.iter()) {
+                        lane[j] = inverse_ncd(*p) as f32;
+                    }
+                }
+            }
+        }
+        match &self.bridge {
+            None => {
+                for (z, lane) in z.iter_mut().zip(self.lanes.iter()) {
+                    *z = f32x8::from(*lane);
+                }
+            }
+            Some(bridge) => {
+                let mut ordered = vec![f32x8::ZERO; self.steps];
+                let mut increments = vec![f32x8::ZERO; self.steps];
+                for f in 0..self.factors {
+                    for (i, o) in ordered.iter_mut().enumerate() {
+                        *o = f32x8::from(self.lanes[i * self.factors + f]);
+                    }
+                    bridge.increments(&ordered, &mut increments);
+                    for (i, inc) in increments.iter().enumerate() {
+                        z[i * self.factors + f] = *inc;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Simulated paths stored path after path
+pub(crate) struct Paths {
+    pub steps: usize,
+    /// Spot at each time step
+    pub spot: Vec<f32>,
+    /// Variance at each time step under stochastic vol models, otherwise empty
+    pub variance: Vec<f32>,
+}
+
+impl Paths {
+    pub fn len(&self) -> usize {
+        self.spot.len() / self.steps
+    }
+}
+
+/// Simulate and keep all the paths, for engines such as least squares Monte Carlo that need to
+/// go backwards through them. Antithetic paths are stored as separate paths, each batch of eight followed by its
+/// eight mirrors
+pub(crate) fn generate_paths(
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &MonteCarloParams,
+) -> Paths {
+    let steps = params.steps.max(1);
+    let generator = PathGenerator::new(
+        model.clone(),
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        steps,
+    );
+    let stochastic_vol = generator.factors() > 1;
+    let mut normals = Normals::new(params, steps, generator.factors());
+    let mut z = vec![f32x8::ZERO; steps * generator.factors()];
+    let mut path = vec![f32x8::ZERO; steps];
+    let mut variance = vec![f32x8::ZERO; if stochastic_vol { steps } else { 0 }];
+    let per_batch = if params.antithetic { 16 } else { 8 };
+    let batches = params.paths.div_ceil(per_batch);
+    let mut paths = Paths {
+        steps,
+        spot: Vec::with_capacity(batches * per_batch * steps),
+        variance: Vec::with_capacity(if stochastic_vol {
+            batches * per_batch * steps
+        } else {
+            0
+        }),
+    };
+    let store = |path: &[f32x8], variance: &[f32x8], paths: &mut Paths| {
+        for j in 0..8 {
+            paths
+                .spot
+                .extend(path.iter().map(|s| cast::<f32x8, [f32; 8]>(*s)[j]));
+            paths
+                .variance
+                .extend(variance.iter().map(|v| cast::<f32x8, [f32; 8]>(*v)[j]));
+        }
+    };
+    for _ in 0..batches {
+        normals.fill(&mut z);
+        generator.generate(&z, &mut path, &mut variance);
+        store(&path, &variance, &mut paths);
+        if params.antithetic {
+            for v in z.iter_mut() {
+                *v = -*v;
+            }
+            generator.generate(&z, &mut path, &mut variance);
+            store(&path, &variance, &mut paths);
+        }
+    }
+    paths
+}
+
+/// Running sums of the samples and the control
+#[derive(Default)]
+struct Moments {
+    n: f64,
+    y: f64,
+    yy: f64,
+    x: f64,
+    xx: f64,
+    xy: f64,
+}
+
+impl Moments {
+    fn add(&mut self, y: f64, x: f64) {
+        self.n += 1.0;
+        self.y += y;
+        self.yy += y * y;
+        self.x += x;
+        self.xx += x * x;
+        self.xy += x * y;
+    }
+
+    /// Mean and variance of the sample, adjusted with the control when its expectation is given
+    fn estimate(&self, control_mean: Option<f64>) -> (f64, f64) {
+        let n = self.n;
+        let syy = self.yy - self.y * self.y / n;
+        let mean = self.y / n;
+        match control_mean {
+            Some(expected) => {
+                let sxx = self.xx - self.x * self.x / n;
+                let sxy = self.xy - self.x * self.y / n;
+                let beta = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+                let mean = mean - beta * (self.x / n - expected);
+                let variance = (syy - beta * sxy).max(0.0) / (n - 1.0);
+                (mean, variance)
+            }
+            None => (mean, syy / (n - 1.0)),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn simulate(
+    payoff: &dyn Payoff,
+    control: Option<(&dyn Payoff, f32)>,
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &MonteCarloParams,
+) -> MonteCarloResult {
+    let steps = params.steps.max(1);
+    let generator = PathGenerator::new(
+        model.clone(),
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        steps,
+    );
+    let mut normals = Normals::new(params, steps, generator.factors());
+    let mut z = vec![f32x8::ZERO; steps * generator.factors()];
+    let mut path = vec![f32x8::ZERO; steps];
+    let mut single = vec![0.0f32; steps];
+    let mut moments = Moments::default();
+    let discount = (-risk_free_rate * years_to_expiry).exp() as f64;
+    let per_batch = if params.antithetic { 16 } else { 8 };
+
+    for _ in 0..params.paths.div_ceil(per_batch) {
+        normals.fill(&mut z);
+        // Payoff and control of each lane
+        let mut evaluate = |path: &[f32x8]| {
+            let mut values = [0.0f32; 8];
+            let mut controls = [0.0f32; 8];
+            for j in 0..8 {
+                for (s, p) in single.iter_mut().zip(path.iter()) {
+                    *s = cast::<f32x8, [f32; 8]>(*p)[j];
+                }
+                values[j] = payoff.payoff(&single);
+                controls[j] = control.map_or(0.0, |(c, _)| c.payoff(&single));
+            }
+            (f32x8::from(values), f32x8::from(controls))
+        };
+        generator.generate(&z, &mut path, &mut []);
+        let (mut values, mut controls) = evaluate(&path);
+        if params.antithetic {
+            for v in z.iter_mut() {
+                *v = -*v;
+            }
+            generator.generate(&z, &mut path, &mut []);
+            let (mirror_values, mirror_controls) = evaluate(&path);
+            values = (values + mirror_values) * 0.5;
+            controls = (controls + mirror_controls) * 0.5;
+        }
+        let values: [f32; 8] = cast(values);
+        let controls: [f32; 8] = cast(controls);
+        for (y, x) in values.iter().zip(controls.iter()) {
+            moments.add(discount * *y as f64, discount * *x as f64);
+        }
+    }
+
+    let (mean, variance) = moments.estimate(control.map(|(_, price)| price as f64));
+    MonteCarloResult::new(mean, (variance / moments.n).sqrt())
+}
+
+/// Monte Carlo price of a payoff
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+pub fn monte_carlo(
+    payoff: &dyn Payoff,
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &MonteCarloParams,
+) -> MonteCarloResult {
+    simulate(
+        payoff,
+        None,
+        model,
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        params,
+    )
+}
+
+/// Monte Carlo price of a payoff using a control variate with a known price.
+/// The control payoff is evaluated on the same paths and the regression coefficient is estimated from the sample
+#[allow(clippy::too_many_arguments)]
+pub fn monte_carlo_control_variate(
+    payoff: &dyn Payoff,
+    control: &dyn Payoff,
+    control_price: f32,
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &MonteCarloParams,
+) -> MonteCarloResult {
+    simulate(
+        payoff,
+        Some((control, control_price)),
+        model,
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        params,
+    )
+}
+
+/// Monte Carlo price of a payoff using a European call as the control variate.
+/// The call is priced in closed form with `bs_call`, or `heston_price` under the Heston model, and on the finite
+/// difference grid under local volatility
+#[allow(clippy::too_many_arguments)]
+pub fn monte_carlo_call_control(
+    payoff: &dyn Payoff,
+    control_strike: f32,
+    model: &Model,
+    spot: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    params: &MonteCarloParams,
+) -> MonteCarloResult {
+    let control_price = match model {
+        Model::BlackScholes { volatility } => bs_call(
+            &[spot],
+            &[control_strike],
+            &[years_to_expiry],
+            &[risk_free_rate],
+            &[*volatility],
+            &[dividend_yield],
+        )[0],
+        Model::Heston(p) => heston_price(
+            OptionDir::CALL,
+            spot,
+            control_strike,
+            years_to_expiry,
+            risk_free_rate,
+            dividend_yield,
+            p,
+        ),
+        Model::LocalVol(surface) => {
+            local_vol_pde_price(
+                OptionDir::CALL,
+                Exercise::European,
+                None,
+                spot,
+                control_strike,
+                years_to_expiry,
+                risk_free_rate,
+                dividend_yield,
+                surface,
+                &CONTROL_GRID,
+            )
+            .pv
+        }
+    };
+    let control = EuropeanPayoff {
+        option_dir: OptionDir::CALL,
+        strike: control_strike,
+    };
+    monte_carlo_control_variate(
+        payoff,
+        &control,
+        control_price,
+        model,
+        spot,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        params,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+
+    const PARAMS: MonteCarloParams = MonteCarloParams {
+        paths: 100_000,
+        steps: 1,
+        seed: 42,
+        antithetic: false,
+        sampler: Sampler::PseudoRandom,
+        brownian_bridge: false,
+    };
+
+    #[test]
+    fn normals() {
+        let mut rng = Rng::new(7);
+        let (mut sum, mut sum_sq) = (0.0f64, 0.0f64);
+        for _ in 0..10_000 {
+            let (a, b) = rng.normal_f32x8();
+            for x in cast::<f32x8, [f32; 8]>(a)
+                .iter()
+                .chain(cast::<f32x8, [f32; 8]>(b).iter())
+            {
+                sum += *x as f64;
+                sum_sq += (*x * *x) as f64;
+            }
+        }
+        let n = 160_000.0;
+        assert!((sum / n).abs() < 0.01);
+        assert!((sum_sq / n - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn european_matches_black_scholes() {
+        let model = Model::BlackScholes { volatility: 0.2 };
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            let payoff = EuropeanPayoff {
+                option_dir: dir,
+                strike: 105.0,
+            };
+            let res = monte_carlo(&payoff, &model, 100.0, 0.5, 0.03, 0.01, &PARAMS);
+            let expected = bs_price(dir, 100.0, 105.0, 0.5, 0.03, 0.2, 0.01);
+            assert!((res.price - expected).abs() < 3.0 * res.std_error);
+            assert!(res.lower < expected && expected < res.upper);
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let model = Model::BlackScholes { volatility: 0.3 };
+        let payoff = AsianPayoff {
+            option_dir: OptionDir::CALL,
+            strike: 100.0,
+        };
+        let params = MonteCarloParams {
+            paths: 1000,
+            steps: 12,
+            ..PARAMS
+        };
+        let a = monte_carlo(&payoff, &model, 100.0, 1.0, 0.03, 0.0, &params);
+        let b = monte_carlo(&payoff, &model, 100.0, 1.0, 0.03, 0.0, &params);
+        assert_eq!(a.price, b.price);
+        let c = monte_carlo(
+            &payoff,
+            &model,
+            100.0,
+            1.0,
+            0.03,
+            0.0,
+            &MonteCarloParams { seed: 1, ..params },
+        );
+        assert!(a.price != c.price);
+    }
+
+    #[test]
+    fn variance_reduction() {
+        let model = Model::BlackScholes { volatility: 0.25 };
+        let payoff = AsianPayoff {
+            option_dir: OptionDir::CALL,
+            strike: 100.0,
+        };
+        let params = MonteCarloParams {
+            paths: 20_000,
+            steps: 12,
+            ..PARAMS
+        };
+        let plain = monte_carlo(&payoff, &model, 100.0, 1.0, 0.03, 0.0, &params);
+        let antithetic = monte_carlo(
+            &payoff,
+            &model,
+            100.0,
+            1.0,
+            0.03,
+            0.0,
+            &MonteCarloParams {
+                antithetic: true,
+                ..params
+            },
+        );
+        let control =
+            monte_carlo_call_control(&payoff, 100.0, &model, 100.0, 1.0, 0.03, 0.0, &params);
+        assert!(antithetic.std_error < plain.std_error);
+        assert!(control.std_error < 0.75 * plain.std_error);
+        assert!((control.price - plain.price).abs() < 3.0 * plain.std_error);
+    }
+
+    #[test]
+    fn antithetic_samples_are_paired() {
+        // Each batch of eight paths is followed by its mirrors, which here cancel them exactly
+        let samples: Vec<f64> = (0..64)
+            .map(|i| {
+                if i % 16 < 8 {
+                    i as f64
+                } else {
+                    -(i as f64 - 8.0)
+                }
+            })
+            .collect();
+        let paired = MonteCarloResult::from_samples(&samples, true);
+        assert_eq!(paired.price, 0.0);
+        assert_eq!(paired.std_error, 0.0);
+        let unpaired = MonteCarloResult::from_samples(&samples, false);
+        assert!(unpaired.std_error > 0.0);
+    }
+
+    #[test]
+    fn sobol_converges_faster() {
+        let model = Model::BlackScholes { volatility: 0.2 };
+        let payoff = EuropeanPayoff {
+            option_dir: OptionDir::CALL,
+            strike: 100.0,
+        };
+        let expected = bs_call(&[100.0], &[100.0], &[1.0], &[0.03], &[0.2], &[0.0])[0];
+        let params = MonteCarloParams {
+            paths: 4096,
+            steps: 16,
+            sampler: Sampler::Sobol { scrambled: false },
+            brownian_bridge: true,
+            ..PARAMS
+        };
+        let sobol = monte_carlo(&payoff, &model, 100.0, 1.0, 0.03, 0.0, &params);
+        assert!((sobol.price - expected).abs() < 0.02);
+        let scrambled = monte_carlo(
+            &payoff,
+            &model,
+            100.0,
+            1.0,
+            0.03,
+            0.0,
+            &MonteCarloParams {
+                sampler: Sampler::Sobol { scrambled: true },
+                ..params
+            },
+        );
+        assert!((scrambled.price - expected).abs() < 0.05);
+        let pseudo = monte_carlo(
+            &payoff,
+            &model,
+            100.0,
+            1.0,
+            0.03,
+            0.0,
+            &MonteCarloParams {
+                sampler: Sampler::PseudoRandom,
+                ..params
+            },
+        );
+        assert!((sobol.price - expected).abs() < (pseudo.price - expected).abs());
+    }
+
+    #[test]
+    fn sobol_beyond_table_dimensions() {
+        // Heston with 40 steps needs 80 dimensions, past the Joe-Kuo table
+        let params = HestonParams {
+            v0: 0.04,
+            kappa: 2.0,
+            theta: 0.04,
+            sigma: 0.3,
+            rho: -0.6,
+        };
+        let payoff = EuropeanPayoff {
+            option_dir: OptionDir::CALL,
+            strike: 100.0,
+        };
+        let mc = MonteCarloParams {
+            paths: 8192,
+            steps: 40,
+            sampler: Sampler::Sobol { scrambled: true },
+            brownian_bridge: true,
+            ..PARAMS
+        };
+        let res = monte_carlo(&payoff, &Model::Heston(params), 100.0, 0.5, 0.02, 0.0, &mc);
+        let expected = heston_price(OptionDir::CALL, 100.0, 100.0, 0.5, 0.02, 0.0, &params);
+        assert!((res.price - expected).abs() < 0.1);
+    }
+
+    #[test]
+    fn heston_paths() {
+        let params = HestonParams {
+            v0: 0.04,
+            kappa: 2.0,
+            theta: 0.04,
+            sigma: 0.3,
+            rho: -0.6,
+        };
+        let model = Model::Heston(params);
+        let payoff = EuropeanPayoff {
+            option_dir: OptionDir::PUT,
+            strike: 95.0,
+        };
+        let mc = MonteCarloParams {
+            paths: 40_000,
+            steps: 50,
+            antithetic: true,
+            ..PARAMS
+        };
+        let res = monte_carlo(&payoff, &model, 100.0, 0.5, 0.02, 0.0, &mc);
+        let expected = heston_price(OptionDir::PUT, 100.0, 95.0, 0.5, 0.02, 0.0, &params);
+        // The Euler scheme has a small discretisation bias on top of the sampling error
+        assert!((res.price - expected).abs() < 3.0 * res.std_error + 0.02);
+    }
+}
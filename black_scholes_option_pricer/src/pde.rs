@@ -0,0 +1,875 @@
+//! Crank-Nicolson finite difference solver of the one dimensional Black Scholes PDE
+//!
+//! * The spot grid is non-uniform, concentrated around the strike by a sinh stretching, and the price and greeks at the
+//!   spot are interpolated from the grid
+//! * Rannacher smoothing replaces the first Crank-Nicolson steps by implicit Euler half steps, which damps the
+//!   oscillations the kink in the payoff otherwise causes in delta and gamma
+//! * Early exercise is handled by projected SOR or the Brennan-Schwartz direct solver
+//! * Barriers are continuously monitored. Knock-out rebates are paid when the barrier is hit, knock-in rebates at
+//!   expiry if the barrier was never hit
+//! * A knock-in is priced in two passes: the vanilla is solved first, then the option before the barrier is hit on
+//!   the grid up to the barrier, with the vanilla value on the barrier. This also holds for American knock-ins,
+//!   which can only be exercised once knocked in
+use crate::bs::OptionDir;
+
+/// Exercise style of the option
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Exercise {
+    European,
+    American,
+}
+
+/// Solver of the complementarity problem an American option gives at each time step
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum EarlyExercise {
+    /// Projected successive over-relaxation, iterates to a tolerance
+    Psor,
+    /// Brennan-Schwartz, a single tridiagonal solve with the projection applied during the substitution.
+    /// Exact for payoffs with a single exercise boundary such as vanilla calls and puts
+    BrennanSchwartz,
+}
+
+/// Side and kind of a barrier
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BarrierType {
+    UpAndOut,
+    DownAndOut,
+    UpAndIn,
+    DownAndIn,
+}
+
+/// Continuously monitored barrier
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Barrier {
+    pub barrier_type: BarrierType,
+    pub level: f32,
+    /// Paid when a knock-out is hit, or at expiry when a knock-in was never hit
+    pub rebate: f32,
+}
+
+impl Barrier {
+    pub(crate) fn knock_in(&self) -> bool {
+        matches!(
+            self.barrier_type,
+            BarrierType::UpAndIn | BarrierType::DownAndIn
+        )
+    }
+
+    pub(crate) fn up(&self) -> bool {
+        matches!(
+            self.barrier_type,
+            BarrierType::UpAndOut | BarrierType::UpAndIn
+        )
+    }
+
+    /// Whether the spot is on or beyond the barrier
+    pub(crate) fn hit(&self, spot: f64) -> bool {
+        if self.up() {
+            spot >= self.level as f64
+        } else {
+            spot <= self.level as f64
+        }
+    }
+}
+
+/// Grid settings
+#[derive(Debug, Copy, Clone)]
+pub struct PdeParams {
+    /// Number of spot intervals
+    pub space_steps: usize,
+    /// Number of time intervals
+    pub time_steps: usize,
+    /// Number of the first time steps replaced by two implicit Euler half steps, 2 is the usual choice
+    pub rannacher_steps: usize,
+    /// Width of the region around the strike the grid is concentrated on, as a fraction of the strike.
+    /// Smaller is more concentrated, large values give a nearly uniform grid
+    pub concentration: f32,
+    pub early_exercise: EarlyExercise,
+}
+
+/// Price and greeks read off the grid, theta is per year
+#[derive(Debug, Copy, Clone)]
+pub struct PdeResult {
+    pub pv: f32,
+    pub delta: f32,
+    pub gamma: f32,
+    pub theta: f32,
+}
+
+/// Number of standard deviations of the log spot the grid covers beyond the spot and strike
+const GRID_WIDTH: f64 = 5.0;
+const PSOR_OMEGA: f64 = 1.2;
+const PSOR_TOLERANCE: f64 = 1.0e-10;
+const PSOR_MAX_ITER: usize = 1000;
+
+/// Spot grid on [lower, upper] with the nodes concentrated around the strike
+fn grid(lower: f64, upper: f64, strike: f64, concentration: f64, nodes: usize) -> Vec<f64> {
+    let c = (concentration * strike).max(1.0e-6 * strike);
+    let (xi_lower, xi_upper) = (
+        ((lower - strike) / c).asinh(),
+        ((upper - strike) / c).asinh(),
+    );
+    let mut s: Vec<f64> = (0..nodes)
+        .map(|i| {
+            let xi = xi_lower + (xi_upper - xi_lower) * i as f64 / (nodes - 1) as f64;
+            strike + c * xi.sinh()
+        })
+        .collect();
+    s[0] = lower;
+    s[nodes - 1] = upper;
+    s
+}
+
+/// Thomas algorithm on the tridiagonal system a x[i-1] + b x[i] + c x[i+1] = d.
+/// With a floor each value is projected onto it during the back substitution, which is the Brennan-Schwartz
+/// algorithm when the substitution starts in the exercise region
+fn solve_tridiagonal(
+    a: &[f64],
+    b: &[f64],
+    c: &[f64],
+    d: &[f64],
+    floor: Option<&[f64]>,
+    x: &mut [f64],
+) {
+    let n = d.len();
+    let mut cp = vec![0.0; n];
+    let mut dp = vec![0.0; n];
+    cp[0] = c[0] / b[0];
+    dp[0] = d[0] / b[0];
+    for i in 1..n {
+        let m = b[i] - a[i] * cp[i - 1];
+        cp[i] = c[i] / m;
+        dp[i] = (d[i] - a[i] * dp[i - 1]) / m;
+    }
+    let project = |i: usize, v: f64| floor.map_or(v, |f| v.max(f[i]));
+    x[n - 1] = project(n - 1, dp[n - 1]);
+    for i in (0..n - 1).rev() {
+        x[i] = project(i, dp[i] - cp[i] * x[i + 1]);
+    }
+}
+
+/// Brennan-Schwartz for a put, whose exercise region is at the bottom of the grid, solves the reversed system
+fn solve_reversed(a: &[f64], b: &[f64], c: &[f64], d: &[f64], floor: &[f64], x: &mut [f64]) {
+    let rev = |v: &[f64]| v.iter().rev().copied().collect::<Vec<f64>>();
+    let mut y = vec![0.0; x.len()];
+    solve_tridiagonal(
+        &rev(c),
+        &rev(b),
+        &rev(a),
+        &rev(d),
+        Some(&rev(floor)),
+        &mut y,
+    );
+    for (x, y) in x.iter_mut().zip(y.iter().rev()) {
+        *x = *y;
+    }
+}
+
+/// Projected SOR, x holds the initial guess
+fn psor(a: &[f64], b: &[f64], c: &[f64], d: &[f64], floor: &[f64], x: &mut [f64]) {
+    let n = d.len();
+    for _ in 0..PSOR_MAX_ITER {
+        let mut change: f64 = 0.0;
+        for i in 0..n {
+            let below = if i > 0 { a[i] * x[i - 1] } else { 0.0 };
+            let above = if i + 1 < n { c[i] * x[i + 1] } else { 0.0 };
+            let gauss_seidel = (d[i] - below - above) / b[i];
+            let v = (x[i] + PSOR_OMEGA * (gauss_seidel - x[i])).max(floor[i]);
+            change = change.max((v - x[i]).abs());
+            x[i] = v;
+        }
+        if change < PSOR_TOLERANCE {
+            break;
+        }
+    }
+}
+
+/// Value, first and second derivative at x of the quadratic through three nodes
+fn quadratic(s: &[f64], v: &[f64], x: f64) -> (f64, f64, f64) {
+    let (mut value, mut first, mut second) = (0.0, 0.0, 0.0);
+    for j in 0..3 {
+        let (k, l) = ((j + 1) % 3, (j + 2) % 3);
+        let w = v[j] / ((s[j] - s[k]) * (s[j] - s[l]));
+        value += w * (x - s[k]) * (x - s[l]);
+        first += w * (2.0 * x - s[k] - s[l]);
+        second += w * 2.0;
+    }
+    (value, first, second)
+}
+
+/// Value, first and second derivative at x of the quadratic through the three nodes nearest to it, kept inside the
+/// grid
+fn interpolate(s: &[f64], v: &[f64], x: f64) -> (f64, f64, f64) {
+    let nodes = s.len();
+    let j = s.partition_point(|y| *y < x).clamp(1, nodes - 2);
+    let j = if j + 1 < nodes - 1 && s[j + 1] - x < x - s[j] {
+        j + 1
+    } else {
+        j
+    };
+    quadratic(&s[j - 1..j + 2], &v[j - 1..j + 2], x)
+}
+
+/// Finite difference price and greeks of a European or American option, optionally with a barrier.
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+#[allow(clippy::too_many_arguments)]
+pub fn pde_price(
+    option_dir: OptionDir,
+    exercise: Exercise,
+    barrier: Option<Barrier>,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    params: &PdeParams,
+) -> PdeResult {
+    let sigma = volatility as f64;
+    solve(
+        option_dir,
+        exercise,
+        barrier,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        dividend_yield,
+        sigma,
+        &|_, _| sigma,
+        params,
+    )
+}
+
+/// The solver behind `pde_price` with a volatility depending on the spot and the time from now. The grid covers
+/// `grid_volatility` standard deviations
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn solve(
+    option_dir: OptionDir,
+    exercise: Exercise,
+    barrier: Option<Barrier>,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+    grid_volatility: f64,
+    volatility: &dyn Fn(f64, f64) -> f64,
+    params: &PdeParams,
+) -> PdeResult {
+    let (spot, k) = (spot as f64, strike as f64);
+    let (t, r, q) = (
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        dividend_yield as f64,
+    );
+    let hit = barrier.is_some_and(|b| b.hit(spot));
+    if hit && barrier.is_some_and(|b| b.knock_in()) {
+        return solve(
+            option_dir,
+            exercise,
+            None,
+            spot as f32,
+            strike,
+            years_to_expiry,
+            risk_free_rate,
+            dividend_yield,
+            grid_volatility,
+            volatility,
+            params,
+        );
+    }
+    if hit {
+        let rebate = barrier.map_or(0.0, |b| b.rebate);
+        return PdeResult {
+            pv: rebate,
+            delta: 0.0,
+            gamma: 0.0,
+            theta: 0.0,
+        };
+    }
+    if t <= 0.0 {
+        let (pv, delta) = match (barrier, option_dir) {
+            (Some(b), _) if b.knock_in() => (b.rebate as f64, 0.0),
+            (_, OptionDir::CALL) if spot > k => (spot - k, 1.0),
+            (_, OptionDir::PUT) if spot < k => (k - spot, -1.0),
+            _ => (0.0, 0.0),
+        };
+        return PdeResult {
+            pv: pv as f32,
+            delta: delta as f32,
+            gamma: 0.0,
+            theta: 0.0,
+        };
+    }
+    let american = exercise == Exercise::American;
+    let rebate = barrier.map_or(0.0, |b| b.rebate as f64);
+    let knock_in = barrier.filter(|b| b.knock_in());
+    let width = (GRID_WIDTH * grid_volatility * t.sqrt()).max(0.5).exp();
+    let nodes = params.space_steps.max(4) + 1;
+    let payoff = |s: f64| match option_dir {
+        OptionDir::CALL => (s - k).max(0.0),
+        OptionDir::PUT => (k - s).max(0.0),
+    };
+    // Dirichlet values of the vanilla at the ends of the grid after tau years
+    let vanilla_boundary = |upper: f64, tau: f64| -> (f64, f64) {
+        match option_dir {
+            OptionDir::CALL => {
+                let forward = upper * (-q * tau).exp() - k * (-r * tau).exp();
+                (
+                    0.0,
+                    if american {
+                        forward.max(upper - k)
+                    } else {
+                        forward
+                    },
+                )
+            }
+            OptionDir::PUT if american => (k, 0.0),
+            OptionDir::PUT => (k * (-r * tau).exp(), 0.0),
+        }
+    };
+    let steps = params.time_steps.max(1);
+    let dt = t / steps as f64;
+
+    let (s, v, layers) = match knock_in {
+        Some(b) => {
+            // The vanilla on a grid wide enough to cover the barrier, read off on the barrier after each step
+            let h = b.level as f64;
+            let upper = spot.max(k).max(h) * width;
+            let s = grid(0.0, upper, k, params.concentration as f64, nodes);
+            let floor: Vec<f64> = s.iter().map(|s| payoff(*s)).collect();
+            let mut v = floor.clone();
+            let (low, high) = vanilla_boundary(upper, 0.0);
+            v[0] = low;
+            v[nodes - 1] = high;
+            let mut on_barrier = vec![interpolate(&s, &v, h).0];
+            march(
+                option_dir,
+                &s,
+                &mut v,
+                t,
+                r,
+                q,
+                volatility,
+                &|tau| vanilla_boundary(upper, tau),
+                if american { Some(&floor) } else { None },
+                params,
+                &mut |v| on_barrier.push(interpolate(&s, v, h).0),
+            );
+            // Linear in time between the steps, for the Rannacher half steps
+            let vanilla = |tau: f64| {
+                let n = ((tau / dt) as usize).min(steps - 1);
+                let w = tau / dt - n as f64;
+                on_barrier[n] + w * (on_barrier[n + 1] - on_barrier[n])
+            };
+            // Before the barrier is hit the rebate is all the option pays, it cannot be exercised early
+            let (lower, upper) = if b.up() {
+                (0.0, h)
+            } else {
+                (h, spot.max(k) * width)
+            };
+            let s = grid(lower, upper, k, params.concentration as f64, nodes);
+            let mut v = vec![rebate; nodes];
+            let boundary = |tau: f64| {
+                let unhit = rebate * (-r * tau).exp();
+                if b.up() {
+                    (unhit, vanilla(tau))
+                } else {
+                    (vanilla(tau), unhit)
+                }
+            };
+            let (low, high) = boundary(0.0);
+            v[0] = low;
+            v[nodes - 1] = high;
+            let layers = march(
+                option_dir,
+                &s,
+                &mut v,
+                t,
+                r,
+                q,
+                volatility,
+                &boundary,
+                None,
+                params,
+                &mut |_| {},
+            );
+            (s, v, layers)
+        }
+        None => {
+            let mut lower = 0.0;
+            let mut upper = spot.max(k) * width;
+            let (mut lower_barrier, mut upper_barrier) = (false, false);
+            if let Some(b) = barrier {
+                if b.up() {
+                    upper = b.level as f64;
+                    upper_barrier = true;
+                } else {
+                    lower = b.level as f64;
+                    lower_barrier = true;
+                }
+            }
+            let s = grid(lower, upper, k, params.concentration as f64, nodes);
+            let floor: Vec<f64> = s.iter().map(|s| payoff(*s)).collect();
+            let mut v = floor.clone();
+            let boundary = |tau: f64| {
+                let (low, high) = vanilla_boundary(upper, tau);
+                (
+                    if lower_barrier { rebate } else { low },
+                    if upper_barrier { rebate } else { high },
+                )
+            };
+            let (low, high) = boundary(0.0);
+            v[0] = low;
+            v[nodes - 1] = high;
+            let layers = march(
+                option_dir,
+                &s,
+                &mut v,
+                t,
+                r,
+                q,
+                volatility,
+                &boundary,
+                if american { Some(&floor) } else { None },
+                params,
+                &mut |_| {},
+            );
+            (s, v, layers)
+        }
+    };
+
+    let (pv, delta, gamma) = interpolate(&s, &v, spot);
+    let theta = if steps > 1 {
+        (4.0 * interpolate(&s, &layers[1], spot).0 - interpolate(&s, &layers[0], spot).0 - 3.0 * pv)
+            / (2.0 * dt)
+    } else {
+        (interpolate(&s, &layers[1], spot).0 - pv) / dt
+    };
+    PdeResult {
+        pv: pv as f32,
+        delta: delta as f32,
+        gamma: gamma as f32,
+        theta: theta as f32,
+    }
+}
+
+/// Steps the values `v` on the grid `s` from expiry back to `t` years before it, with Dirichlet values
+/// `boundary(tau)` at the ends of the grid tau years before expiry and early exercise onto `floor`. `observe` sees
+/// the values after each time step. Returns the values one and two steps before the end, two steps first
+#[allow(clippy::too_many_arguments)]
+fn march(
+    option_dir: OptionDir,
+    s: &[f64],
+    v: &mut [f64],
+    t: f64,
+    r: f64,
+    q: f64,
+    volatility: &dyn Fn(f64, f64) -> f64,
+    boundary: &dyn Fn(f64) -> (f64, f64),
+    floor: Option<&[f64]>,
+    params: &PdeParams,
+    observe: &mut dyn FnMut(&[f64]),
+) -> [Vec<f64>; 2] {
+    let nodes = s.len();
+    // Spatial operator L V = 0.5 sigma^2 S^2 V_SS + (r - q) S V_S - r V at the interior nodes, with sigma taken
+    // tau years before expiry
+    let m = nodes - 2;
+    let operator = |i: usize, tau: f64| -> (f64, f64, f64) {
+        let (hm, hp) = (s[i + 1] - s[i], s[i + 2] - s[i + 1]);
+        let sigma = volatility(s[i + 1], t - tau);
+        let diffusion = 0.5 * sigma * sigma * s[i + 1] * s[i + 1];
+        let drift = (r - q) * s[i + 1];
+        (
+            (2.0 * diffusion - drift * hp) / (hm * (hm + hp)),
+            (drift * (hp - hm) - 2.0 * diffusion) / (hm * hp) - r,
+            (2.0 * diffusion + drift * hm) / (hp * (hm + hp)),
+        )
+    };
+
+    let floor = floor.map(|f| &f[1..nodes - 1]);
+    let (mut a, mut b, mut c, mut d) = (vec![0.0; m], vec![0.0; m], vec![0.0; m], vec![0.0; m]);
+    let mut x = vec![0.0; m];
+    // One theta scheme step from tau to tau + dt
+    let mut step = |v: &mut [f64], tau: f64, dt: f64, theta: f64| {
+        let (low, high) = boundary(tau + dt);
+        for i in 0..m {
+            let (lo, di, up) = operator(i, tau);
+            let explicit = lo * v[i] + di * v[i + 1] + up * v[i + 2];
+            d[i] = v[i + 1] + (1.0 - theta) * dt * explicit;
+            let (lo, di, up) = operator(i, tau + dt);
+            a[i] = -theta * dt * lo;
+            b[i] = 1.0 - theta * dt * di;
+            c[i] = -theta * dt * up;
+        }
+        d[0] -= a[0] * low;
+        d[m - 1] -= c[m - 1] * high;
+        match floor {
+            Some(floor) => match params.early_exercise {
+                EarlyExercise::Psor => {
+                    for (x, v) in x.iter_mut().zip(v[1..nodes - 1].iter()) {
+                        *x = *v;
+                    }
+                    psor(&a, &b, &c, &d, floor, &mut x);
+                }
+                EarlyExercise::BrennanSchwartz => match option_dir {
+                    OptionDir::CALL => solve_tridiagonal(&a, &b, &c, &d, Some(floor), &mut x),
+                    OptionDir::PUT => solve_reversed(&a, &b, &c, &d, floor, &mut x),
+                },
+            },
+            None => solve_tridiagonal(&a, &b, &c, &d, None, &mut x),
+        }
+        v[0] = low;
+        v[1..nodes - 1].copy_from_slice(&x);
+        v[nodes - 1] = high;
+    };
+
+    let steps = params.time_steps.max(1);
+    let dt = t / steps as f64;
+    // The two time layers before the last, for a second order backward difference theta
+    let mut layers = [v.to_vec(), v.to_vec()];
+    for n in 0..steps {
+        if n + 2 >= steps {
+            layers.rotate_left(1);
+            layers[1].copy_from_slice(v);
+        }
+        let tau = n as f64 * dt;
+        if n < params.rannacher_steps {
+            step(v, tau, dt / 2.0, 1.0);
+            step(v, tau + dt / 2.0, dt / 2.0, 1.0);
+        } else {
+            step(v, tau, dt, 0.5);
+        }
+        observe(v);
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::{american_binomial, bs_price, delta};
+
+    const PARAMS: PdeParams = PdeParams {
+        space_steps: 400,
+        time_steps: 200,
+        rannacher_steps: 2,
+        concentration: 0.1,
+        early_exercise: EarlyExercise::BrennanSchwartz,
+    };
+
+    #[test]
+    fn european_against_closed_form() {
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            for &strike in [80.0, 100.0, 120.0].iter() {
+                let pde = pde_price(
+                    dir,
+                    Exercise::European,
+                    None,
+                    100.0,
+                    strike,
+                    1.0,
+                    0.05,
+                    0.25,
+                    0.02,
+                    &PARAMS,
+                );
+                let bs = bs_price(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02);
+                assert!((pde.pv - bs).abs() < 0.005);
+                let d = delta(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02);
+                assert!((pde.delta - d).abs() < 0.001);
+                // Gamma against the closed form, the same for calls and puts
+                let d1 = ((100.0f32 / strike).ln() + (0.05 - 0.02 + 0.03125)) / 0.25;
+                let gamma = (-0.02f32).exp() * (-d1 * d1 / 2.0).exp()
+                    / (2.0 * std::f32::consts::PI).sqrt()
+                    / (100.0 * 0.25);
+                assert!((pde.gamma - gamma).abs() < 0.0002);
+                // Theta from the PDE itself
+                let th = 0.05 * bs - 0.03 * 100.0 * d - 0.5 * 0.0625 * 100.0 * 100.0 * gamma;
+                assert!((pde.theta - th).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn american_put_reference() {
+        let bs = pde_price(
+            OptionDir::PUT,
+            Exercise::American,
+            None,
+            36.0,
+            40.0,
+            1.0,
+            0.06,
+            0.2,
+            0.0,
+            &PARAMS,
+        );
+        let psor = pde_price(
+            OptionDir::PUT,
+            Exercise::American,
+            None,
+            36.0,
+            40.0,
+            1.0,
+            0.06,
+            0.2,
+            0.0,
+            &PdeParams {
+                early_exercise: EarlyExercise::Psor,
+                ..PARAMS
+            },
+        );
+        let tree = american_binomial(OptionDir::PUT, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0, 5000);
+        assert!((bs.pv - 4.4867).abs() < 0.002);
+        assert!((bs.pv - tree).abs() < 0.005);
+        assert!((bs.pv - psor.pv).abs() < 0.001);
+        assert!((bs.delta - psor.delta).abs() < 0.001);
+        // Early exercise is worth something and the put is above intrinsic
+        let european = bs_price(OptionDir::PUT, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0);
+        assert!(bs.pv > european + 0.2);
+        assert!(bs.delta < 0.0 && bs.delta > -1.0 && bs.gamma > 0.0);
+    }
+
+    #[test]
+    fn american_call_with_dividends() {
+        // Without dividends an American call is a European call, with them it is worth more
+        let american = |q: f32| {
+            pde_price(
+                OptionDir::CALL,
+                Exercise::American,
+                None,
+                100.0,
+                100.0,
+                1.0,
+                0.05,
+                0.3,
+                q,
+                &PARAMS,
+            )
+            .pv
+        };
+        assert!(
+            (american(0.0) - bs_price(OptionDir::CALL, 100.0, 100.0, 1.0, 0.05, 0.3, 0.0)).abs()
+                < 0.005
+        );
+        let tree = american_binomial(OptionDir::CALL, 100.0, 100.0, 1.0, 0.05, 0.3, 0.08, 5000);
+        assert!((american(0.08) - tree).abs() < 0.01);
+        assert!(
+            american(0.08) > bs_price(OptionDir::CALL, 100.0, 100.0, 1.0, 0.05, 0.3, 0.08) + 0.05
+        );
+    }
+
+    #[test]
+    fn down_and_out_call() {
+        // Barrier below the strike, by the reflection principle
+        // C_do = C(S) - (H / S)^(2 lambda - 2) C(H^2 / S), lambda = (r - q + sigma^2 / 2) / sigma^2
+        let (spot, strike, h, t, r, v, q) = (100.0f32, 100.0, 90.0, 0.5, 0.05, 0.25, 0.01);
+        let lambda = (r - q + v * v / 2.0) / (v * v);
+        let closed_form = bs_price(OptionDir::CALL, spot, strike, t, r, v, q)
+            - (h / spot).powf(2.0 * lambda - 2.0)
+                * bs_price(OptionDir::CALL, h * h / spot, strike, t, r, v, q);
+        let barrier = Barrier {
+            barrier_type: BarrierType::DownAndOut,
+            level: h,
+            rebate: 0.0,
+        };
+        let pde = pde_price(
+            OptionDir::CALL,
+            Exercise::European,
+            Some(barrier),
+            spot,
+            strike,
+            t,
+            r,
+            v,
+            q,
+            &PARAMS,
+        );
+        assert!((pde.pv - closed_form).abs() < 0.005);
+        // Knocked out already pays the rebate
+        let out = pde_price(
+            OptionDir::CALL,
+            Exercise::European,
+            Some(Barrier {
+                rebate: 1.5,
+                ..barrier
+            }),
+            85.0,
+            strike,
+            t,
+            r,
+            v,
+            q,
+            &PARAMS,
+        );
+        assert_eq!(out.pv, 1.5);
+    }
+
+    #[test]
+    fn rannacher_smooths_gamma() {
+        // A short dated at the money option with coarse time steps, plain Crank-Nicolson leaves oscillations
+        // in gamma that the implicit Euler start removes
+        let run = |rannacher_steps: usize| {
+            pde_price(
+                OptionDir::CALL,
+                Exercise::European,
+                None,
+                100.0,
+                100.0,
+                0.05,
+                0.05,
+                0.2,
+                0.0,
+                &PdeParams {
+                    time_steps: 10,
+                    rannacher_steps,
+                    ..PARAMS
+                },
+            )
+            .gamma
+        };
+        let d1 = (0.05f32 + 0.02) * 0.05 / (0.2 * 0.05f32.sqrt());
+        let gamma = (-d1 * d1 / 2.0).exp()
+            / (2.0 * std::f32::consts::PI).sqrt()
+            / (100.0 * 0.2 * 0.05f32.sqrt());
+        assert!((run(2) - gamma).abs() < 0.01 * gamma);
+        assert!((run(0) - gamma).abs() > (run(2) - gamma).abs());
+    }
+
+    #[test]
+    fn knock_ins_against_closed_form() {
+        use crate::barrier::{barrier_price, BarrierKind};
+        for (dir, barrier_type, kind, level, strike) in [
+            (
+                OptionDir::CALL,
+                BarrierType::DownAndIn,
+                BarrierKind::DownAndIn,
+                90.0,
+                100.0,
+            ),
+            (
+                OptionDir::PUT,
+                BarrierType::DownAndIn,
+                BarrierKind::DownAndIn,
+                90.0,
+                95.0,
+            ),
+            (
+                OptionDir::CALL,
+                BarrierType::UpAndIn,
+                BarrierKind::UpAndIn,
+                115.0,
+                105.0,
+            ),
+            (
+                OptionDir::PUT,
+                BarrierType::UpAndIn,
+                BarrierKind::UpAndIn,
+                110.0,
+                100.0,
+            ),
+        ] {
+            let pde = pde_price(
+                dir,
+                Exercise::European,
+                Some(Barrier {
+                    barrier_type,
+                    level,
+                    rebate: 2.0,
+                }),
+                100.0,
+                strike,
+                1.0,
+                0.05,
+                0.25,
+                0.02,
+                &PARAMS,
+            );
+            let closed_form = barrier_price(
+                dir, kind, 100.0, strike, level, 2.0, 1.0, 0.05, 0.25, 0.02, 0.0,
+            );
+            assert!((pde.pv - closed_form).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn american_knock_in() {
+        let price = |exercise, barrier_type, spot| {
+            pde_price(
+                OptionDir::PUT,
+                exercise,
+                Some(Barrier {
+                    barrier_type,
+                    level: 90.0,
+                    rebate: 0.0,
+                }),
+                spot,
+                100.0,
+                1.0,
+                0.08,
+                0.25,
+                0.0,
+                &PARAMS,
+            )
+            .pv
+        };
+        // Exercise is worth something once knocked in
+        let american = price(Exercise::American, BarrierType::DownAndIn, 100.0);
+        let european = price(Exercise::European, BarrierType::DownAndIn, 100.0);
+        assert!(american > european + 0.05);
+        // and at most the vanilla, which the knock-in and knock-out share between them
+        let vanilla = pde_price(
+            OptionDir::PUT,
+            Exercise::American,
+            None,
+            100.0,
+            100.0,
+            1.0,
+            0.08,
+            0.25,
+            0.0,
+            &PARAMS,
+        )
+        .pv;
+        assert!(american < vanilla);
+        assert!(american + price(Exercise::American, BarrierType::DownAndOut, 100.0) > vanilla);
+        // Already knocked in is the vanilla
+        assert_eq!(
+            price(Exercise::American, BarrierType::DownAndIn, 85.0),
+            pde_price(
+                OptionDir::PUT,
+                Exercise::American,
+                None,
+                85.0,
+                100.0,
+                1.0,
+                0.08,
+                0.25,
+                0.0,
+                &PARAMS,
+            )
+            .pv
+        );
+    }
+
+    #[test]
+    fn expired_is_intrinsic() {
+        let expired = pde_price(
+            OptionDir::PUT,
+            Exercise::American,
+            None,
+            90.0,
+            100.0,
+            0.0,
+            0.05,
+            0.25,
+            0.0,
+            &PARAMS,
+        );
+        assert_eq!(expired.pv, 10.0);
+        assert_eq!(expired.delta, -1.0);
+        assert_eq!(expired.theta, 0.0);
+    }
+}
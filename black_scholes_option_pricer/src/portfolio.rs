@@ -0,0 +1,436 @@
+//! Positions in options and their underlyings, priced together and aggregated into net risk
+//!
+//! An instrument is a European call or put on a named underlying, or the underlying itself, with a contract
+//! multiplier. A position holds a signed quantity of an instrument, and a portfolio holds positions with the market
+//! data of each underlying they reference.
+//!
+//! Options are priced in batch through the SIMD vanilla call and put greeks, one batch per option type.
+//! The greeks of each position are those of one unit of the instrument scaled by quantity times multiplier, so
+//! delta is in units of the underlying and sums across positions on the same underlying. Vega and rho are per
+//! 1.0 = 100% move and theta is the decay per year. Options at or past expiry are worth their intrinsic value.
+//!
+//! Net greeks are aggregated over the whole portfolio, per underlying, and per underlying and expiry bucket. A
+//! bucket is given by the ascending ends of its expiry ranges, and anything beyond the last end falls into one
+//! more bucket.
+use crate::bs::{call_greeks, put_greeks, OptionDir};
+use std::collections::{BTreeMap, HashMap};
+use wasm_bindgen::prelude::*;
+
+/// The type of an instrument
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum InstrumentType {
+    Call = 1,
+    Put = 0,
+    Underlying = 2,
+}
+
+/// A tradable contract. The strike and expiry of the underlying itself are ignored
+#[derive(PartialEq, Debug, Clone)]
+pub struct Instrument {
+    pub underlying: String,
+    pub instrument_type: InstrumentType,
+    pub strike: f32,
+    pub years_to_expiry: f32,
+    pub multiplier: f32,
+}
+
+/// A signed quantity of an instrument, negative when short
+#[derive(PartialEq, Debug, Clone)]
+pub struct Position {
+    pub instrument: Instrument,
+    pub quantity: f32,
+}
+
+/// Market data of one underlying
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct MarketData {
+    pub spot: f32,
+    pub risk_free_rate: f32,
+    pub volatility: f32,
+    pub dividend_yield: f32,
+}
+
+#[wasm_bindgen]
+impl MarketData {
+    /// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+    #[wasm_bindgen(constructor)]
+    pub fn new(spot: f32, risk_free_rate: f32, volatility: f32, dividend_yield: f32) -> MarketData {
+        MarketData {
+            spot,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+        }
+    }
+}
+
+/// Value and greeks of one or more positions, scaled by quantity and multiplier
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct PortfolioGreeks {
+    pub pv: f32,
+    pub delta: f32,
+    pub gamma: f32,
+    pub vega: f32,
+    pub theta: f32,
+    pub rho: f32,
+}
+
+impl std::ops::AddAssign for PortfolioGreeks {
+    fn add_assign(&mut self, other: PortfolioGreeks) {
+        self.pv += other.pv;
+        self.delta += other.delta;
+        self.gamma += other.gamma;
+        self.vega += other.vega;
+        self.theta += other.theta;
+        self.rho += other.rho;
+    }
+}
+
+impl std::ops::Mul<f32> for PortfolioGreeks {
+    type Output = PortfolioGreeks;
+    fn mul(self, scale: f32) -> PortfolioGreeks {
+        PortfolioGreeks {
+            pv: self.pv * scale,
+            delta: self.delta * scale,
+            gamma: self.gamma * scale,
+            vega: self.vega * scale,
+            theta: self.theta * scale,
+            rho: self.rho * scale,
+        }
+    }
+}
+
+/// Index of the expiry bucket, one past the last end for expiries beyond it
+fn bucket(years_to_expiry: f32, bucket_ends: &[f32]) -> usize {
+    bucket_ends
+        .iter()
+        .position(|&end| years_to_expiry <= end)
+        .unwrap_or(bucket_ends.len())
+}
+
+/// Positions and the market data of their underlyings
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    positions: Vec<Position>,
+    market: HashMap<String, MarketData>,
+}
+
+impl Portfolio {
+    pub fn add(&mut self, position: Position) {
+        self.positions.push(position);
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    pub fn market_data(&self, underlying: &str) -> Option<MarketData> {
+        self.market.get(underlying).copied()
+    }
+
+    /// Value and greeks of each position, in the order they were added. Positions on an underlying without market
+    /// data are NaN
+    pub fn position_greeks(&self) -> Vec<PortfolioGreeks> {
+        let nan = PortfolioGreeks {
+            pv: f32::NAN,
+            delta: f32::NAN,
+            gamma: f32::NAN,
+            vega: f32::NAN,
+            theta: f32::NAN,
+            rho: f32::NAN,
+        };
+        let mut unit = vec![nan; self.positions.len()];
+        for &(option_type, option_dir) in &[
+            (InstrumentType::Call, OptionDir::CALL),
+            (InstrumentType::Put, OptionDir::PUT),
+        ] {
+            let mut index = Vec::new();
+            let (mut s, mut k, mut t, mut r, mut v, mut q) =
+                (vec![], vec![], vec![], vec![], vec![], vec![]);
+            for (i, position) in self.positions.iter().enumerate() {
+                let instrument = &position.instrument;
+                if instrument.instrument_type != option_type {
+                    continue;
+                }
+                let market = match self.market.get(&instrument.underlying) {
+                    Some(market) => market,
+                    None => continue,
+                };
+                if instrument.years_to_expiry <= 0.0 {
+                    let phi = if option_dir == OptionDir::CALL {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                    let itm = phi * (market.spot - instrument.strike) > 0.0;
+                    unit[i] = PortfolioGreeks {
+                        pv: (phi * (market.spot - instrument.strike)).max(0.0),
+                        delta: if itm { phi } else { 0.0 },
+                        ..Default::default()
+                    };
+                    continue;
+                }
+                index.push(i);
+                s.push(market.spot);
+                k.push(instrument.strike);
+                t.push(instrument.years_to_expiry);
+                r.push(market.risk_free_rate);
+                v.push(market.volatility);
+                q.push(market.dividend_yield);
+            }
+            if index.is_empty() {
+                continue;
+            }
+            let greeks = match option_dir {
+                OptionDir::CALL => call_greeks(&s, &k, &t, &r, &v, &q),
+                OptionDir::PUT => put_greeks(&s, &k, &t, &r, &v, &q),
+            };
+            for (j, &i) in index.iter().enumerate() {
+                unit[i] = PortfolioGreeks {
+                    pv: greeks.pv[j],
+                    delta: greeks.delta[j],
+                    gamma: greeks.gamma[j],
+                    vega: greeks.vega[j],
+                    theta: greeks.theta[j],
+                    rho: greeks.rho[j],
+                };
+            }
+        }
+        for (i, position) in self.positions.iter().enumerate() {
+            let instrument = &position.instrument;
+            if instrument.instrument_type == InstrumentType::Underlying {
+                if let Some(market) = self.market.get(&instrument.underlying) {
+                    unit[i] = PortfolioGreeks {
+                        pv: market.spot,
+                        delta: 1.0,
+                        ..Default::default()
+                    };
+                }
+            }
+        }
+        unit.iter()
+            .zip(&self.positions)
+            .map(|(&greeks, position)| {
+                greeks * (position.quantity * position.instrument.multiplier)
+            })
+            .collect()
+    }
+
+    /// Net value and greeks of each underlying, by name
+    pub fn greeks_by_underlying(&self) -> BTreeMap<String, PortfolioGreeks> {
+        let mut net = BTreeMap::new();
+        for (greeks, position) in self.position_greeks().into_iter().zip(&self.positions) {
+            *net.entry(position.instrument.underlying.clone())
+                .or_insert_with(PortfolioGreeks::default) += greeks;
+        }
+        net
+    }
+
+    /// Net value and greeks of each underlying and expiry bucket, keyed by the name and the index of the bucket.
+    /// Holdings of the underlying itself fall into the first bucket
+    pub fn greeks_by_bucket(
+        &self,
+        bucket_ends: &[f32],
+    ) -> BTreeMap<(String, usize), PortfolioGreeks> {
+        let mut net = BTreeMap::new();
+        for (greeks, position) in self.position_greeks().into_iter().zip(&self.positions) {
+            let instrument = &position.instrument;
+            let index = match instrument.instrument_type {
+                InstrumentType::Underlying => 0,
+                _ => bucket(instrument.years_to_expiry, bucket_ends),
+            };
+            *net.entry((instrument.underlying.clone(), index))
+                .or_insert_with(PortfolioGreeks::default) += greeks;
+        }
+        net
+    }
+}
+
+#[wasm_bindgen]
+impl Portfolio {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Portfolio {
+        Portfolio::default()
+    }
+
+    /// Add a position in an instrument on an underlying, with a negative quantity when short
+    /// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+    pub fn add_position(
+        &mut self,
+        underlying: &str,
+        instrument_type: InstrumentType,
+        strike: f32,
+        years_to_expiry: f32,
+        quantity: f32,
+        multiplier: f32,
+    ) {
+        self.add(Position {
+            instrument: Instrument {
+                underlying: underlying.to_string(),
+                instrument_type,
+                strike,
+                years_to_expiry,
+                multiplier,
+            },
+            quantity,
+        });
+    }
+
+    /// Set or replace the market data of an underlying
+    pub fn set_market_data(&mut self, underlying: &str, market_data: MarketData) {
+        self.market.insert(underlying.to_string(), market_data);
+    }
+
+    /// Number of positions
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Net value and greeks of the whole portfolio
+    pub fn net_greeks(&self) -> PortfolioGreeks {
+        let mut net = PortfolioGreeks::default();
+        for greeks in self.position_greeks() {
+            net += greeks;
+        }
+        net
+    }
+
+    /// Net value and greeks of the positions on one underlying
+    pub fn underlying_greeks(&self, underlying: &str) -> PortfolioGreeks {
+        self.greeks_by_underlying()
+            .remove(underlying)
+            .unwrap_or_default()
+    }
+
+    /// Net value and greeks of the positions on one underlying in each expiry bucket, one more than the ends given
+    pub fn bucket_greeks(&self, underlying: &str, bucket_ends: &[f32]) -> Vec<PortfolioGreeks> {
+        let mut net = vec![PortfolioGreeks::default(); bucket_ends.len() + 1];
+        for ((name, index), greeks) in self.greeks_by_bucket(bucket_ends) {
+            if name == underlying {
+                net[index] = greeks;
+            }
+        }
+        net
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single;
+
+    fn portfolio() -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.25, 0.01));
+        portfolio.set_market_data("XYZ", MarketData::new(50.0, 0.03, 0.4, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Call, 105.0, 0.1, 10.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Put, 95.0, 0.5, -5.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Call, 100.0, 2.0, 3.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Underlying, 0.0, 0.0, -200.0, 1.0);
+        portfolio.add_position("XYZ", InstrumentType::Put, 45.0, 0.25, 20.0, 100.0);
+        portfolio.add_position("XYZ", InstrumentType::Call, 60.0, 0.25, -20.0, 100.0);
+        portfolio
+    }
+
+    #[test]
+    fn positions_match_scalar_greeks() {
+        let portfolio = portfolio();
+        let greeks = portfolio.position_greeks();
+        assert_eq!(greeks.len(), portfolio.len());
+        for (greeks, position) in greeks.iter().zip(portfolio.positions()) {
+            let instrument = &position.instrument;
+            let market = portfolio.market_data(&instrument.underlying).unwrap();
+            let scale = position.quantity * instrument.multiplier;
+            let dir = match instrument.instrument_type {
+                InstrumentType::Call => OptionDir::CALL,
+                InstrumentType::Put => OptionDir::PUT,
+                InstrumentType::Underlying => {
+                    assert_eq!(greeks.pv, market.spot * scale);
+                    assert_eq!(greeks.delta, scale);
+                    assert_eq!(greeks.gamma, 0.0);
+                    continue;
+                }
+            };
+            let (s, k, t) = (market.spot, instrument.strike, instrument.years_to_expiry);
+            let (r, v, q) = (
+                market.risk_free_rate,
+                market.volatility,
+                market.dividend_yield,
+            );
+            let close = |a: f32, b: f32| (a - b * scale).abs() < 1.0e-3 * scale.abs().max(1.0);
+            assert!(close(greeks.pv, bs_single::bs_price(dir, s, k, t, r, v, q)));
+            assert!(close(greeks.delta, bs_single::delta(dir, s, k, t, r, v, q)));
+            assert!(close(greeks.gamma, bs_single::gamma(s, k, t, r, v, q)));
+            assert!(close(greeks.vega, bs_single::vega(s, k, t, r, v, q)));
+            assert!(close(greeks.theta, bs_single::theta(dir, s, k, t, r, v, q)));
+            assert!(close(greeks.rho, bs_single::rho(dir, s, k, t, r, v, q)));
+        }
+    }
+
+    #[test]
+    fn aggregation() {
+        let portfolio = portfolio();
+        let positions = portfolio.position_greeks();
+        let net = portfolio.net_greeks();
+        let abc = portfolio.underlying_greeks("ABC");
+        let xyz = portfolio.underlying_greeks("XYZ");
+        assert!((abc.delta + xyz.delta - net.delta).abs() < 1.0e-2);
+        assert!((abc.pv + xyz.pv - net.pv).abs() < 1.0e-2);
+        let abc_vega: f32 = positions[..4].iter().map(|g| g.vega).sum();
+        assert!((abc.vega - abc_vega).abs() < 1.0e-2);
+        assert_eq!(
+            portfolio.underlying_greeks("none"),
+            PortfolioGreeks::default()
+        );
+
+        // Up to 3 months, up to 1 year and beyond
+        let ends = [0.25, 1.0];
+        let buckets = portfolio.bucket_greeks("ABC", &ends);
+        assert_eq!(buckets.len(), 3);
+        let mut first = positions[0];
+        first += positions[3];
+        assert_eq!(buckets[0], first);
+        assert_eq!(buckets[1], positions[1]);
+        assert_eq!(buckets[2], positions[2]);
+        let mut xyz_bucket = positions[4];
+        xyz_bucket += positions[5];
+        assert_eq!(portfolio.bucket_greeks("XYZ", &ends)[0], xyz_bucket);
+        assert_eq!(portfolio.greeks_by_bucket(&ends).len(), 4);
+
+        // The net gamma is the slope of the net delta
+        let bump = |spot: f32| {
+            let mut bumped = portfolio.clone();
+            bumped.set_market_data("ABC", MarketData::new(spot, 0.03, 0.25, 0.01));
+            bumped.underlying_greeks("ABC")
+        };
+        let (up, down) = (bump(100.5), bump(99.5));
+        assert!(((up.delta - down.delta) / 1.0 - abc.gamma).abs() < 0.02 * abc.gamma.abs());
+        assert!(((up.pv - down.pv) / 1.0 - abc.delta).abs() < 0.01 * abc.delta.abs().max(1.0));
+    }
+
+    #[test]
+    fn expired_and_missing_market_data() {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.25, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Call, 90.0, 0.0, 2.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Put, 90.0, 0.0, 2.0, 100.0);
+        let greeks = portfolio.position_greeks();
+        assert_eq!(greeks[0].pv, 2000.0);
+        assert_eq!(greeks[0].delta, 200.0);
+        assert_eq!(greeks[1].pv, 0.0);
+        assert_eq!(greeks[1].delta, 0.0);
+
+        portfolio.add_position("XYZ", InstrumentType::Call, 50.0, 0.5, 1.0, 100.0);
+        assert!(portfolio.position_greeks()[2].pv.is_nan());
+        assert!(portfolio.net_greeks().pv.is_nan());
+        assert_eq!(portfolio.underlying_greeks("ABC").pv, 2000.0);
+    }
+}
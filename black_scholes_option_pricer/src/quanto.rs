@@ -0,0 +1,503 @@
+//! Options on a foreign underlying settled in the domestic currency
+//!
+//! The exchange rate is quoted as domestic currency per unit of foreign currency, with its own volatility and a
+//! correlation to the underlying's returns measured in the foreign currency.
+//!
+//! * A quanto pays the foreign payoff max(phi (S - K), 0), with the strike in the foreign currency, converted at an
+//!   exchange rate fixed up front. Under the domestic measure the underlying drifts at rf - q - rho vs vx, so it is
+//!   Black Scholes at the domestic rate with an adjusted dividend yield of rd - rf + q + rho vs vx, times the fixed
+//!   rate
+//! * A composite pays max(phi (S X - K), 0) with the strike in the domestic currency. It is Black Scholes on the
+//!   converted underlying S X, which has volatility sqrt(vs^2 + vx^2 + 2 rho vs vx)
+//!
+//! Prices and greeks come from the vanilla, via the chain rule through the adjusted dividend yield or volatility.
+//! The scalar versions are computed in f64, the batch versions eight at a time in f32x8. The greeks are sensitivities of the domestic value, with delta and gamma per unit of the
+//! underlying in its own currency. A quanto has no exchange rate delta since its conversion rate is fixed.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_::price_f32x8;
+use crate::normal::{european, ncd_f64, npd_f64, sign};
+use bytemuck::cast;
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// Price and greeks of a single option in the domestic currency
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone)]
+pub struct QuantoGreeks {
+    pub pv: f32,
+    pub delta: f32,
+    pub fx_delta: f32,
+    pub gamma: f32,
+    pub theta: f32,
+    pub rho: f32,
+    pub foreign_rho: f32,
+    pub vega: f32,
+    pub fx_vega: f32,
+    pub correlation: f32,
+}
+
+/// Black Scholes price and greeks in f64
+struct Vanilla {
+    pv: f64,
+    delta: f64,
+    gamma: f64,
+    theta: f64,
+    rho: f64,
+    vega: f64,
+}
+
+fn vanilla(option_dir: OptionDir, s: f64, k: f64, t: f64, r: f64, v: f64, q: f64) -> Vanilla {
+    let phi = sign(option_dir);
+    let sd = v * t.sqrt();
+    let d1 = ((s / k).ln() + (r - q + v * v / 2.0) * t) / sd;
+    let d2 = d1 - sd;
+    let (asset, cash) = ((-q * t).exp(), (-r * t).exp());
+    let density = asset * npd_f64(d1);
+    Vanilla {
+        pv: european(phi, s, k, t, r, v, q),
+        delta: phi * asset * ncd_f64(phi * d1),
+        gamma: density / (s * sd),
+        theta: -s * density * v / (2.0 * t.sqrt()) - phi * r * k * cash * ncd_f64(phi * d2)
+            + phi * q * s * asset * ncd_f64(phi * d1),
+        rho: phi * k * t * cash * ncd_f64(phi * d2),
+        vega: s * density * t.sqrt(),
+    }
+}
+
+/// Quanto option price and greeks, the foreign payoff converted at `fixed_fx_rate`
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rates, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn quanto(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    foreign_rate: f32,
+    fx_volatility: f32,
+    correlation: f32,
+    fixed_fx_rate: f32,
+) -> QuantoGreeks {
+    let (s, t, v) = (spot as f64, years_to_expiry as f64, volatility as f64);
+    let (vx, rho, x) = (
+        fx_volatility as f64,
+        correlation as f64,
+        fixed_fx_rate as f64,
+    );
+    let r = risk_free_rate as f64;
+    let adjusted_yield = r - foreign_rate as f64 + dividend_yield as f64 + rho * v * vx;
+    let g = vanilla(option_dir, s, strike as f64, t, r, v, adjusted_yield);
+    // Sensitivity to the adjusted dividend yield, through which the rates, vols and correlation act
+    let dq = -t * s * g.delta;
+    QuantoGreeks {
+        pv: (x * g.pv) as f32,
+        delta: (x * g.delta) as f32,
+        fx_delta: 0.0,
+        gamma: (x * g.gamma) as f32,
+        theta: (x * g.theta) as f32,
+        rho: (x * (g.rho + dq)) as f32,
+        foreign_rho: (-x * dq) as f32,
+        vega: (x * (g.vega + rho * vx * dq)) as f32,
+        fx_vega: (x * rho * v * dq) as f32,
+        correlation: (x * v * vx * dq) as f32,
+    }
+}
+
+/// Composite option price and greeks, on the underlying converted at the spot `fx_rate` with a domestic strike
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn composite(
+    option_dir: OptionDir,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility: f32,
+    dividend_yield: f32,
+    fx_volatility: f32,
+    correlation: f32,
+    fx_rate: f32,
+) -> QuantoGreeks {
+    let (s, v, x) = (spot as f64, volatility as f64, fx_rate as f64);
+    let (vx, rho) = (fx_volatility as f64, correlation as f64);
+    let composite_vol = (v * v + vx * vx + 2.0 * rho * v * vx).sqrt();
+    let g = vanilla(
+        option_dir,
+        s * x,
+        strike as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+        composite_vol,
+        dividend_yield as f64,
+    );
+    QuantoGreeks {
+        pv: g.pv as f32,
+        delta: (x * g.delta) as f32,
+        fx_delta: (s * g.delta) as f32,
+        gamma: (x * x * g.gamma) as f32,
+        theta: g.theta as f32,
+        rho: g.rho as f32,
+        foreign_rho: 0.0,
+        vega: (g.vega * (v + rho * vx) / composite_vol) as f32,
+        fx_vega: (g.vega * (vx + rho * v) / composite_vol) as f32,
+        correlation: (g.vega * v * vx / composite_vol) as f32,
+    }
+}
+
+/// Quanto prices of arrays eight at a time
+#[allow(clippy::too_many_arguments)]
+fn batch_quanto(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    foreign_rate: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fixed_fx_rate: &[f32],
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let s = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let k = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let t = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let r = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let v = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let q = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let rf = f32x8::from(&foreign_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let vx = f32x8::from(&fx_volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let rho = f32x8::from(&correlation[i..std::cmp::min(max_idx, i + 8)]);
+        let fx = f32x8::from(&fixed_fx_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let adjusted_yield = r - rf + q + rho * v * vx;
+        let price: [f32; 8] = cast(fx * price_f32x8(option_dir, s, k, t, r, v, adjusted_yield));
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Composite prices of arrays eight at a time
+#[allow(clippy::too_many_arguments)]
+fn batch_composite(
+    option_dir: OptionDir,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fx_rate: &[f32],
+) -> Vec<f32> {
+    let max_idx = spot.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let s = f32x8::from(&spot[i..std::cmp::min(max_idx, i + 8)]);
+        let k = f32x8::from(&strike[i..std::cmp::min(max_idx, i + 8)]);
+        let t = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let r = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let v = f32x8::from(&volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let q = f32x8::from(&dividend_yield[i..std::cmp::min(max_idx, i + 8)]);
+        let vx = f32x8::from(&fx_volatility[i..std::cmp::min(max_idx, i + 8)]);
+        let rho = f32x8::from(&correlation[i..std::cmp::min(max_idx, i + 8)]);
+        let fx = f32x8::from(&fx_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let composite_vol = (v * v + vx * vx + 2.0 * rho * v * vx).sqrt();
+        let price: [f32; 8] = cast(price_f32x8(option_dir, s * fx, k, t, r, composite_vol, q));
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Quanto call pricing in the domestic currency. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rates, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn quanto_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    foreign_rate: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fixed_fx_rate: &[f32],
+) -> Vec<f32> {
+    batch_quanto(
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        foreign_rate,
+        fx_volatility,
+        correlation,
+        fixed_fx_rate,
+    )
+}
+
+/// Quanto put pricing in the domestic currency. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rates, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn quanto_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    foreign_rate: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fixed_fx_rate: &[f32],
+) -> Vec<f32> {
+    batch_quanto(
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        foreign_rate,
+        fx_volatility,
+        correlation,
+        fixed_fx_rate,
+    )
+}
+
+/// Composite call pricing in the domestic currency. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn composite_call(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fx_rate: &[f32],
+) -> Vec<f32> {
+    batch_composite(
+        OptionDir::CALL,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        fx_volatility,
+        correlation,
+        fx_rate,
+    )
+}
+
+/// Composite put pricing in the domestic currency. The results are at the same index as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn composite_put(
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    fx_volatility: &[f32],
+    correlation: &[f32],
+    fx_rate: &[f32],
+) -> Vec<f32> {
+    batch_composite(
+        OptionDir::PUT,
+        spot,
+        strike,
+        years_to_expiry,
+        risk_free_rate,
+        volatility,
+        dividend_yield,
+        fx_volatility,
+        correlation,
+        fx_rate,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+    use crate::monte_carlo::Rng;
+    use crate::normal::inverse_ncd;
+
+    const S: f32 = 100.0;
+    const T: f32 = 0.75;
+    const RD: f32 = 0.04;
+    const RF: f32 = 0.02;
+    const V: f32 = 0.25;
+    const Q: f32 = 0.01;
+    const VX: f32 = 0.12;
+    const RHO: f32 = -0.4;
+    const FX: f32 = 1.25;
+
+    /// Terminal underlying and exchange rate simulated under the foreign measure, where the exchange rate drifts at
+    /// rd - rf + vx^2, with antithetic pairs
+    fn terminal_spots(n: usize) -> Vec<(f64, f64)> {
+        let (t, rd, rf, v, q, vx, rho) = (
+            T as f64, RD as f64, RF as f64, V as f64, Q as f64, VX as f64, RHO as f64,
+        );
+        let mut rng = Rng::new(11);
+        let mut spots = Vec::with_capacity(2 * n);
+        for _ in 0..n {
+            let z1 = inverse_ncd(rng.uniform());
+            let z2 = rho * z1 + (1.0 - rho * rho).sqrt() * inverse_ncd(rng.uniform());
+            for sign in [1.0, -1.0] {
+                let s = S as f64 * ((rf - q - v * v / 2.0) * t + sign * v * t.sqrt() * z1).exp();
+                let x =
+                    FX as f64 * ((rd - rf + vx * vx / 2.0) * t + sign * vx * t.sqrt() * z2).exp();
+                spots.push((s, x));
+            }
+        }
+        spots
+    }
+
+    /// Domestic value of a domestic payoff, discounted in the foreign currency and converted at today's rate
+    fn domestic_value(spots: &[(f64, f64)], payoff: impl Fn(f64, f64) -> f64) -> f32 {
+        let mean = spots.iter().map(|&(s, x)| payoff(s, x) / x).sum::<f64>() / spots.len() as f64;
+        (FX as f64 * (-RF as f64 * T as f64).exp() * mean) as f32
+    }
+
+    #[test]
+    fn against_monte_carlo() {
+        let spots = terminal_spots(100_000);
+        for (dir, phi) in [(OptionDir::CALL, 1.0), (OptionDir::PUT, -1.0)] {
+            for strike in [90.0, 100.0, 110.0] {
+                let q = quanto(dir, S, strike, T, RD, V, Q, RF, VX, RHO, 1.5);
+                let mc = domestic_value(&spots, |s, _| 1.5 * (phi * (s - strike as f64)).max(0.0));
+                assert!((q.pv - mc).abs() < 0.01 * q.pv.max(1.0));
+
+                let c = composite(dir, S, strike * FX, T, RD, V, Q, VX, RHO, FX);
+                let mc = domestic_value(&spots, |s, x| {
+                    (phi * (s * x - (strike * FX) as f64)).max(0.0)
+                });
+                assert!((c.pv - mc).abs() < 0.01 * c.pv.max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn reduces_to_black_scholes() {
+        // Uncorrelated with equal rates the quanto is the foreign vanilla at the fixed rate
+        let q = quanto(OptionDir::CALL, S, 105.0, T, RD, V, Q, RD, VX, 0.0, 1.5);
+        let call = bs_price(OptionDir::CALL, S, 105.0, T, RD, V, Q);
+        assert!((q.pv - 1.5 * call).abs() < 1.0e-4);
+        // A composite with a fixed exchange rate is the vanilla on the converted underlying
+        let c = composite(OptionDir::PUT, S, 120.0, T, RD, V, Q, 0.0, RHO, FX);
+        let put = bs_price(OptionDir::PUT, S * FX, 120.0, T, RD, V, Q);
+        assert!((c.pv - put).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn greeks_against_finite_differences() {
+        let h = 0.002;
+        for dir in [OptionDir::CALL, OptionDir::PUT] {
+            let price = |s: f32, t: f32, rd: f32, rf: f32, v: f32, vx: f32, rho: f32| {
+                quanto(dir, s, 102.0, t, rd, v, Q, rf, vx, rho, 1.5).pv
+            };
+            let g = quanto(dir, S, 102.0, T, RD, V, Q, RF, VX, RHO, 1.5);
+            let up = price(S + 0.5, T, RD, RF, V, VX, RHO);
+            let down = price(S - 0.5, T, RD, RF, V, VX, RHO);
+            assert!((g.delta - (up - down)).abs() < 2.0e-3);
+            assert!((g.gamma - (up - 2.0 * g.pv + down) / 0.25).abs() < 2.0e-3);
+            let theta =
+                -(price(S, T + h, RD, RF, V, VX, RHO) - price(S, T - h, RD, RF, V, VX, RHO));
+            assert!((g.theta - theta / (2.0 * h)).abs() < 0.05);
+            let rho = price(S, T, RD + h, RF, V, VX, RHO) - price(S, T, RD - h, RF, V, VX, RHO);
+            assert!((g.rho - rho / (2.0 * h)).abs() < 0.1);
+            let rho = price(S, T, RD, RF + h, V, VX, RHO) - price(S, T, RD, RF - h, V, VX, RHO);
+            assert!((g.foreign_rho - rho / (2.0 * h)).abs() < 0.1);
+            let vega = price(S, T, RD, RF, V + h, VX, RHO) - price(S, T, RD, RF, V - h, VX, RHO);
+            assert!((g.vega - vega / (2.0 * h)).abs() < 0.1);
+            let vega = price(S, T, RD, RF, V, VX + h, RHO) - price(S, T, RD, RF, V, VX - h, RHO);
+            assert!((g.fx_vega - vega / (2.0 * h)).abs() < 0.1);
+            let corr = price(S, T, RD, RF, V, VX, RHO + h) - price(S, T, RD, RF, V, VX, RHO - h);
+            assert!((g.correlation - corr / (2.0 * h)).abs() < 0.1);
+
+            let price = |s: f32, t: f32, rd: f32, v: f32, vx: f32, rho: f32, fx: f32| {
+                composite(dir, s, 130.0, t, rd, v, Q, vx, rho, fx).pv
+            };
+            let g = composite(dir, S, 130.0, T, RD, V, Q, VX, RHO, FX);
+            let up = price(S + 0.5, T, RD, V, VX, RHO, FX);
+            let down = price(S - 0.5, T, RD, V, VX, RHO, FX);
+            assert!((g.delta - (up - down)).abs() < 2.0e-3);
+            assert!((g.gamma - (up - 2.0 * g.pv + down) / 0.25).abs() < 2.0e-3);
+            let fx_delta =
+                price(S, T, RD, V, VX, RHO, FX + h) - price(S, T, RD, V, VX, RHO, FX - h);
+            assert!((g.fx_delta - fx_delta / (2.0 * h)).abs() < 0.1);
+            let theta =
+                -(price(S, T + h, RD, V, VX, RHO, FX) - price(S, T - h, RD, V, VX, RHO, FX));
+            assert!((g.theta - theta / (2.0 * h)).abs() < 0.05);
+            let rho = price(S, T, RD + h, V, VX, RHO, FX) - price(S, T, RD - h, V, VX, RHO, FX);
+            assert!((g.rho - rho / (2.0 * h)).abs() < 0.1);
+            let vega = price(S, T, RD, V + h, VX, RHO, FX) - price(S, T, RD, V - h, VX, RHO, FX);
+            assert!((g.vega - vega / (2.0 * h)).abs() < 0.1);
+            let vega = price(S, T, RD, V, VX + h, RHO, FX) - price(S, T, RD, V, VX - h, RHO, FX);
+            assert!((g.fx_vega - vega / (2.0 * h)).abs() < 0.1);
+            let corr = price(S, T, RD, V, VX, RHO + h, FX) - price(S, T, RD, V, VX, RHO - h, FX);
+            assert!((g.correlation - corr / (2.0 * h)).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        let spot = [80.0, 90.0, 95.0, 100.0, 105.0, 110.0, 120.0, 130.0, 140.0];
+        let n = spot.len();
+        let strike = vec![100.0; n];
+        let t = vec![T; n];
+        let rd = vec![RD; n];
+        let v = vec![V; n];
+        let q = vec![Q; n];
+        let rf = vec![RF; n];
+        let vx = vec![VX; n];
+        let rho = vec![RHO; n];
+        let fx = vec![FX; n];
+        let calls = quanto_call(&spot, &strike, &t, &rd, &v, &q, &rf, &vx, &rho, &fx);
+        let puts = quanto_put(&spot, &strike, &t, &rd, &v, &q, &rf, &vx, &rho, &fx);
+        let strike = vec![125.0; n];
+        let composite_calls = composite_call(&spot, &strike, &t, &rd, &v, &q, &vx, &rho, &fx);
+        let composite_puts = composite_put(&spot, &strike, &t, &rd, &v, &q, &vx, &rho, &fx);
+        assert_eq!(calls.len(), n);
+        assert_eq!(composite_puts.len(), n);
+        for (i, &s) in spot.iter().enumerate() {
+            let call = quanto(OptionDir::CALL, s, 100.0, T, RD, V, Q, RF, VX, RHO, FX).pv;
+            let put = quanto(OptionDir::PUT, s, 100.0, T, RD, V, Q, RF, VX, RHO, FX).pv;
+            assert!((calls[i] - call).abs() < 1.0e-3);
+            assert!((puts[i] - put).abs() < 1.0e-3);
+            let call = composite(OptionDir::CALL, s, 125.0, T, RD, V, Q, VX, RHO, FX).pv;
+            let put = composite(OptionDir::PUT, s, 125.0, T, RD, V, Q, VX, RHO, FX).pv;
+            assert!((composite_calls[i] - call).abs() < 1.0e-3);
+            assert!((composite_puts[i] - put).abs() < 1.0e-3);
+        }
+    }
+}
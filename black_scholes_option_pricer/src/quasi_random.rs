@@ -0,0 +1,382 @@
+//! Quasi random sampling for the Monte Carlo engine
+//!
+//! * Sobol sequences with the Joe-Kuo (2008) direction numbers for the first 32 dimensions. Dimensions past the
+//!   table take the next primitive polynomials in the same order, with initial direction numbers drawn at random
+//!   from a fixed seed as in Jaeckel (2002). They are valid Sobol dimensions, but their two dimensional
+//!   projections are not optimised like those of the table
+//! * Optional Matousek (1998) linear matrix scrambling followed by a random digital shift
+//! * Brownian bridge construction, which puts most of the path variance on the first Sobol dimensions
+use crate::monte_carlo::Rng;
+use std::collections::VecDeque;
+use wide::*;
+
+/// Joe-Kuo new-joe-kuo-6.21201 primitive polynomials (degree, coefficients) and initial direction numbers
+/// for dimensions 2 onwards. Dimension 1 is the van der Corput sequence
+const JOE_KUO: [(u32, u32, &[u32]); 31] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+    (7, 7, &[1, 1, 3, 13, 7, 35, 63]),
+    (7, 8, &[1, 3, 5, 9, 1, 25, 53]),
+    (7, 14, &[1, 3, 1, 13, 9, 35, 107]),
+    (7, 19, &[1, 3, 1, 5, 27, 61, 31]),
+    (7, 21, &[1, 1, 5, 11, 19, 41, 61]),
+    (7, 28, &[1, 3, 5, 3, 3, 13, 69]),
+    (7, 31, &[1, 1, 7, 13, 1, 19, 1]),
+    (7, 32, &[1, 3, 7, 5, 13, 19, 59]),
+    (7, 37, &[1, 1, 3, 9, 25, 29, 41]),
+    (7, 41, &[1, 3, 5, 13, 23, 1, 55]),
+    (7, 42, &[1, 3, 7, 3, 13, 59, 17]),
+];
+
+/// Number of dimensions the Sobol generator supports
+pub const SOBOL_DIMENSIONS: usize = 1024;
+
+const BITS: usize = 32;
+
+/// Seed of the initial direction numbers of the dimensions past the Joe-Kuo table
+const DIRECTION_SEED: u64 = 0x5eed_d1ec_7104;
+
+/// Random lower triangular binary matrix with a unit diagonal for linear matrix scrambling. Digit i of a number is
+/// its bit BITS - 1 - i, and row i holds the digits that are added up into digit i of the product
+fn lower_triangular(rng: &mut Rng) -> [u32; BITS] {
+    let mut rows = [0u32; BITS];
+    for (i, row) in rows.iter_mut().enumerate() {
+        let diagonal = 1u32 << (BITS - 1 - i);
+        let above = !(diagonal | (diagonal - 1));
+        *row = ((rng.next_u64() >> 32) as u32 & above) | diagonal;
+    }
+    rows
+}
+
+/// Product of a binary matrix by the digits of `x`
+fn mat_mul(rows: &[u32; BITS], x: u32) -> u32 {
+    rows.iter().enumerate().fold(0, |y, (i, row)| {
+        y | (((row & x).count_ones() & 1) << (BITS - 1 - i))
+    })
+}
+
+/// a * b modulo the polynomial `p` of degree `degree` over GF(2), with polynomials as bit sets
+fn mul_mod(mut a: u32, mut b: u32, p: u32, degree: u32) -> u32 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        b >>= 1;
+        a <<= 1;
+        if (a >> degree) & 1 == 1 {
+            a ^= p;
+        }
+    }
+    product
+}
+
+/// x^e modulo the polynomial `p` of degree `degree` over GF(2)
+fn pow_x_mod(mut e: u32, p: u32, degree: u32) -> u32 {
+    let (mut result, mut base) = (1, mul_mod(2, 1, p, degree));
+    while e != 0 {
+        if e & 1 == 1 {
+            result = mul_mod(result, base, p, degree);
+        }
+        base = mul_mod(base, base, p, degree);
+        e >>= 1;
+    }
+    result
+}
+
+/// Whether x generates the multiplicative group modulo `p`, i.e. has order 2^degree - 1
+fn is_primitive(p: u32, degree: u32) -> bool {
+    let order = (1u32 << degree) - 1;
+    if pow_x_mod(order, p, degree) != 1 {
+        return false;
+    }
+    let (mut rest, mut factor) = (order, 2);
+    while rest > 1 {
+        if factor * factor > rest {
+            factor = rest;
+        }
+        if rest % factor == 0 {
+            if pow_x_mod(order / factor, p, degree) == 1 {
+                return false;
+            }
+            while rest % factor == 0 {
+                rest /= factor;
+            }
+        }
+        factor += 1;
+    }
+    true
+}
+
+/// Primitive polynomials from (`degree`, `coefficients`) on, ordered and encoded as in the Joe-Kuo table
+fn primitive_polynomials(degree: u32, coefficients: u32) -> impl Iterator<Item = (u32, u32)> {
+    (degree..BITS as u32)
+        .flat_map(move |s| {
+            let from = if s == degree { coefficients } else { 0 };
+            (from..1 << (s - 1)).map(move |a| (s, a))
+        })
+        .filter(|&(s, a)| is_primitive((1 << s) | (a << 1) | 1, s))
+}
+
+/// Sobol low discrepancy sequence generator, points are generated in Gray code order
+#[derive(Debug, Clone)]
+pub struct Sobol {
+    index: u32,
+    direction: Vec<[u32; BITS]>,
+    state: Vec<u32>,
+    shift: Vec<u32>,
+}
+
+impl Sobol {
+    /// Sobol generator of `dimensions` up to `SOBOL_DIMENSIONS`.
+    /// With a scramble seed the digits of each dimension are mixed by a random lower triangular matrix and then
+    /// digitally shifted, which keeps the net structure of the points while making the estimate unbiased. Without it
+    /// the first point (the origin) is skipped
+    pub fn new(dimensions: usize, scramble_seed: Option<u64>) -> Sobol {
+        assert!(
+            dimensions <= SOBOL_DIMENSIONS,
+            "Sobol supports up to {} dimensions",
+            SOBOL_DIMENSIONS
+        );
+        let (last_degree, last_coefficients, _) = JOE_KUO[JOE_KUO.len() - 1];
+        let mut polynomials = primitive_polynomials(last_degree, last_coefficients + 1);
+        let mut rng = Rng::new(DIRECTION_SEED);
+        let mut direction = Vec::with_capacity(dimensions);
+        for d in 0..dimensions {
+            let mut v = [0u32; BITS];
+            if d == 0 {
+                for (k, v) in v.iter_mut().enumerate() {
+                    *v = 1 << (BITS - 1 - k);
+                }
+            } else {
+                let initial: Vec<u32>;
+                let (s, a, m) = match JOE_KUO.get(d - 1) {
+                    Some(&entry) => entry,
+                    None => {
+                        let (s, a) = polynomials.next().expect("enough primitive polynomials");
+                        // Odd and below 2^(k + 1)
+                        initial = (0..s)
+                            .map(|k| ((rng.next_u64() >> 32) as u32 >> (31 - k)) | 1)
+                            .collect();
+                        (s, a, &initial[..])
+                    }
+                };
+                let s = s as usize;
+                for k in 0..s {
+                    v[k] = m[k] << (BITS - 1 - k);
+                }
+                for k in s..BITS {
+                    v[k] = v[k - s] ^ (v[k - s] >> s);
+                    for j in 1..s {
+                        if (a >> (s - 1 - j)) & 1 == 1 {
+                            v[k] ^= v[k - j];
+                        }
+                    }
+                }
+            }
+            direction.push(v);
+        }
+        let shift = match scramble_seed {
+            Some(seed) => {
+                let mut rng = Rng::new(seed);
+                // The state is a sum of direction numbers, so scrambling them scrambles every point
+                for v in direction.iter_mut() {
+                    let rows = lower_triangular(&mut rng);
+                    for x in v.iter_mut() {
+                        *x = mat_mul(&rows, *x);
+                    }
+                }
+                (0..dimensions)
+                    .map(|_| (rng.next_u64() >> 32) as u32)
+                    .collect()
+            }
+            None => vec![0; dimensions],
+        };
+        let mut sobol = Sobol {
+            index: 0,
+            direction,
+            state: vec![0; dimensions],
+            shift,
+        };
+        if scramble_seed.is_none() {
+            let mut origin = vec![0.0; dimensions];
+            sobol.next_point(&mut origin);
+        }
+        sobol
+    }
+
+    /// Next point of the sequence in (0, 1) for every dimension
+    pub fn next_point(&mut self, point: &mut [f64]) {
+        for ((p, x), s) in point
+            .iter_mut()
+            .zip(self.state.iter())
+            .zip(self.shift.iter())
+        {
+            *p = ((x ^ s) as f64 + 0.5) / (1u64 << BITS) as f64;
+        }
+        // Gray code update, flip the direction number of the lowest zero bit of the index
+        let c = (!self.index).trailing_zeros() as usize;
+        for (x, v) in self.state.iter_mut().zip(self.direction.iter()) {
+            *x ^= v[c.min(BITS - 1)];
+        }
+        self.index = self.index.wrapping_add(1);
+    }
+}
+
+/// Brownian bridge over equally spaced steps.
+/// The terminal value is drawn first, then the midpoints recursively, so the leading normals decide the
+/// overall shape of the path
+#[derive(Debug, Clone)]
+pub struct BrownianBridge {
+    /// (point, left, right, left weight, right weight, std dev) in construction order, left/right index 0 is time 0
+    order: Vec<(usize, usize, usize, f32, f32, f32)>,
+}
+
+impl BrownianBridge {
+    pub fn new(steps: usize) -> BrownianBridge {
+        let mut order = Vec::with_capacity(steps);
+        // Points are 1..=steps, the terminal one conditioned on time 0 only
+        order.push((steps, 0, 0, 0.0, 0.0, (steps as f32).sqrt()));
+        // Breadth first so the coarsest midpoints come first
+        let mut intervals = VecDeque::new();
+        intervals.push_back((0usize, steps));
+        while let Some((left, right)) = intervals.pop_front() {
+            if right - left < 2 {
+                continue;
+            }
+            let mid = (left + right) / 2;
+            let (l, r) = ((mid - left) as f32, (right - mid) as f32);
+            order.push((
+                mid,
+                left,
+                right,
+                r / (l + r),
+                l / (l + r),
+                (l * r / (l + r)).sqrt(),
+            ));
+            intervals.push_back((left, mid));
+            intervals.push_back((mid, right));
+        }
+        BrownianBridge { order }
+    }
+
+    /// Turn normals in construction order into standard normal increments for each step, eight paths at a time
+    pub fn increments(&self, z: &[f32x8], increments: &mut [f32x8]) {
+        let steps = increments.len();
+        let mut w = vec![f32x8::ZERO; steps + 1];
+        for (&(point, left, right, wl, wr, sd), z) in self.order.iter().zip(z.iter()) {
+            w[point] = w[left] * wl + w[right] * wr + *z * sd;
+        }
+        for (i, inc) in increments.iter_mut().enumerate() {
+            *inc = w[i + 1] - w[i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_points() {
+        let mut sobol = Sobol::new(3, None);
+        let mut p = [0.0; 3];
+        let expected = [
+            [0.5, 0.5, 0.5],
+            [0.75, 0.25, 0.25],
+            [0.25, 0.75, 0.75],
+            [0.375, 0.375, 0.625],
+        ];
+        for e in expected.iter() {
+            sobol.next_point(&mut p);
+            for d in 0..3 {
+                assert!((p[d] - e[d]).abs() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn polynomials_follow_the_table() {
+        // Enumerating from the start finds the polynomials of the Joe-Kuo table in the same order
+        let found: Vec<(u32, u32)> = primitive_polynomials(1, 0).take(JOE_KUO.len()).collect();
+        let table: Vec<(u32, u32)> = JOE_KUO.iter().map(|&(s, a, _)| (s, a)).collect();
+        assert_eq!(found, table);
+        // Every primitive polynomial up to degree 13, 1110 of them, gives enough dimensions
+        assert_eq!(
+            primitive_polynomials(1, 0)
+                .take_while(|p| p.0 <= 13)
+                .count(),
+            1110
+        );
+    }
+
+    #[test]
+    fn stratified() {
+        // Every dimension of the first 2^k points is a permutation of the 2^k equal intervals
+        let mut sobol = Sobol::new(SOBOL_DIMENSIONS, Some(3));
+        let n = 1024;
+        let mut counts = vec![vec![0; n]; SOBOL_DIMENSIONS];
+        let mut p = vec![0.0; SOBOL_DIMENSIONS];
+        for _ in 0..n {
+            sobol.next_point(&mut p);
+            for d in 0..SOBOL_DIMENSIONS {
+                counts[d][(p[d] * n as f64) as usize] += 1;
+            }
+        }
+        assert!(counts.iter().all(|c| c.iter().all(|&c| c == 1)));
+    }
+
+    #[test]
+    fn scrambling_is_not_a_shift() {
+        // A digital shift leaves the xor of two points unchanged, the matrix scrambling does not
+        let xor = |seed| {
+            let mut sobol = Sobol::new(4, Some(seed));
+            let (mut a, mut b) = ([0.0; 4], [0.0; 4]);
+            sobol.next_point(&mut a);
+            sobol.next_point(&mut b);
+            let bits = |x: f64| (x * (1u64 << BITS) as f64) as u32;
+            (0..4)
+                .map(|d| bits(a[d]) ^ bits(b[d]))
+                .collect::<Vec<u32>>()
+        };
+        assert_ne!(xor(1), xor(2));
+    }
+
+    #[test]
+    fn bridge_variance() {
+        // The bridge only reorders the randomness, a unit normal in one coordinate gives increments with the
+        // same total variance as the path
+        let steps = 7;
+        let bridge = BrownianBridge::new(steps);
+        let mut inc = vec![f32x8::ZERO; steps];
+        let mut total = vec![0.0f32; steps];
+        for k in 0..steps {
+            let mut z = vec![f32x8::ZERO; steps];
+            z[k] = f32x8::ONE;
+            bridge.increments(&z, &mut inc);
+            for (t, i) in total.iter_mut().zip(inc.iter()) {
+                let i: [f32; 8] = (*i).into();
+                *t += i[0] * i[0];
+            }
+        }
+        assert!(total.iter().all(|v| (v - 1.0).abs() < 1.0e-5));
+    }
+}
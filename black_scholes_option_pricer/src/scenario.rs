@@ -0,0 +1,266 @@
+//! Scenario P&L of a portfolio under shocks to spot, volatility and time
+//!
+//! A scenario moves the spot of every underlying by a relative shift, their volatility by a parallel shift plus a
+//! skew shift proportional to the log moneyness of each option, ln(K / S), and rolls time forward. Options expiring
+//! within the roll are worth their intrinsic value at the shocked spot, and shocked vols are floored at
+//! MIN_VOLATILITY.
+//!
+//! Every option is repriced in every scenario with the batch Black Scholes kernels. The inputs of one option are laid
+//! out contiguously across the scenarios, with the unshocked market first, so the SIMD lanes run over the scenario
+//! dimension and the P&L is measured against the same kernel's base price. A ladder is the P&L matrix of a grid of
+//! spot and vol shifts, row-major by spot shift.
+use crate::bs::{bs_call, bs_put};
+use crate::portfolio::{InstrumentType, Portfolio};
+use wasm_bindgen::prelude::*;
+
+/// Floor of the shocked volatilities
+pub const MIN_VOLATILITY: f32 = 0.01;
+
+/// Shocks applied to every underlying of a portfolio
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct Scenario {
+    /// Relative move of the spot, 0.05 = +5%
+    pub spot_shift: f32,
+    /// Parallel move of the volatility, 0.02 = +2 vol points
+    pub vol_shift: f32,
+    /// Move of the volatility per unit of log moneyness, negative to steepen the put wing
+    pub vol_skew: f32,
+    /// Years to roll forward
+    pub time_roll: f32,
+}
+
+#[wasm_bindgen]
+impl Scenario {
+    #[wasm_bindgen(constructor)]
+    pub fn new(spot_shift: f32, vol_shift: f32, vol_skew: f32, time_roll: f32) -> Scenario {
+        Scenario {
+            spot_shift,
+            vol_shift,
+            vol_skew,
+            time_roll,
+        }
+    }
+}
+
+/// P&L of the portfolio in each scenario against its current value. Positions on an underlying without market
+/// data make the P&L NaN
+pub fn scenario_pnl(portfolio: &Portfolio, scenarios: &[Scenario]) -> Vec<f32> {
+    // The base market in the first lane of each option, then the scenarios
+    let lanes = scenarios.len() + 1;
+    let shocks: Vec<Scenario> = std::iter::once(Scenario::default())
+        .chain(scenarios.iter().copied())
+        .collect();
+    let mut pnl = vec![0.0; scenarios.len()];
+    for &option_type in &[InstrumentType::Call, InstrumentType::Put] {
+        let mut scale = Vec::new();
+        let (mut s, mut k, mut t, mut r, mut v, mut q) =
+            (vec![], vec![], vec![], vec![], vec![], vec![]);
+        for position in portfolio.positions() {
+            let instrument = &position.instrument;
+            if instrument.instrument_type != option_type {
+                continue;
+            }
+            let market = match portfolio.market_data(&instrument.underlying) {
+                Some(market) => market,
+                None => {
+                    pnl.iter_mut().for_each(|p| *p = f32::NAN);
+                    continue;
+                }
+            };
+            scale.push(position.quantity * instrument.multiplier);
+            let moneyness = (instrument.strike / market.spot).ln();
+            for shock in &shocks {
+                s.push(market.spot * (1.0 + shock.spot_shift));
+                k.push(instrument.strike);
+                t.push(instrument.years_to_expiry - shock.time_roll);
+                r.push(market.risk_free_rate);
+                v.push(
+                    (market.volatility + shock.vol_shift + shock.vol_skew * moneyness)
+                        .max(MIN_VOLATILITY),
+                );
+                q.push(market.dividend_yield);
+            }
+        }
+        if scale.is_empty() {
+            continue;
+        }
+        let (prices, phi) = match option_type {
+            InstrumentType::Call => (bs_call(&s, &k, &t, &r, &v, &q), 1.0),
+            _ => (bs_put(&s, &k, &t, &r, &v, &q), -1.0),
+        };
+        let value = |i: usize| {
+            if t[i] <= 0.0 {
+                (phi * (s[i] - k[i])).max(0.0)
+            } else {
+                prices[i]
+            }
+        };
+        for (j, &scale) in scale.iter().enumerate() {
+            let base = value(j * lanes);
+            for (i, pnl) in pnl.iter_mut().enumerate() {
+                *pnl += scale * (value(j * lanes + i + 1) - base);
+            }
+        }
+    }
+    for position in portfolio.positions() {
+        let instrument = &position.instrument;
+        if instrument.instrument_type != InstrumentType::Underlying {
+            continue;
+        }
+        let scale = position.quantity * instrument.multiplier;
+        match portfolio.market_data(&instrument.underlying) {
+            Some(market) => {
+                for (pnl, shock) in pnl.iter_mut().zip(scenarios) {
+                    *pnl += scale * market.spot * shock.spot_shift;
+                }
+            }
+            None => pnl.iter_mut().for_each(|p| *p = f32::NAN),
+        }
+    }
+    pnl
+}
+
+/// P&L matrix of a portfolio over a grid of relative spot shifts and parallel vol shifts, with a common skew shift
+/// and time roll, row-major by spot shift
+/// Years to roll should be expressed as a f32 such as 20 days is 20/252 = 0.79
+#[wasm_bindgen]
+pub fn pnl_ladder(
+    portfolio: &Portfolio,
+    spot_shifts: &[f32],
+    vol_shifts: &[f32],
+    vol_skew: f32,
+    time_roll: f32,
+) -> Vec<f32> {
+    let scenarios: Vec<Scenario> = spot_shifts
+        .iter()
+        .flat_map(|&spot_shift| {
+            vol_shifts
+                .iter()
+                .map(move |&vol_shift| Scenario::new(spot_shift, vol_shift, vol_skew, time_roll))
+        })
+        .collect();
+    scenario_pnl(portfolio, &scenarios)
+}
+
+/// P&L of a portfolio from rolling time forward by each of the rolls with the market unchanged
+/// Years to roll should be expressed as a f32 such as 20 days is 20/252 = 0.79
+#[wasm_bindgen]
+pub fn time_decay(portfolio: &Portfolio, time_rolls: &[f32]) -> Vec<f32> {
+    let scenarios: Vec<Scenario> = time_rolls
+        .iter()
+        .map(|&time_roll| Scenario::new(0.0, 0.0, 0.0, time_roll))
+        .collect();
+    scenario_pnl(portfolio, &scenarios)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::MarketData;
+
+    fn portfolio() -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.25, 0.01));
+        portfolio.set_market_data("XYZ", MarketData::new(50.0, 0.03, 0.4, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Call, 105.0, 0.1, 10.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Put, 90.0, 0.5, 5.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Underlying, 0.0, 0.0, -300.0, 1.0);
+        portfolio.add_position("XYZ", InstrumentType::Call, 55.0, 0.25, -20.0, 100.0);
+        portfolio
+    }
+
+    fn shocked(portfolio: &Portfolio, spot_shift: f32, vol_shift: f32) -> Portfolio {
+        let mut shocked = portfolio.clone();
+        for name in &["ABC", "XYZ"] {
+            let market = portfolio.market_data(name).unwrap();
+            shocked.set_market_data(
+                name,
+                MarketData::new(
+                    market.spot * (1.0 + spot_shift),
+                    market.risk_free_rate,
+                    market.volatility + vol_shift,
+                    market.dividend_yield,
+                ),
+            );
+        }
+        shocked
+    }
+
+    #[test]
+    fn ladder_matches_full_revaluation() {
+        let portfolio = portfolio();
+        let base = portfolio.net_greeks().pv;
+        let (spot_shifts, vol_shifts) = ([-0.1, -0.05, 0.0, 0.05, 0.1], [-0.05, 0.0, 0.05]);
+        let ladder = pnl_ladder(&portfolio, &spot_shifts, &vol_shifts, 0.0, 0.0);
+        assert_eq!(ladder.len(), 15);
+        for (i, &spot_shift) in spot_shifts.iter().enumerate() {
+            for (j, &vol_shift) in vol_shifts.iter().enumerate() {
+                let pnl = shocked(&portfolio, spot_shift, vol_shift).net_greeks().pv - base;
+                assert!((ladder[i * 3 + j] - pnl).abs() < 2.0);
+            }
+        }
+        // The unshocked corner of the grid
+        assert!(ladder[7].abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn small_shocks_match_greeks() {
+        let portfolio = portfolio();
+        let net = portfolio.net_greeks();
+        let abc = portfolio.underlying_greeks("ABC");
+        let xyz = portfolio.underlying_greeks("XYZ");
+        let h = 0.002;
+        let pnl = scenario_pnl(
+            &portfolio,
+            &[
+                Scenario::new(h, 0.0, 0.0, 0.0),
+                Scenario::new(-h, 0.0, 0.0, 0.0),
+                Scenario::new(0.0, 0.01, 0.0, 0.0),
+                Scenario::new(0.0, -0.01, 0.0, 0.0),
+                Scenario::new(0.0, 0.0, 0.0, 1.0 / 365.0),
+            ],
+        );
+        let delta = (pnl[0] - pnl[1]) / (2.0 * h);
+        let gamma = (pnl[0] + pnl[1]) / (h * h);
+        let dollar_delta = abc.delta * 100.0 + xyz.delta * 50.0;
+        let dollar_gamma = abc.gamma * 100.0 * 100.0 + xyz.gamma * 50.0 * 50.0;
+        assert!((delta - dollar_delta).abs() < 0.01 * dollar_delta.abs().max(100.0));
+        assert!((gamma - dollar_gamma).abs() < 0.05 * dollar_gamma.abs().max(100.0));
+        assert!(((pnl[2] - pnl[3]) / 0.02 - net.vega).abs() < 0.01 * net.vega.abs());
+        // Up to the f32 rounding of the values
+        assert!((pnl[4] - net.theta / 365.0).abs() < 0.15);
+    }
+
+    #[test]
+    fn skew_and_expiry() {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.25, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Put, 80.0, 0.5, 1.0, 1.0);
+        portfolio.add_position("ABC", InstrumentType::Call, 120.0, 0.5, 1.0, 1.0);
+        // A skew shift moves the vol of each option by its log moneyness, raising the put wing and lowering the call
+        // wing
+        let pnl = scenario_pnl(&portfolio, &[Scenario::new(0.0, 0.0, -0.2, 0.0)]);
+        let parallel = |strike: f32, instrument_type| {
+            let mut single = Portfolio::new();
+            single.set_market_data("ABC", portfolio.market_data("ABC").unwrap());
+            single.add_position("ABC", instrument_type, strike, 0.5, 1.0, 1.0);
+            let shift = -0.2 * (strike / 100.0).ln();
+            scenario_pnl(&single, &[Scenario::new(0.0, shift, 0.0, 0.0)])[0]
+        };
+        let (put, call) = (
+            parallel(80.0, InstrumentType::Put),
+            parallel(120.0, InstrumentType::Call),
+        );
+        assert!(put > 0.0 && call < 0.0);
+        assert!((pnl[0] - put - call).abs() < 1.0e-5);
+
+        // Rolled past expiry the options pay their intrinsic value at the shocked spot
+        let base = portfolio.net_greeks().pv;
+        let pnl = time_decay(&portfolio, &[1.0]);
+        assert!((pnl[0] + base).abs() < 1.0e-3);
+        let pnl = scenario_pnl(&portfolio, &[Scenario::new(-0.3, 0.0, 0.0, 1.0)]);
+        assert!((pnl[0] - (10.0 - base)).abs() < 1.0e-3);
+        assert_eq!(scenario_pnl(&portfolio, &[]).len(), 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! Bracketed root finding shared by the pricers that need to invert a price
+use crate::bs::OptionDir;
+use crate::bs_single::bs_price;
+
+/// Brent's method on [a, b]. Returns None if the root is not bracketed
+pub(crate) fn brent<F: FnMut(f64) -> f64>(
+    mut f: F,
+    a: f64,
+    b: f64,
+    tolerance: f64,
+    max_iter: usize,
+) -> Option<f64> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a), f(b));
+    if fa * fb > 0.0 {
+        return None;
+    }
+    if fa.abs() < fb.abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut fa, &mut fb);
+    }
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut bisected = true;
+    for _ in 0..max_iter {
+        if fb == 0.0 || (b - a).abs() < tolerance {
+            return Some(b);
+        }
+        let mut s = if fa != fc && fb != fc {
+            // Inverse quadratic interpolation
+            a * fb * fc / ((fa - fb) * (fa - fc))
+                + b * fa * fc / ((fb - fa) * (fb - fc))
+                + c * fa * fb / ((fc - fa) * (fc - fb))
+        } else {
+            // Secant
+            b - fb * (b - a) / (fb - fa)
+        };
+        let m = (3.0 * a + b) / 4.0;
+        if (s - m) * (s - b) > 0.0
+            || (bisected && (s - b).abs() >= (b - c).abs() / 2.0)
+            || (!bisected && (s - b).abs() >= (c - d).abs() / 2.0)
+            || (bisected && (b - c).abs() < tolerance)
+            || (!bisected && (c - d).abs() < tolerance)
+        {
+            s = (a + b) / 2.0;
+            bisected = true;
+        } else {
+            bisected = false;
+        }
+        let fs = f(s);
+        d = c;
+        c = b;
+        fc = fb;
+        if fa * fs < 0.0 {
+            b = s;
+            fb = fs;
+        } else {
+            a = s;
+            fa = fs;
+        }
+        if fa.abs() < fb.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+    }
+    Some(b)
+}
+
+/// Black Scholes implied vol by Brent's method, unlike the Newton iteration in `bs_single` this always terminates.
+/// Returns NaN when the price is outside the no-arbitrage bounds
+pub(crate) fn black_scholes_implied_vol(
+    option_dir: OptionDir,
+    price: f32,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    dividend_yield: f32,
+) -> f32 {
+    brent(
+        |v| {
+            (bs_price(
+                option_dir,
+                spot,
+                strike,
+                years_to_expiry,
+                risk_free_rate,
+                v as f32,
+                dividend_yield,
+            ) - price) as f64
+        },
+        0.0001,
+        5.0,
+        1.0e-7,
+        100,
+    )
+    .map_or(f32::NAN, |v| v as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brent_root() {
+        let root = brent(|x| x * x * x - 2.0 * x - 5.0, 2.0, 3.0, 1.0e-12, 100).unwrap();
+        assert!((root - 2.0945514815423265).abs() < 1.0e-10);
+        assert!(brent(|x| x * x + 1.0, -1.0, 1.0, 1.0e-12, 100).is_none());
+    }
+
+    #[test]
+    fn implied_vol_round_trip() {
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            for i in (60..160).step_by(20) {
+                let strike = i as f32;
+                let price = bs_price(dir, 100.0, strike, 0.5, 0.02, 0.35, 0.01);
+                let v = black_scholes_implied_vol(dir, price, 100.0, strike, 0.5, 0.02, 0.01);
+                assert!((v - 0.35).abs() < 0.0005);
+            }
+        }
+        assert!(
+            black_scholes_implied_vol(OptionDir::CALL, 120.0, 100.0, 100.0, 0.5, 0.02, 0.01)
+                .is_nan()
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Multi-leg option strategies on one underlying: spreads, straddles, condors, calendars and the like
+//!
+//! A strategy is a set of legs, each a signed quantity of a call, a put or the underlying itself, entered at their
+//! Black Scholes values in the market of the strategy. Net greeks come from the batch pricing of the portfolio
+//! module.
+//!
+//! The payoff diagram is the P&L at the horizon, the first expiry of the legs, over a range of spots. Legs expiring
+//! later are valued at the horizon at the same volatility, so calendars have a curved payoff, and the financing of
+//! the premium is ignored. Breakevens, the maximum profit and loss, and the probability of profit are found over the
+//! spots where the underlying could plausibly be at the horizon, with the profit or loss unbounded when the P&L keeps
+//! growing with the spot. The probability of profit is risk neutral under Black Scholes.
+use crate::bs::OptionDir;
+use crate::density::probability_itm;
+use crate::portfolio::{InstrumentType, MarketData, Portfolio, PortfolioGreeks};
+use crate::scenario::{scenario_pnl, Scenario};
+use crate::solver::brent;
+use wasm_bindgen::prelude::*;
+
+/// Name of the underlying in the portfolio a strategy is priced as
+const UNDERLYING: &str = "underlying";
+
+/// Points in the search for breakevens and extremes
+const GRID_POINTS: usize = 1000;
+
+/// One leg of a strategy, with a negative quantity when sold. The strike and expiry of the underlying are ignored
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Leg {
+    pub instrument_type: InstrumentType,
+    pub strike: f32,
+    pub years_to_expiry: f32,
+    pub quantity: f32,
+}
+
+/// Legs on one underlying and its market data
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Strategy {
+    market: MarketData,
+    legs: Vec<Leg>,
+}
+
+impl Strategy {
+    pub fn legs(&self) -> &[Leg] {
+        &self.legs
+    }
+
+    fn portfolio(&self) -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data(UNDERLYING, self.market);
+        for leg in &self.legs {
+            portfolio.add_position(
+                UNDERLYING,
+                leg.instrument_type,
+                leg.strike,
+                leg.years_to_expiry,
+                leg.quantity,
+                1.0,
+            );
+        }
+        portfolio
+    }
+
+    fn options(&self) -> impl Iterator<Item = &Leg> {
+        self.legs
+            .iter()
+            .filter(|leg| leg.instrument_type != InstrumentType::Underlying)
+    }
+
+    /// Ascending spots covering six standard deviations either side of the spot and strikes at the horizon, down
+    /// to near zero, with the strikes themselves
+    fn grid(&self) -> Vec<f32> {
+        let spread = (6.0 * self.market.volatility * self.horizon().max(0.0).sqrt()).exp();
+        let high = self
+            .options()
+            .map(|leg| leg.strike)
+            .fold(self.market.spot, f32::max)
+            * spread.max(3.0);
+        let mut grid: Vec<f32> = (0..=GRID_POINTS)
+            .map(|i| high * (i as f32 / GRID_POINTS as f32).max(1.0e-4))
+            .chain(self.options().map(|leg| leg.strike))
+            .collect();
+        grid.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        grid.dedup();
+        grid
+    }
+
+    /// Change of the P&L at the horizon per unit of spot far above the strikes
+    fn upper_slope(&self) -> f32 {
+        let horizon = self.horizon();
+        self.legs
+            .iter()
+            .map(|leg| match leg.instrument_type {
+                InstrumentType::Call => {
+                    leg.quantity
+                        * (-self.market.dividend_yield * (leg.years_to_expiry - horizon)).exp()
+                }
+                InstrumentType::Underlying => leg.quantity,
+                InstrumentType::Put => 0.0,
+            })
+            .sum()
+    }
+}
+
+#[wasm_bindgen]
+impl Strategy {
+    /// An empty strategy in the market of the underlying
+    #[wasm_bindgen(constructor)]
+    pub fn new(market: MarketData) -> Strategy {
+        Strategy {
+            market,
+            legs: vec![],
+        }
+    }
+
+    /// Add a leg, with a negative quantity when sold
+    /// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+    pub fn add_leg(
+        &mut self,
+        instrument_type: InstrumentType,
+        strike: f32,
+        years_to_expiry: f32,
+        quantity: f32,
+    ) {
+        self.legs.push(Leg {
+            instrument_type,
+            strike,
+            years_to_expiry,
+            quantity,
+        });
+    }
+
+    /// Buy the option at the long strike and sell it at the short strike, a bull or bear spread
+    pub fn vertical_spread(
+        market: MarketData,
+        option_dir: OptionDir,
+        long_strike: f32,
+        short_strike: f32,
+        years_to_expiry: f32,
+    ) -> Strategy {
+        let instrument_type = instrument_type(option_dir);
+        let mut strategy = Strategy::new(market);
+        strategy.add_leg(instrument_type, long_strike, years_to_expiry, 1.0);
+        strategy.add_leg(instrument_type, short_strike, years_to_expiry, -1.0);
+        strategy
+    }
+
+    /// Buy a call and a put at the same strike
+    pub fn straddle(market: MarketData, strike: f32, years_to_expiry: f32) -> Strategy {
+        Strategy::strangle(market, strike, strike, years_to_expiry)
+    }
+
+    /// Buy a put at the lower strike and a call at the higher strike
+    pub fn strangle(
+        market: MarketData,
+        put_strike: f32,
+        call_strike: f32,
+        years_to_expiry: f32,
+    ) -> Strategy {
+        let mut strategy = Strategy::new(market);
+        strategy.add_leg(InstrumentType::Put, put_strike, years_to_expiry, 1.0);
+        strategy.add_leg(InstrumentType::Call, call_strike, years_to_expiry, 1.0);
+        strategy
+    }
+
+    /// Buy the wings and sell two of the middle strike
+    pub fn butterfly(
+        market: MarketData,
+        option_dir: OptionDir,
+        low_strike: f32,
+        middle_strike: f32,
+        high_strike: f32,
+        years_to_expiry: f32,
+    ) -> Strategy {
+        let instrument_type = instrument_type(option_dir);
+        let mut strategy = Strategy::new(market);
+        strategy.add_leg(instrument_type, low_strike, years_to_expiry, 1.0);
+        strategy.add_leg(instrument_type, middle_strike, years_to_expiry, -2.0);
+        strategy.add_leg(instrument_type, high_strike, years_to_expiry, 1.0);
+        strategy
+    }
+
+    /// Sell a put spread and a call spread, buying the outer strikes and selling the inner ones
+    pub fn iron_condor(
+        market: MarketData,
+        long_put_strike: f32,
+        short_put_strike: f32,
+        short_call_strike: f32,
+        long_call_strike: f32,
+        years_to_expiry: f32,
+    ) -> Strategy {
+        let mut strategy = Strategy::new(market);
+        strategy.add_leg(InstrumentType::Put, long_put_strike, years_to_expiry, 1.0);
+        strategy.add_leg(InstrumentType::Put, short_put_strike, years_to_expiry, -1.0);
+        strategy.add_leg(
+            InstrumentType::Call,
+            short_call_strike,
+            years_to_expiry,
+            -1.0,
+        );
+        strategy.add_leg(InstrumentType::Call, long_call_strike, years_to_expiry, 1.0);
+        strategy
+    }
+
+    /// Sell the near expiry and buy the far expiry at the same strike
+    pub fn calendar_spread(
+        market: MarketData,
+        option_dir: OptionDir,
+        strike: f32,
+        near_expiry: f32,
+        far_expiry: f32,
+    ) -> Strategy {
+        let instrument_type = instrument_type(option_dir);
+        let mut strategy = Strategy::new(market);
+        strategy.add_leg(instrument_type, strike, near_expiry, -1.0);
+        strategy.add_leg(instrument_type, strike, far_expiry, 1.0);
+        strategy
+    }
+
+    /// Number of legs
+    pub fn len(&self) -> usize {
+        self.legs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.legs.is_empty()
+    }
+
+    /// Net value and greeks of the legs, the premium paid when positive and received when negative
+    pub fn net_greeks(&self) -> PortfolioGreeks {
+        self.portfolio().net_greeks()
+    }
+
+    /// Years to the first expiry of the option legs, 0.0 without any
+    pub fn horizon(&self) -> f32 {
+        let first = self
+            .options()
+            .map(|leg| leg.years_to_expiry)
+            .fold(f32::INFINITY, f32::min);
+        if first.is_finite() {
+            first.max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// P&L at the horizon at each of the spots
+    pub fn payoff(&self, spots: &[f32]) -> Vec<f32> {
+        let (spot, horizon) = (self.market.spot, self.horizon());
+        let scenarios: Vec<Scenario> = spots
+            .iter()
+            .map(|&s| Scenario::new(s / spot - 1.0, 0.0, 0.0, horizon))
+            .collect();
+        scenario_pnl(&self.portfolio(), &scenarios)
+    }
+
+    /// Ascending spots at the horizon where the P&L crosses zero
+    pub fn breakevens(&self) -> Vec<f32> {
+        let grid = self.grid();
+        let pnl = self.payoff(&grid);
+        let mut breakevens = vec![];
+        for i in 1..grid.len() {
+            if pnl[i] == 0.0 && pnl[i - 1] != 0.0 {
+                breakevens.push(grid[i]);
+            } else if pnl[i - 1] * pnl[i] < 0.0 {
+                let f = |s: f64| self.payoff(&[s as f32])[0] as f64;
+                if let Some(s) = brent(f, grid[i - 1] as f64, grid[i] as f64, 1.0e-5, 100) {
+                    breakevens.push(s as f32);
+                }
+            }
+        }
+        breakevens
+    }
+
+    /// Largest P&L at the horizon, infinite when it grows with the spot
+    pub fn max_profit(&self) -> f32 {
+        if self.upper_slope() > 1.0e-6 {
+            return f32::INFINITY;
+        }
+        self.payoff(&self.grid())
+            .into_iter()
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Largest loss at the horizon as a positive amount, infinite when it grows with the spot
+    pub fn max_loss(&self) -> f32 {
+        if self.upper_slope() < -1.0e-6 {
+            return f32::INFINITY;
+        }
+        -self
+            .payoff(&self.grid())
+            .into_iter()
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Risk neutral probability of a positive P&L at the horizon
+    pub fn probability_of_profit(&self) -> f32 {
+        let m = self.market;
+        let horizon = self.horizon();
+        let below = |s: f32| {
+            if s.is_infinite() {
+                1.0
+            } else if s <= 0.0 {
+                0.0
+            } else {
+                probability_itm(
+                    OptionDir::PUT,
+                    m.spot,
+                    s,
+                    horizon,
+                    m.risk_free_rate,
+                    m.volatility,
+                    m.dividend_yield,
+                )
+            }
+        };
+        let mut bounds = vec![0.0];
+        bounds.extend(self.breakevens());
+        bounds.push(f32::INFINITY);
+        bounds
+            .windows(2)
+            .map(|b| {
+                // A spot inside the interval to find which side of zero it is on
+                let inside = match (b[0] > 0.0, b[1].is_finite()) {
+                    (true, true) => (b[0] * b[1]).sqrt(),
+                    (false, true) => b[1] / 2.0,
+                    (true, false) => b[0] * 2.0,
+                    (false, false) => m.spot,
+                };
+                if self.payoff(&[inside])[0] > 0.0 {
+                    below(b[1]) - below(b[0])
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+}
+
+fn instrument_type(option_dir: OptionDir) -> InstrumentType {
+    match option_dir {
+        OptionDir::CALL => InstrumentType::Call,
+        OptionDir::PUT => InstrumentType::Put,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::{bs_price, vega};
+
+    fn market() -> MarketData {
+        MarketData::new(100.0, 0.03, 0.25, 0.01)
+    }
+
+    #[test]
+    fn straddle() {
+        let (k, t) = (100.0, 0.5);
+        let strategy = Strategy::straddle(market(), k, t);
+        let cost = strategy.net_greeks().pv;
+        let call = bs_price(OptionDir::CALL, 100.0, k, t, 0.03, 0.25, 0.01);
+        let put = bs_price(OptionDir::PUT, 100.0, k, t, 0.03, 0.25, 0.01);
+        assert!((cost - call - put).abs() < 1.0e-3);
+        let straddle_vega = 2.0 * vega(100.0, k, t, 0.03, 0.25, 0.01);
+        assert!((strategy.net_greeks().vega - straddle_vega).abs() < 1.0e-3);
+
+        let payoff = strategy.payoff(&[80.0, 100.0, 130.0]);
+        assert!((payoff[0] - (20.0 - cost)).abs() < 1.0e-3);
+        assert!((payoff[1] + cost).abs() < 1.0e-3);
+        assert!((payoff[2] - (30.0 - cost)).abs() < 1.0e-3);
+
+        let breakevens = strategy.breakevens();
+        assert_eq!(breakevens.len(), 2);
+        assert!((breakevens[0] - (k - cost)).abs() < 1.0e-3);
+        assert!((breakevens[1] - (k + cost)).abs() < 1.0e-3);
+        assert_eq!(strategy.max_profit(), f32::INFINITY);
+        assert!((strategy.max_loss() - cost).abs() < 1.0e-3);
+
+        let (r, v, q) = (0.03, 0.25, 0.01);
+        let expected = probability_itm(OptionDir::PUT, 100.0, k - cost, t, r, v, q)
+            + probability_itm(OptionDir::CALL, 100.0, k + cost, t, r, v, q);
+        assert!((strategy.probability_of_profit() - expected).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn spreads_and_condors() {
+        let bull = Strategy::vertical_spread(market(), OptionDir::CALL, 95.0, 110.0, 0.25);
+        let cost = bull.net_greeks().pv;
+        assert!(cost > 0.0 && cost < 15.0);
+        let breakevens = bull.breakevens();
+        assert_eq!(breakevens.len(), 1);
+        assert!((breakevens[0] - (95.0 + cost)).abs() < 1.0e-3);
+        assert!((bull.max_profit() - (15.0 - cost)).abs() < 1.0e-3);
+        assert!((bull.max_loss() - cost).abs() < 1.0e-3);
+        let itm = probability_itm(OptionDir::CALL, 100.0, 95.0 + cost, 0.25, 0.03, 0.25, 0.01);
+        assert!((bull.probability_of_profit() - itm).abs() < 1.0e-4);
+
+        let condor = Strategy::iron_condor(market(), 80.0, 90.0, 110.0, 120.0, 0.25);
+        let credit = -condor.net_greeks().pv;
+        assert!(credit > 0.0);
+        assert!((condor.max_profit() - credit).abs() < 1.0e-3);
+        assert!((condor.max_loss() - (10.0 - credit)).abs() < 1.0e-3);
+        let breakevens = condor.breakevens();
+        assert_eq!(breakevens.len(), 2);
+        assert!((breakevens[0] - (90.0 - credit)).abs() < 1.0e-3);
+        assert!((breakevens[1] - (110.0 + credit)).abs() < 1.0e-3);
+        let pop = condor.probability_of_profit();
+        assert!(pop > 0.5 && pop < 1.0);
+
+        let fly = Strategy::butterfly(market(), OptionDir::PUT, 90.0, 100.0, 110.0, 0.25);
+        assert_eq!(fly.len(), 3);
+        assert!((fly.max_profit() - (10.0 - fly.net_greeks().pv)).abs() < 1.0e-3);
+        assert!(fly.net_greeks().gamma < 0.0);
+
+        // Selling the underlying against a short put makes the loss unbounded upwards
+        let mut short = Strategy::new(market());
+        short.add_leg(InstrumentType::Underlying, 0.0, 0.0, -1.0);
+        short.add_leg(InstrumentType::Put, 100.0, 0.25, -1.0);
+        assert_eq!(short.max_loss(), f32::INFINITY);
+        assert_eq!(short.horizon(), 0.25);
+    }
+
+    #[test]
+    fn calendar() {
+        // Without dividends the far call is worth S - K e^-rt far above the strike, so the loss is bounded
+        let no_dividends = MarketData::new(100.0, 0.03, 0.25, 0.0);
+        let calendar = Strategy::calendar_spread(no_dividends, OptionDir::CALL, 100.0, 0.1, 0.5);
+        assert_eq!(calendar.horizon(), 0.1);
+        assert!(calendar.net_greeks().pv > 0.0);
+        assert!(calendar.net_greeks().theta > 0.0);
+        // The far call is worth most against the expiring one at the strike
+        let payoff = calendar.payoff(&[80.0, 100.0, 120.0]);
+        assert!(payoff[1] > payoff[0] && payoff[1] > payoff[2]);
+        assert!((calendar.max_profit() - payoff[1]).abs() < 0.05);
+        assert!(calendar.max_loss() < calendar.net_greeks().pv + 1.0e-3);
+        assert_eq!(calendar.breakevens().len(), 2);
+        assert!(Strategy::new(market()).is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+//! Kamrad-Ritchken trinomial tree
+//!
+//! The log spot moves by dx = lambda sigma sqrt(dt) each step, lambda = 1 is Boyle's original tree without a middle
+//! branch and larger values put more weight on the middle. Rates and dividend yields may follow piecewise flat term
+//! structures, which only change the branch probabilities and discounting so the lattice itself stays recombining.
+//! Barriers are placed exactly on a layer of nodes by stretching lambda, which removes most of the sawtooth
+//! convergence of barrier options on lattices. Knock-ins step a vanilla lattice alongside and take its value at the
+//! nodes on or beyond the barrier.
+//!
+//! Delta, gamma and theta are read from the nodes of the first two steps rather than by repricing.
+use crate::bs::{Greeks, OptionDir};
+use crate::pde::Barrier;
+
+/// Piecewise flat term structure of continuously compounded rates or yields.
+/// `rates[i]` applies up to `times[i]`, the last rate applies beyond the last time
+#[derive(PartialEq, Debug, Clone)]
+pub struct TermStructure {
+    pub times: Vec<f32>,
+    pub rates: Vec<f32>,
+}
+
+impl TermStructure {
+    pub fn flat(rate: f32) -> TermStructure {
+        TermStructure {
+            times: vec![],
+            rates: vec![rate],
+        }
+    }
+
+    /// Average rate between t0 and t1
+    pub fn forward(&self, t0: f64, t1: f64) -> f64 {
+        let mut integral = 0.0;
+        let mut start = t0;
+        for (i, &rate) in self.rates.iter().enumerate() {
+            let end = self.times.get(i).map_or(t1, |&t| (t as f64).min(t1));
+            if end > start {
+                integral += rate as f64 * (end - start);
+                start = end;
+            }
+            if start >= t1 {
+                break;
+            }
+        }
+        integral / (t1 - t0)
+    }
+}
+
+/// Exercise rights of the option
+#[derive(PartialEq, Debug, Clone)]
+pub enum TreeExercise {
+    European,
+    American,
+    /// Exercise allowed at these times, rounded to the nearest step, as well as at expiry
+    Bermudan(Vec<f32>),
+}
+
+/// Lattice settings
+#[derive(Debug, Copy, Clone)]
+pub struct TreeParams {
+    pub steps: usize,
+    /// Stretch of the log spot step, at least 1. sqrt(3 / 2) gives roughly equal branch probabilities
+    pub lambda: f32,
+}
+
+/// Price and greeks read off the lattice, theta is per year
+#[derive(Debug, Copy, Clone)]
+pub struct TreeResult {
+    pub pv: f32,
+    pub delta: f32,
+    pub gamma: f32,
+    pub theta: f32,
+}
+
+/// Trinomial tree price of a European, American or Bermudan option, optionally with a barrier,
+/// under term structures of rates and dividend yields
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+#[allow(clippy::too_many_arguments)]
+pub fn trinomial(
+    option_dir: OptionDir,
+    exercise: &TreeExercise,
+    barrier: Option<Barrier>,
+    spot: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: &TermStructure,
+    volatility: f32,
+    dividend_yield: &TermStructure,
+    params: &TreeParams,
+) -> TreeResult {
+    let n = params.steps.max(2);
+    let (s0, k, sigma) = (spot as f64, strike as f64, volatility as f64);
+    let dt = years_to_expiry as f64 / n as f64;
+    let sd = sigma * dt.sqrt();
+    let mut lambda = (params.lambda as f64).max(1.0);
+
+    // Stretch lambda so the barrier falls exactly on a layer of nodes
+    if let Some(b) = barrier {
+        let distance = (b.level as f64 / s0).ln().abs();
+        let layers = (distance / (lambda * sd)).floor();
+        if layers >= 1.0 {
+            lambda = distance / (layers * sd);
+        }
+    }
+    let dx = lambda * sd;
+    let barrier_layer = barrier.map(|b| ((b.level as f64 / s0).ln() / dx).round() as i64);
+    // Nodes on or beyond the barrier are knocked out or in
+    let knocked = |j: i64| match (barrier, barrier_layer) {
+        (Some(b), Some(layer)) if b.up() => j >= layer,
+        (Some(_), Some(layer)) => j <= layer,
+        _ => false,
+    };
+    let knock_in = barrier.is_some_and(|b| b.knock_in());
+    if knocked(0) && knock_in {
+        return trinomial(
+            option_dir,
+            exercise,
+            None,
+            spot,
+            strike,
+            years_to_expiry,
+            risk_free_rate,
+            volatility,
+            dividend_yield,
+            params,
+        );
+    }
+    if knocked(0) {
+        return TreeResult {
+            pv: barrier.map_or(0.0, |b| b.rebate),
+            delta: 0.0,
+            gamma: 0.0,
+            theta: 0.0,
+        };
+    }
+    let rebate = barrier.map_or(0.0, |b| b.rebate as f64);
+
+    let exercise_step: Vec<bool> = match exercise {
+        TreeExercise::European => vec![false; n + 1],
+        TreeExercise::American => vec![true; n + 1],
+        TreeExercise::Bermudan(times) => {
+            let mut steps = vec![false; n + 1];
+            for &t in times.iter() {
+                let i = (t as f64 / dt).round() as usize;
+                if i <= n {
+                    steps[i] = true;
+                }
+            }
+            steps
+        }
+    };
+    let payoff = |j: i64| {
+        let s = s0 * (j as f64 * dx).exp();
+        match option_dir {
+            OptionDir::CALL => (s - k).max(0.0),
+            OptionDir::PUT => (k - s).max(0.0),
+        }
+    };
+
+    let n = n as i64;
+    // Before a knock-in is hit it pays the rebate at expiry and cannot be exercised
+    let mut vanilla: Vec<f64> = if knock_in {
+        (-n..=n).map(payoff).collect()
+    } else {
+        vec![]
+    };
+    let mut v: Vec<f64> = (-n..=n)
+        .map(|j| match (knocked(j), knock_in) {
+            (true, true) | (false, false) => payoff(j),
+            _ => rebate,
+        })
+        .collect();
+    let mut step_one = [0.0; 3];
+    let mut step_two_middle = 0.0;
+    for i in (0..n).rev() {
+        let (t0, t1) = (i as f64 * dt, (i + 1) as f64 * dt);
+        let r = risk_free_rate.forward(t0, t1);
+        let q = dividend_yield.forward(t0, t1);
+        let nu = r - q - sigma * sigma / 2.0;
+        let pm = 1.0 - 1.0 / (lambda * lambda);
+        let pu = 1.0 / (2.0 * lambda * lambda) + nu * dt.sqrt() / (2.0 * lambda * sigma);
+        let pd = 1.0 - pm - pu;
+        let discount = (-r * dt).exp();
+        // Node j at step i is index j + i, its children at step i + 1 are at indices j + i .. j + i + 2
+        let continuation =
+            |v: &[f64], idx: usize| discount * (pd * v[idx] + pm * v[idx + 1] + pu * v[idx + 2]);
+        let exercise = |value: f64, j: i64| {
+            if exercise_step[i as usize] {
+                value.max(payoff(j))
+            } else {
+                value
+            }
+        };
+        for (idx, j) in (-i..=i).enumerate() {
+            if knock_in {
+                vanilla[idx] = exercise(continuation(&vanilla, idx), j);
+                v[idx] = if knocked(j) {
+                    vanilla[idx]
+                } else {
+                    continuation(&v, idx)
+                };
+            } else {
+                v[idx] = if knocked(j) {
+                    rebate
+                } else {
+                    exercise(continuation(&v, idx), j)
+                };
+            }
+        }
+        if i == 2 {
+            step_two_middle = v[2];
+        }
+        if i == 1 {
+            step_one = [v[0], v[1], v[2]];
+        }
+    }
+
+    let (s_down, s_up) = (s0 * (-dx).exp(), s0 * dx.exp());
+    let [v_down, v_middle, v_up] = step_one;
+    let delta = (v_up - v_down) / (s_up - s_down);
+    let gamma = ((v_up - v_middle) / (s_up - s0) - (v_middle - v_down) / (s0 - s_down))
+        / ((s_up - s_down) / 2.0);
+    // The middle node two steps in has the same spot as today
+    let theta = (step_two_middle - v[0]) / (2.0 * dt);
+    TreeResult {
+        pv: v[0] as f32,
+        delta: delta as f32,
+        gamma: gamma as f32,
+        theta: theta as f32,
+    }
+}
+
+/// Trinomial price and greeks for arrays of options with flat rates and dividend yields.
+/// The results are at the same index as the inputs. Delta, gamma and theta come from the lattice,
+/// vega and rho, which a single tree cannot give, by repricing with 1% bumps
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatility and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[allow(clippy::too_many_arguments)]
+pub fn trinomial_greeks(
+    option_dir: OptionDir,
+    exercise: &TreeExercise,
+    spot: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility: &[f32],
+    dividend_yield: &[f32],
+    params: &TreeParams,
+) -> Greeks {
+    const BUMP: f32 = 0.01;
+    let mut greeks = Greeks {
+        pv: Vec::with_capacity(spot.len()),
+        delta: Vec::with_capacity(spot.len()),
+        theta: Vec::with_capacity(spot.len()),
+        gamma: Vec::with_capacity(spot.len()),
+        rho: Vec::with_capacity(spot.len()),
+        vega: Vec::with_capacity(spot.len()),
+    };
+    for i in 0..spot.len() {
+        let price = |r: f32, v: f32| {
+            trinomial(
+                option_dir,
+                exercise,
+                None,
+                spot[i],
+                strike[i],
+                years_to_expiry[i],
+                &TermStructure::flat(r),
+                v,
+                &TermStructure::flat(dividend_yield[i]),
+                params,
+            )
+        };
+        let (r, v) = (risk_free_rate[i], volatility[i]);
+        let result = price(r, v);
+        greeks.pv.push(result.pv);
+        greeks.delta.push(result.delta);
+        greeks.gamma.push(result.gamma);
+        greeks.theta.push(result.theta);
+        greeks
+            .rho
+            .push((price(r + BUMP, v).pv - price(r - BUMP, v).pv) / (2.0 * BUMP));
+        greeks
+            .vega
+            .push((price(r, v + BUMP).pv - price(r, v - BUMP).pv) / (2.0 * BUMP));
+    }
+    greeks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::{american_binomial, bs_price, delta};
+    use crate::pde::{pde_price, BarrierType, EarlyExercise, Exercise, PdeParams};
+
+    const PARAMS: TreeParams = TreeParams {
+        steps: 500,
+        lambda: 1.224_745,
+    };
+
+    fn flat(rate: f32) -> TermStructure {
+        TermStructure::flat(rate)
+    }
+
+    #[test]
+    fn european_with_lattice_greeks() {
+        for &dir in [OptionDir::CALL, OptionDir::PUT].iter() {
+            for &strike in [90.0, 100.0, 110.0].iter() {
+                let tree = trinomial(
+                    dir,
+                    &TreeExercise::European,
+                    None,
+                    100.0,
+                    strike,
+                    1.0,
+                    &flat(0.05),
+                    0.25,
+                    &flat(0.02),
+                    &PARAMS,
+                );
+                let bs = bs_price(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02);
+                assert!((tree.pv - bs).abs() < 0.01);
+                let d = delta(dir, 100.0, strike, 1.0, 0.05, 0.25, 0.02);
+                assert!((tree.delta - d).abs() < 0.002);
+                let d1 = ((100.0f32 / strike).ln() + 0.03 + 0.03125) / 0.25;
+                let gamma = (-0.02f32).exp() * (-d1 * d1 / 2.0).exp()
+                    / (2.0 * std::f32::consts::PI).sqrt()
+                    / (100.0 * 0.25);
+                assert!((tree.gamma - gamma).abs() < 0.0005);
+                // Theta from the Black Scholes PDE
+                let theta = 0.05 * bs - 0.03 * 100.0 * d - 0.5 * 0.0625 * 100.0 * 100.0 * gamma;
+                assert!((tree.theta - theta).abs() < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn american_and_bermudan_put() {
+        let price = |exercise: &TreeExercise| {
+            trinomial(
+                OptionDir::PUT,
+                exercise,
+                None,
+                36.0,
+                40.0,
+                1.0,
+                &flat(0.06),
+                0.2,
+                &flat(0.0),
+                &PARAMS,
+            )
+            .pv
+        };
+        let american = price(&TreeExercise::American);
+        let binomial = american_binomial(OptionDir::PUT, 36.0, 40.0, 1.0, 0.06, 0.2, 0.0, 2000);
+        assert!((american - 4.4867).abs() < 0.005);
+        assert!((american - binomial).abs() < 0.01);
+        let european = price(&TreeExercise::European);
+        let quarterly = price(&TreeExercise::Bermudan(vec![0.25, 0.5, 0.75]));
+        let monthly = price(&TreeExercise::Bermudan(
+            (1..12).map(|m| m as f32 / 12.0).collect(),
+        ));
+        assert!(european < quarterly && quarterly < monthly && monthly < american);
+        // Exercise at expiry only is European
+        assert!((price(&TreeExercise::Bermudan(vec![1.0])) - european).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn term_structure() {
+        let rates = TermStructure {
+            times: vec![0.25, 0.5],
+            rates: vec![0.01, 0.03, 0.06],
+        };
+        assert!((rates.forward(0.0, 1.0) - 0.04).abs() < 1.0e-7);
+        assert!((rates.forward(0.2, 0.3) - 0.02).abs() < 1.0e-7);
+        // A European option only depends on the average rate and yield
+        let dividends = TermStructure {
+            times: vec![0.5],
+            rates: vec![0.0, 0.04],
+        };
+        let tree = trinomial(
+            OptionDir::CALL,
+            &TreeExercise::European,
+            None,
+            100.0,
+            100.0,
+            1.0,
+            &rates,
+            0.2,
+            &dividends,
+            &PARAMS,
+        );
+        let bs = bs_price(OptionDir::CALL, 100.0, 100.0, 1.0, 0.04, 0.2, 0.02);
+        assert!((tree.pv - bs).abs() < 0.01);
+    }
+
+    #[test]
+    fn down_and_out_call() {
+        // C_do = C(S) - (H / S)^(2 lambda - 2) C(H^2 / S), lambda = (r - q + sigma^2 / 2) / sigma^2
+        let (spot, strike, h, t, r, v, q) = (100.0f32, 100.0, 95.0, 0.5, 0.05, 0.25, 0.01);
+        let lambda = (r - q + v * v / 2.0) / (v * v);
+        let closed_form = bs_price(OptionDir::CALL, spot, strike, t, r, v, q)
+            - (h / spot).powf(2.0 * lambda - 2.0)
+                * bs_price(OptionDir::CALL, h * h / spot, strike, t, r, v, q);
+        let barrier = Barrier {
+            barrier_type: BarrierType::DownAndOut,
+            level: h,
+            rebate: 0.0,
+        };
+        let tree = trinomial(
+            OptionDir::CALL,
+            &TreeExercise::European,
+            Some(barrier),
+            spot,
+            strike,
+            t,
+            &flat(r),
+            v,
+            &flat(q),
+            &PARAMS,
+        );
+        assert!((tree.pv - closed_form).abs() < 0.02);
+    }
+
+    #[test]
+    fn knock_ins() {
+        use crate::barrier::{barrier_price, BarrierKind};
+        let price = |exercise: &TreeExercise, barrier_type| {
+            trinomial(
+                OptionDir::PUT,
+                exercise,
+                Some(Barrier {
+                    barrier_type,
+                    level: 90.0,
+                    rebate: 0.0,
+                }),
+                100.0,
+                100.0,
+                1.0,
+                &flat(0.08),
+                0.25,
+                &flat(0.0),
+                &PARAMS,
+            )
+            .pv
+        };
+        let closed_form = barrier_price(
+            OptionDir::PUT,
+            BarrierKind::DownAndIn,
+            100.0,
+            100.0,
+            90.0,
+            0.0,
+            1.0,
+            0.08,
+            0.25,
+            0.0,
+            0.0,
+        );
+        let european = price(&TreeExercise::European, BarrierType::DownAndIn);
+        assert!((european - closed_form).abs() < 0.02);
+        let american = price(&TreeExercise::American, BarrierType::DownAndIn);
+        assert!(american > european + 0.05);
+        let pde = pde_price(
+            OptionDir::PUT,
+            Exercise::American,
+            Some(Barrier {
+                barrier_type: BarrierType::DownAndIn,
+                level: 90.0,
+                rebate: 0.0,
+            }),
+            100.0,
+            100.0,
+            1.0,
+            0.08,
+            0.25,
+            0.0,
+            &PdeParams {
+                space_steps: 400,
+                time_steps: 200,
+                rannacher_steps: 2,
+                concentration: 0.1,
+                early_exercise: EarlyExercise::BrennanSchwartz,
+            },
+        )
+        .pv;
+        assert!((american - pde).abs() < 0.02);
+    }
+
+    #[test]
+    fn batch_greeks() {
+        let spot = [90.0, 100.0, 110.0];
+        let greeks = trinomial_greeks(
+            OptionDir::CALL,
+            &TreeExercise::European,
+            &spot,
+            &[100.0; 3],
+            &[0.5; 3],
+            &[0.03; 3],
+            &[0.2; 3],
+            &[0.0; 3],
+            &TreeParams {
+                steps: 200,
+                ..PARAMS
+            },
+        );
+        assert_eq!(greeks.pv.len(), 3);
+        for (i, &s) in spot.iter().enumerate() {
+            assert!(
+                (greeks.pv[i] - bs_price(OptionDir::CALL, s, 100.0, 0.5, 0.03, 0.2, 0.0)).abs()
+                    < 0.02
+            );
+            // Vega and rho against bumped closed form prices
+            let vega = (bs_price(OptionDir::CALL, s, 100.0, 0.5, 0.03, 0.21, 0.0)
+                - bs_price(OptionDir::CALL, s, 100.0, 0.5, 0.03, 0.19, 0.0))
+                / 0.02;
+            let rho = (bs_price(OptionDir::CALL, s, 100.0, 0.5, 0.04, 0.2, 0.0)
+                - bs_price(OptionDir::CALL, s, 100.0, 0.5, 0.02, 0.2, 0.0))
+                / 0.02;
+            assert!((greeks.vega[i] - vega).abs() < 0.5);
+            assert!((greeks.rho[i] - rho).abs() < 0.5);
+        }
+    }
+}
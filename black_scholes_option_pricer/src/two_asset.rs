@@ -0,0 +1,629 @@
+//! Options on two correlated assets
+//!
+//! * Margrabe (1978) exchange option, the right to swap the second asset for the first
+//! * Spread options paying the first asset less the second less the strike, after Kirk (1995), who treats the
+//!   second asset plus the strike as lognormal, or Bjerksund and Stensland (2011), whose three term formula is a
+//!   lower bound and usually closer to the exact value. Both need the forward of the second asset plus the strike
+//!   to be positive, below that the call is priced at its discounted forward intrinsic value, a lower bound
+//! * Stulz (1982) calls and puts on the minimum or the maximum of the two assets, using the bivariate normal
+//!
+//! The scalar versions are computed in f64. Exchange and spread options also come in batch versions computed eight
+//! at a time in f32x8, the Stulz formula needs correlations of the bivariate normal beyond the range of the f32x8
+//! kernel and stays scalar.
+use crate::bs::OptionDir;
+use crate::bs_f32x8_::ncd_f32x8;
+use crate::normal::{bivariate_ncd, ncd_f64};
+use bytemuck::cast;
+use wasm_bindgen::prelude::*;
+use wide::*;
+
+/// Which spread option approximation to use
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum SpreadApproximation {
+    Kirk,
+    BjerksundStensland,
+}
+
+/// Whether a Stulz option is on the better or the worse of the two assets
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Rainbow {
+    Min,
+    Max,
+}
+
+/// Volatility of the ratio of the two assets
+fn ratio_vol(v1: f64, v2: f64, rho: f64) -> f64 {
+    (v1 * v1 + v2 * v2 - 2.0 * rho * v1 * v2).sqrt()
+}
+
+/// Margrabe on forwards, discounted by `discount`
+fn margrabe(f1: f64, f2: f64, t: f64, v: f64, discount: f64) -> f64 {
+    let sd = v * t.sqrt();
+    let d1 = ((f1 / f2).ln() + sd * sd / 2.0) / sd;
+    discount * (f1 * ncd_f64(d1) - f2 * ncd_f64(d1 - sd))
+}
+
+/// Exchange option price, receiving the first asset and giving up the second at expiry
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn margrabe_price(
+    spot1: f32,
+    spot2: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility1: f32,
+    volatility2: f32,
+    correlation: f32,
+    dividend_yield1: f32,
+    dividend_yield2: f32,
+) -> f32 {
+    let (t, r) = (years_to_expiry as f64, risk_free_rate as f64);
+    let f1 = spot1 as f64 * ((r - dividend_yield1 as f64) * t).exp();
+    let f2 = spot2 as f64 * ((r - dividend_yield2 as f64) * t).exp();
+    let v = ratio_vol(volatility1 as f64, volatility2 as f64, correlation as f64);
+    margrabe(f1, f2, t, v, (-r * t).exp()) as f32
+}
+
+/// Spread option price, paying the first asset less the second less the strike for a call
+/// When the forward of the second asset plus the strike is not positive the call is priced at its discounted forward
+/// intrinsic value and the put at 0
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn spread_price(
+    approximation: SpreadApproximation,
+    option_dir: OptionDir,
+    spot1: f32,
+    spot2: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility1: f32,
+    volatility2: f32,
+    correlation: f32,
+    dividend_yield1: f32,
+    dividend_yield2: f32,
+) -> f32 {
+    let (k, t, r) = (strike as f64, years_to_expiry as f64, risk_free_rate as f64);
+    let (v1, v2, rho) = (volatility1 as f64, volatility2 as f64, correlation as f64);
+    let f1 = spot1 as f64 * ((r - dividend_yield1 as f64) * t).exp();
+    let f2 = spot2 as f64 * ((r - dividend_yield2 as f64) * t).exp();
+    let discount = (-r * t).exp();
+    // Both approximations take the second leg plus the strike as a single lognormal asset
+    let a = f2 + k;
+    let w = f2 / a;
+    let v = (v1 * v1 - 2.0 * w * rho * v1 * v2 + w * w * v2 * v2).sqrt();
+    let call = match approximation {
+        _ if a <= 0.0 => discount * (f1 - a),
+        SpreadApproximation::Kirk => margrabe(f1, a, t, v, discount),
+        SpreadApproximation::BjerksundStensland => {
+            let sd = v * t.sqrt();
+            let m = (f1 / a).ln();
+            let d1 = (m + (v1 * v1 / 2.0 - w * rho * v1 * v2 + w * w * v2 * v2 / 2.0) * t) / sd;
+            let d2 = (m + (-v1 * v1 / 2.0 + rho * v1 * v2 + (w * w / 2.0 - w) * v2 * v2) * t) / sd;
+            let d3 = (m + (-v1 * v1 / 2.0 + w * w * v2 * v2 / 2.0) * t) / sd;
+            discount * (f1 * ncd_f64(d1) - f2 * ncd_f64(d2) - k * ncd_f64(d3))
+        }
+    };
+    let price = match option_dir {
+        OptionDir::CALL => call,
+        OptionDir::PUT => call - discount * (f1 - f2 - k),
+    };
+    price as f32
+}
+
+/// Stulz call or put on the minimum or maximum of two assets
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn stulz_price(
+    option_dir: OptionDir,
+    rainbow: Rainbow,
+    spot1: f32,
+    spot2: f32,
+    strike: f32,
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatility1: f32,
+    volatility2: f32,
+    correlation: f32,
+    dividend_yield1: f32,
+    dividend_yield2: f32,
+) -> f32 {
+    let (k, t, r) = (strike as f64, years_to_expiry as f64, risk_free_rate as f64);
+    let (v1, v2, rho) = (volatility1 as f64, volatility2 as f64, correlation as f64);
+    let (s1, s2) = (spot1 as f64, spot2 as f64);
+    let (b1, b2) = (r - dividend_yield1 as f64, r - dividend_yield2 as f64);
+    // Discounted forwards
+    let (f1, f2) = (s1 * ((b1 - r) * t).exp(), s2 * ((b2 - r) * t).exp());
+    let discount = (-r * t).exp();
+    let v = ratio_vol(v1, v2, rho);
+    let (sd, sd1, sd2) = (v * t.sqrt(), v1 * t.sqrt(), v2 * t.sqrt());
+    let d = ((s1 / s2).ln() + (b1 - b2 + v * v / 2.0) * t) / sd;
+    let (rho1, rho2) = ((v1 - rho * v2) / v, (v2 - rho * v1) / v);
+    // Calls on the minimum and maximum, and the forward values of the minimum and maximum themselves
+    let call = |k: f64| {
+        let y1 = ((s1 / k).ln() + (b1 + v1 * v1 / 2.0) * t) / sd1;
+        let y2 = ((s2 / k).ln() + (b2 + v2 * v2 / 2.0) * t) / sd2;
+        match rainbow {
+            Rainbow::Min => {
+                f1 * bivariate_ncd(y1, -d, -rho1) + f2 * bivariate_ncd(y2, d - sd, -rho2)
+                    - k * discount * bivariate_ncd(y1 - sd1, y2 - sd2, rho)
+            }
+            Rainbow::Max => {
+                f1 * bivariate_ncd(y1, d, rho1) + f2 * bivariate_ncd(y2, -d + sd, rho2)
+                    - k * discount * (1.0 - bivariate_ncd(-y1 + sd1, -y2 + sd2, rho))
+            }
+        }
+    };
+    let extreme = match rainbow {
+        Rainbow::Min => f1 - f1 * ncd_f64(d) + f2 * ncd_f64(d - sd),
+        Rainbow::Max => f2 + f1 * ncd_f64(d) - f2 * ncd_f64(d - sd),
+    };
+    let price = match option_dir {
+        OptionDir::CALL => call(k),
+        OptionDir::PUT => k * discount - extreme + call(k),
+    };
+    price as f32
+}
+
+/// Spread call eight at a time, the exchange option being the spread with no strike
+#[allow(clippy::too_many_arguments)]
+fn spread_call_f32x8(
+    approximation: SpreadApproximation,
+    f1: f32x8,
+    f2: f32x8,
+    k: f32x8,
+    t: f32x8,
+    v1: f32x8,
+    v2: f32x8,
+    rho: f32x8,
+    discount: f32x8,
+) -> f32x8 {
+    let a = f2 + k;
+    let w = f2 / a;
+    let v = (v1 * v1 - 2.0 * w * rho * v1 * v2 + w * w * v2 * v2).sqrt();
+    let sd = v * t.sqrt();
+    let m = (f1 / a).ln();
+    let call = match approximation {
+        SpreadApproximation::Kirk => {
+            let d1 = (m + sd * sd * 0.5) / sd;
+            discount * (f1 * ncd_f32x8(d1) - a * ncd_f32x8(d1 - sd))
+        }
+        SpreadApproximation::BjerksundStensland => {
+            let d1 = (m + (v1 * v1 * 0.5 - w * rho * v1 * v2 + w * w * v2 * v2 * 0.5) * t) / sd;
+            let d2 = (m + (-v1 * v1 * 0.5 + rho * v1 * v2 + (w * w * 0.5 - w) * v2 * v2) * t) / sd;
+            let d3 = (m + (-v1 * v1 * 0.5 + w * w * v2 * v2 * 0.5) * t) / sd;
+            discount * (f1 * ncd_f32x8(d1) - f2 * ncd_f32x8(d2) - k * ncd_f32x8(d3))
+        }
+    };
+    // The forward intrinsic value where the second leg plus the strike is not positive, as in the scalar version
+    a.cmp_le(f32x8::ZERO).blend(discount * (f1 - a), call)
+}
+
+/// Spread prices of arrays eight at a time, `strike` is None for exchange options
+#[allow(clippy::too_many_arguments)]
+fn batch_price(
+    approximation: SpreadApproximation,
+    option_dir: OptionDir,
+    spot1: &[f32],
+    spot2: &[f32],
+    strike: Option<&[f32]>,
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility1: &[f32],
+    volatility2: &[f32],
+    correlation: &[f32],
+    dividend_yield1: &[f32],
+    dividend_yield2: &[f32],
+) -> Vec<f32> {
+    let max_idx = spot1.len();
+    let mut res = Vec::with_capacity(max_idx + 8);
+    for i in (0..max_idx).step_by(8) {
+        let spot1 = f32x8::from(&spot1[i..std::cmp::min(max_idx, i + 8)]);
+        let spot2 = f32x8::from(&spot2[i..std::cmp::min(max_idx, i + 8)]);
+        let strike = strike.map_or(f32x8::ZERO, |k| {
+            f32x8::from(&k[i..std::cmp::min(max_idx, i + 8)])
+        });
+        let t = f32x8::from(&years_to_expiry[i..std::cmp::min(max_idx, i + 8)]);
+        let r = f32x8::from(&risk_free_rate[i..std::cmp::min(max_idx, i + 8)]);
+        let v1 = f32x8::from(&volatility1[i..std::cmp::min(max_idx, i + 8)]);
+        let v2 = f32x8::from(&volatility2[i..std::cmp::min(max_idx, i + 8)]);
+        let rho = f32x8::from(&correlation[i..std::cmp::min(max_idx, i + 8)]);
+        let q1 = f32x8::from(&dividend_yield1[i..std::cmp::min(max_idx, i + 8)]);
+        let q2 = f32x8::from(&dividend_yield2[i..std::cmp::min(max_idx, i + 8)]);
+        let f1 = spot1 * ((r - q1) * t).exp();
+        let f2 = spot2 * ((r - q2) * t).exp();
+        let discount = (-r * t).exp();
+        let call = spread_call_f32x8(approximation, f1, f2, strike, t, v1, v2, rho, discount);
+        let price: [f32; 8] = cast(match option_dir {
+            OptionDir::CALL => call,
+            OptionDir::PUT => call - discount * (f1 - f2 - strike),
+        });
+        res.extend(&price);
+    }
+    res.truncate(max_idx);
+    res
+}
+
+/// Exchange option pricing, receiving the first asset and giving up the second. The results are at the same index
+/// as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn margrabe_exchange(
+    spot1: &[f32],
+    spot2: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility1: &[f32],
+    volatility2: &[f32],
+    correlation: &[f32],
+    dividend_yield1: &[f32],
+    dividend_yield2: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        SpreadApproximation::Kirk,
+        OptionDir::CALL,
+        spot1,
+        spot2,
+        None,
+        years_to_expiry,
+        risk_free_rate,
+        volatility1,
+        volatility2,
+        correlation,
+        dividend_yield1,
+        dividend_yield2,
+    )
+}
+
+/// Spread call pricing, paying the first asset less the second less the strike. The results are at the same index
+/// as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn spread_call(
+    approximation: SpreadApproximation,
+    spot1: &[f32],
+    spot2: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility1: &[f32],
+    volatility2: &[f32],
+    correlation: &[f32],
+    dividend_yield1: &[f32],
+    dividend_yield2: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        approximation,
+        OptionDir::CALL,
+        spot1,
+        spot2,
+        Some(strike),
+        years_to_expiry,
+        risk_free_rate,
+        volatility1,
+        volatility2,
+        correlation,
+        dividend_yield1,
+        dividend_yield2,
+    )
+}
+
+/// Spread put pricing, paying the strike plus the second asset less the first. The results are at the same index
+/// as the inputs
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yields expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn spread_put(
+    approximation: SpreadApproximation,
+    spot1: &[f32],
+    spot2: &[f32],
+    strike: &[f32],
+    years_to_expiry: &[f32],
+    risk_free_rate: &[f32],
+    volatility1: &[f32],
+    volatility2: &[f32],
+    correlation: &[f32],
+    dividend_yield1: &[f32],
+    dividend_yield2: &[f32],
+) -> Vec<f32> {
+    batch_price(
+        approximation,
+        OptionDir::PUT,
+        spot1,
+        spot2,
+        Some(strike),
+        years_to_expiry,
+        risk_free_rate,
+        volatility1,
+        volatility2,
+        correlation,
+        dividend_yield1,
+        dividend_yield2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs_single::bs_price;
+    use crate::monte_carlo::Rng;
+    use crate::normal::inverse_ncd;
+
+    /// Terminal spots of the two assets by exact simulation, with antithetic pairs
+    fn terminal_spots(n: usize, t: f64, r: f64, v1: f64, v2: f64, rho: f64) -> Vec<(f64, f64)> {
+        let mut rng = Rng::new(3);
+        let mut spots = Vec::with_capacity(2 * n);
+        for _ in 0..n {
+            let z1 = inverse_ncd(rng.uniform());
+            let z2 = rho * z1 + (1.0 - rho * rho).sqrt() * inverse_ncd(rng.uniform());
+            for sign in [1.0, -1.0] {
+                let s1 = 100.0 * ((r - v1 * v1 / 2.0) * t + sign * v1 * t.sqrt() * z1).exp();
+                let s2 = 90.0 * ((r - 0.03 - v2 * v2 / 2.0) * t + sign * v2 * t.sqrt() * z2).exp();
+                spots.push((s1, s2));
+            }
+        }
+        spots
+    }
+
+    #[test]
+    fn exchange_and_spread() {
+        // Margrabe is the spread with no strike, for both approximations
+        let margrabe = margrabe_price(100.0, 90.0, 1.0, 0.05, 0.3, 0.25, 0.6, 0.0, 0.03);
+        for approximation in [
+            SpreadApproximation::Kirk,
+            SpreadApproximation::BjerksundStensland,
+        ] {
+            let spread = spread_price(
+                approximation,
+                OptionDir::CALL,
+                100.0,
+                90.0,
+                0.0,
+                1.0,
+                0.05,
+                0.3,
+                0.25,
+                0.6,
+                0.0,
+                0.03,
+            );
+            assert!((margrabe - spread).abs() < 1.0e-4);
+        }
+        // Exchanging an asset for nothing volatile is a vanilla call
+        let call = bs_price(OptionDir::CALL, 100.0, 90.0, 1.0, 0.05, 0.3, 0.0);
+        let margrabe = margrabe_price(100.0, 90.0, 1.0, 0.05, 0.3, 0.0, 0.0, 0.0, 0.05);
+        assert!((margrabe - call).abs() < 2.0e-3);
+
+        let (t, r, v1, v2, rho) = (1.0, 0.05, 0.3, 0.25, 0.6);
+        let spots = terminal_spots(100_000, t, r, v1, v2, rho);
+        for strike in [0.0, 5.0, 15.0] {
+            let payoffs: Vec<f64> = spots
+                .iter()
+                .map(|(s1, s2)| (s1 - s2 - strike).max(0.0))
+                .collect();
+            let mc = (-r * t).exp() * payoffs.iter().sum::<f64>() / payoffs.len() as f64;
+            let price = |approximation, dir| {
+                spread_price(
+                    approximation,
+                    dir,
+                    100.0,
+                    90.0,
+                    strike as f32,
+                    1.0,
+                    0.05,
+                    0.3,
+                    0.25,
+                    0.6,
+                    0.0,
+                    0.03,
+                ) as f64
+            };
+            let kirk = price(SpreadApproximation::Kirk, OptionDir::CALL);
+            let bs = price(SpreadApproximation::BjerksundStensland, OptionDir::CALL);
+            assert!((kirk - mc).abs() < 0.1);
+            assert!((bs - mc).abs() < 0.1);
+            // Put-call parity on the forwards
+            let put = price(SpreadApproximation::Kirk, OptionDir::PUT);
+            let forward =
+                (-r * t).exp() * (100.0 * (r * t).exp() - 90.0 * ((r - 0.03) * t).exp() - strike);
+            assert!((kirk - put - forward).abs() < 2.0e-3);
+        }
+    }
+
+    #[test]
+    fn negative_second_leg_plus_strike() {
+        // The forward of the second asset is about 50.25, a strike of -60 leaves nothing to take the log of
+        let (spot1, spot2, strike, t, r) = (100.0f32, 50.0, -60.0f32, 0.5f32, 0.02f32);
+        let forward_intrinsic = spot1 - spot2 - strike * (-r * t).exp();
+        for approximation in [
+            SpreadApproximation::Kirk,
+            SpreadApproximation::BjerksundStensland,
+        ] {
+            let price = |dir| {
+                spread_price(
+                    approximation,
+                    dir,
+                    spot1,
+                    spot2,
+                    strike,
+                    t,
+                    r,
+                    0.2,
+                    0.3,
+                    0.5,
+                    0.0,
+                    0.0,
+                )
+            };
+            assert!((price(OptionDir::CALL) - forward_intrinsic).abs() < 1.0e-3);
+            assert!(price(OptionDir::PUT).abs() < 1.0e-3);
+            let batch = spread_call(
+                approximation,
+                &[spot1],
+                &[spot2],
+                &[strike],
+                &[t],
+                &[r],
+                &[0.2],
+                &[0.3],
+                &[0.5],
+                &[0.0],
+                &[0.0],
+            );
+            assert!((batch[0] - forward_intrinsic).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn stulz() {
+        let (t, r, v1, v2, rho) = (1.0, 0.05, 0.3, 0.25, 0.6);
+        let spots = terminal_spots(100_000, t, r, v1, v2, rho);
+        for strike in [80.0, 95.0, 110.0] {
+            let price = |dir, rainbow| {
+                stulz_price(
+                    dir, rainbow, 100.0, 90.0, strike, 1.0, 0.05, 0.3, 0.25, 0.6, 0.0, 0.03,
+                )
+            };
+            // A call on the min and a call on the max are together a call on each asset
+            let vanillas = bs_price(OptionDir::CALL, 100.0, strike, 1.0, 0.05, 0.3, 0.0)
+                + bs_price(OptionDir::CALL, 90.0, strike, 1.0, 0.05, 0.25, 0.03);
+            assert!(
+                (price(OptionDir::CALL, Rainbow::Min) + price(OptionDir::CALL, Rainbow::Max)
+                    - vanillas)
+                    .abs()
+                    < 2.0e-3
+            );
+            let vanillas = bs_price(OptionDir::PUT, 100.0, strike, 1.0, 0.05, 0.3, 0.0)
+                + bs_price(OptionDir::PUT, 90.0, strike, 1.0, 0.05, 0.25, 0.03);
+            assert!(
+                (price(OptionDir::PUT, Rainbow::Min) + price(OptionDir::PUT, Rainbow::Max)
+                    - vanillas)
+                    .abs()
+                    < 2.0e-3
+            );
+
+            let discount = (-r * t).exp() / spots.len() as f64;
+            for (dir, rainbow) in [
+                (OptionDir::CALL, Rainbow::Min),
+                (OptionDir::CALL, Rainbow::Max),
+                (OptionDir::PUT, Rainbow::Min),
+                (OptionDir::PUT, Rainbow::Max),
+            ] {
+                let mc: f64 = spots
+                    .iter()
+                    .map(|&(s1, s2)| {
+                        let s = if rainbow == Rainbow::Min {
+                            s1.min(s2)
+                        } else {
+                            s1.max(s2)
+                        };
+                        if dir == OptionDir::CALL {
+                            (s - strike as f64).max(0.0)
+                        } else {
+                            (strike as f64 - s).max(0.0)
+                        }
+                    })
+                    .sum::<f64>()
+                    * discount;
+                assert!((price(dir, rainbow) as f64 - mc).abs() < 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_matches_scalar() {
+        // Correlations close to 1 and -1. Near 1 with similar volatilities the spread barely moves and the
+        // ratio volatility is a small difference of large terms
+        let spot1 = [100.0, 100.0, 50.0, 100.0, 200.0, 100.0, 100.0, 80.0, 100.0];
+        let spot2 = [100.0, 98.0, 50.0, 90.0, 195.0, 105.0, 100.0, 82.0, 60.0];
+        let strike = [0.0, 2.0, 1.0, 5.0, 0.5, 10.0, 3.0, 0.0, 30.0];
+        let years_to_expiry = [1.0, 0.25, 2.0, 0.5, 0.05, 3.0, 0.75, 1.5, 0.1];
+        let risk_free_rate = [0.03, 0.0, 0.05, 0.02, 0.08, 0.01, 0.04, 0.06, 0.0];
+        let volatility1 = [0.2, 0.25, 0.4, 0.15, 0.3, 0.2, 0.5, 0.35, 0.1];
+        let volatility2 = [0.2, 0.3, 0.35, 0.15, 0.3, 0.25, 0.45, 0.35, 0.12];
+        let correlation = [0.999, 0.99, -0.999, 0.9999, -0.99, 1.0, -1.0, 0.995, -0.95];
+        let dividend_yield1 = [0.0, 0.02, 0.01, 0.0, 0.03, 0.0, 0.02, 0.01, 0.0];
+        let dividend_yield2 = [0.01, 0.0, 0.0, 0.03, 0.02, 0.01, 0.0, 0.0, 0.04];
+        let exchange = margrabe_exchange(
+            &spot1,
+            &spot2,
+            &years_to_expiry,
+            &risk_free_rate,
+            &volatility1,
+            &volatility2,
+            &correlation,
+            &dividend_yield1,
+            &dividend_yield2,
+        );
+        assert_eq!(exchange.len(), spot1.len());
+        for i in 0..spot1.len() {
+            let scalar = margrabe_price(
+                spot1[i],
+                spot2[i],
+                years_to_expiry[i],
+                risk_free_rate[i],
+                volatility1[i],
+                volatility2[i],
+                correlation[i],
+                dividend_yield1[i],
+                dividend_yield2[i],
+            );
+            assert!((exchange[i] - scalar).abs() < 2.0e-3);
+        }
+        for approximation in [
+            SpreadApproximation::Kirk,
+            SpreadApproximation::BjerksundStensland,
+        ] {
+            for dir in [OptionDir::CALL, OptionDir::PUT] {
+                let batch = if dir == OptionDir::CALL {
+                    spread_call
+                } else {
+                    spread_put
+                };
+                let prices = batch(
+                    approximation,
+                    &spot1,
+                    &spot2,
+                    &strike,
+                    &years_to_expiry,
+                    &risk_free_rate,
+                    &volatility1,
+                    &volatility2,
+                    &correlation,
+                    &dividend_yield1,
+                    &dividend_yield2,
+                );
+                for i in 0..spot1.len() {
+                    let scalar = spread_price(
+                        approximation,
+                        dir,
+                        spot1[i],
+                        spot2[i],
+                        strike[i],
+                        years_to_expiry[i],
+                        risk_free_rate[i],
+                        volatility1[i],
+                        volatility2[i],
+                        correlation[i],
+                        dividend_yield1[i],
+                        dividend_yield2[i],
+                    );
+                    assert!((prices[i] - scalar).abs() < 2.0e-3);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Value at risk and expected shortfall of a portfolio over a horizon
+//!
+//! Losses are positive and both measures are given at a confidence level such as 0.99. The underlyings of the
+//! portfolio are the risk factors, ordered alphabetically by name, and returns are relative moves of their spots
+//! over the horizon.
+//!
+//! * Historical simulation replays a history of returns and vol changes through the scenario engine, fully
+//!   revaluing every option with time rolled forward by the horizon. The VaR is the loss at the quantile of the
+//!   scenarios and the expected shortfall the mean loss beyond it
+//! * Delta-gamma-normal approximates the P&L by the net delta, gamma and theta of each underlying and takes it as
+//!   normal with the exact mean and variance of that quadratic under normal returns, whose volatilities are those
+//!   of the market data of each underlying and correlations are given
+//! * Delta-gamma Monte Carlo simulates the same correlated normal returns and measures the quantile of the
+//!   quadratic P&L, which keeps the skew that gamma gives it
+//!
+//! The delta-gamma methods use the net greeks of the portfolio, which come from the vanilla call and put greeks
+//! kernels, so they ignore vol risk and the cross gamma between underlyings.
+use crate::monte_carlo::Rng;
+use crate::normal::{inverse_ncd, npd_f64};
+use crate::portfolio::{Portfolio, PortfolioGreeks};
+use crate::scenario::{scenario_pnl, Scenario};
+use wasm_bindgen::prelude::*;
+
+/// Value at risk and expected shortfall as positive losses
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct RiskMeasure {
+    pub value_at_risk: f32,
+    pub expected_shortfall: f32,
+}
+
+/// Net greeks, spot and volatility of each underlying in alphabetical order
+fn risk_factors(portfolio: &Portfolio) -> Vec<(String, PortfolioGreeks, f32, f32)> {
+    portfolio
+        .greeks_by_underlying()
+        .into_iter()
+        .map(|(name, greeks)| {
+            let market = portfolio.market_data(&name);
+            let spot = market.map_or(f32::NAN, |m| m.spot);
+            let volatility = market.map_or(f32::NAN, |m| m.volatility);
+            (name, greeks, spot, volatility)
+        })
+        .collect()
+}
+
+/// Loss at the quantile of the scenarios and the mean of the losses from there on
+fn tail(pnl: &mut [f32], confidence: f32) -> RiskMeasure {
+    if pnl.is_empty() {
+        return RiskMeasure {
+            value_at_risk: f32::NAN,
+            expected_shortfall: f32::NAN,
+        };
+    }
+    pnl.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // Allowing for the rounding of the confidence in f32
+    let count = (((1.0 - confidence as f64) * pnl.len() as f64 - 1.0e-4).ceil() as usize)
+        .clamp(1, pnl.len());
+    RiskMeasure {
+        value_at_risk: -pnl[count - 1],
+        expected_shortfall: -pnl[..count].iter().map(|&p| p as f64).sum::<f64>() as f32
+            / count as f32,
+    }
+}
+
+/// Lower triangular factor of a correlation matrix, row-major. Directions without variance left are dropped so
+/// that a singular matrix still factors
+fn cholesky(correlation: &[f32], n: usize) -> Vec<f64> {
+    let mut l = vec![0.0f64; n * n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i * n + k] * l[j * n + k]).sum();
+            let c = correlation[i * n + j] as f64 - sum;
+            if i == j {
+                l[i * n + i] = c.max(0.0).sqrt();
+            } else if l[j * n + j] > 0.0 {
+                l[i * n + j] = c / l[j * n + j];
+            }
+        }
+    }
+    l
+}
+
+/// Full revaluation historical VaR and expected shortfall. Returns and vol changes over the horizon are row-major
+/// by scenario, with a column per underlying in alphabetical order. Vol changes may be empty to leave vols unchanged
+/// Horizon should be expressed as a f32 such as 1 day is 1/252 = 0.004
+#[wasm_bindgen]
+pub fn historical_var(
+    portfolio: &Portfolio,
+    returns: &[f32],
+    vol_changes: &[f32],
+    horizon: f32,
+    confidence: f32,
+) -> RiskMeasure {
+    let factors = risk_factors(portfolio);
+    let n = factors.len().max(1);
+    let scenarios = returns.len() / n;
+    let mut pnl = vec![0.0f32; scenarios];
+    for (j, (name, _, _, _)) in factors.iter().enumerate() {
+        let mut single = Portfolio::new();
+        if let Some(market) = portfolio.market_data(name) {
+            single.set_market_data(name, market);
+        }
+        for position in portfolio.positions() {
+            if &position.instrument.underlying == name {
+                single.add(position.clone());
+            }
+        }
+        let shocks: Vec<Scenario> = (0..scenarios)
+            .map(|d| {
+                let vol_change = vol_changes.get(d * n + j).copied().unwrap_or(0.0);
+                Scenario::new(returns[d * n + j], vol_change, 0.0, horizon)
+            })
+            .collect();
+        for (total, p) in pnl.iter_mut().zip(scenario_pnl(&single, &shocks)) {
+            *total += p;
+        }
+    }
+    tail(&mut pnl, confidence)
+}
+
+/// Delta-gamma-normal VaR and expected shortfall, with the correlation of the underlyings in alphabetical order
+/// row-major
+/// Horizon should be expressed as a f32 such as 1 day is 1/252 = 0.004
+#[wasm_bindgen]
+pub fn delta_gamma_normal_var(
+    portfolio: &Portfolio,
+    correlation: &[f32],
+    horizon: f32,
+    confidence: f32,
+) -> RiskMeasure {
+    let factors = risk_factors(portfolio);
+    let n = factors.len();
+    let h = horizon as f64;
+    // Dollar delta and gamma per unit return, and the covariance of the returns
+    let delta: Vec<f64> = factors.iter().map(|f| (f.1.delta * f.2) as f64).collect();
+    let gamma: Vec<f64> = factors
+        .iter()
+        .map(|f| (f.1.gamma * f.2 * f.2) as f64)
+        .collect();
+    let sd: Vec<f64> = factors.iter().map(|f| f.3 as f64 * h.sqrt()).collect();
+    let covariance = |i: usize, j: usize| correlation[i * n + j] as f64 * sd[i] * sd[j];
+    let theta: f64 = factors.iter().map(|f| f.1.theta as f64).sum();
+    let mut mean = theta * h;
+    let mut variance = 0.0;
+    for i in 0..n {
+        mean += gamma[i] * covariance(i, i) / 2.0;
+        for j in 0..n {
+            let c = covariance(i, j);
+            variance += delta[i] * delta[j] * c + gamma[i] * gamma[j] * c * c / 2.0;
+        }
+    }
+    let (z, sd) = (inverse_ncd(confidence as f64), variance.sqrt());
+    RiskMeasure {
+        value_at_risk: (z * sd - mean) as f32,
+        expected_shortfall: (sd * npd_f64(z) / (1.0 - confidence as f64) - mean) as f32,
+    }
+}
+
+/// Delta-gamma Monte Carlo VaR and expected shortfall, with the correlation of the underlyings in alphabetical
+/// order row-major
+/// Horizon should be expressed as a f32 such as 1 day is 1/252 = 0.004
+#[wasm_bindgen]
+pub fn delta_gamma_monte_carlo_var(
+    portfolio: &Portfolio,
+    correlation: &[f32],
+    horizon: f32,
+    confidence: f32,
+    paths: usize,
+    seed: u64,
+) -> RiskMeasure {
+    let factors = risk_factors(portfolio);
+    let n = factors.len();
+    let h = horizon as f64;
+    let l = cholesky(correlation, n);
+    let theta: f64 = factors.iter().map(|f| f.1.theta as f64).sum();
+    let mut rng = Rng::new(seed);
+    let (mut z, mut pnl) = (vec![0.0f64; n], Vec::with_capacity(paths));
+    for _ in 0..paths {
+        z.iter_mut().for_each(|z| *z = inverse_ncd(rng.uniform()));
+        let mut p = theta * h;
+        for (i, (_, greeks, spot, volatility)) in factors.iter().enumerate() {
+            let x: f64 = (0..=i).map(|k| l[i * n + k] * z[k]).sum();
+            let ret = *volatility as f64 * h.sqrt() * x;
+            let (delta, gamma) = (
+                (greeks.delta * spot) as f64,
+                (greeks.gamma * spot * spot) as f64,
+            );
+            p += delta * ret + gamma * ret * ret / 2.0;
+        }
+        pnl.push(p as f32);
+    }
+    tail(&mut pnl, confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{InstrumentType, MarketData};
+
+    #[test]
+    fn quantile_and_shortfall() {
+        let mut pnl: Vec<f32> = (1..=100).rev().map(|i| -(i as f32)).collect();
+        let risk = tail(&mut pnl, 0.95);
+        assert_eq!(risk.value_at_risk, 96.0);
+        assert_eq!(risk.expected_shortfall, 98.0);
+        assert!(tail(&mut [], 0.99).value_at_risk.is_nan());
+    }
+
+    #[test]
+    fn linear_portfolio() {
+        // Stock only, where the delta-gamma methods are exact for returns
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(100.0, 0.03, 0.2, 0.0));
+        portfolio.set_market_data("XYZ", MarketData::new(50.0, 0.03, 0.4, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Underlying, 0.0, 0.0, 1000.0, 1.0);
+        portfolio.add_position("XYZ", InstrumentType::Underlying, 0.0, 0.0, -500.0, 1.0);
+        let (rho, h, c) = (0.6f64, 1.0f32 / 252.0, 0.99f32);
+        let correlation = [1.0, rho as f32, rho as f32, 1.0];
+        let (a, b) = (1.0e5 * 0.2, -2.5e4 * 0.4);
+        let sd = ((a * a + b * b + 2.0 * rho * a * b) * h as f64).sqrt();
+        let z = inverse_ncd(c as f64);
+        let normal = delta_gamma_normal_var(&portfolio, &correlation, h, c);
+        assert!((normal.value_at_risk as f64 - z * sd).abs() < 1.0e-3 * z * sd);
+        let es = sd * npd_f64(z) / (1.0 - c as f64);
+        assert!((normal.expected_shortfall as f64 - es).abs() < 1.0e-3 * es);
+
+        let mc = delta_gamma_monte_carlo_var(&portfolio, &correlation, h, c, 200_000, 7);
+        assert!((mc.value_at_risk - normal.value_at_risk).abs() < 0.02 * normal.value_at_risk);
+        assert!(
+            (mc.expected_shortfall - normal.expected_shortfall).abs()
+                < 0.03 * normal.expected_shortfall
+        );
+    }
+
+    #[test]
+    fn option_portfolio() {
+        let (s, v, h, c) = (100.0f32, 0.3f32, 1.0f32 / 252.0, 0.99f32);
+        let mut portfolio = Portfolio::new();
+        portfolio.set_market_data("ABC", MarketData::new(s, 0.03, v, 0.0));
+        portfolio.add_position("ABC", InstrumentType::Call, 100.0, 0.1, -50.0, 100.0);
+        portfolio.add_position("ABC", InstrumentType::Put, 95.0, 0.25, -50.0, 100.0);
+        // A history of normal returns and no vol changes
+        let mut rng = Rng::new(11);
+        let returns: Vec<f32> = (0..50_000)
+            .map(|_| (v * h.sqrt()) * inverse_ncd(rng.uniform()) as f32)
+            .collect();
+        let historical = historical_var(&portfolio, &returns, &[], h, c);
+        let mc = delta_gamma_monte_carlo_var(&portfolio, &[1.0], h, c, 50_000, 3);
+        let normal = delta_gamma_normal_var(&portfolio, &[1.0], h, c);
+        let gamma = risk_factors(&portfolio)[0].1.gamma;
+        let fd_gamma = {
+            let pv = |spot: f32| {
+                let mut shifted = Portfolio::new();
+                shifted.set_market_data("ABC", MarketData::new(spot, 0.03, v, 0.0));
+                for position in portfolio.positions() {
+                    shifted.add(position.clone());
+                }
+                shifted.net_greeks().pv as f64
+            };
+            (pv(s + 1.0) - 2.0 * pv(s) + pv(s - 1.0)) as f32
+        };
+        assert!(gamma < 0.0);
+        assert!((gamma - fd_gamma).abs() < 0.02 * gamma.abs());
+        // Short gamma fattens the loss tail beyond the normal approximation
+        assert!(mc.value_at_risk > normal.value_at_risk);
+        assert!((historical.value_at_risk - mc.value_at_risk).abs() < 0.05 * mc.value_at_risk);
+        assert!(
+            (historical.expected_shortfall - mc.expected_shortfall).abs()
+                < 0.05 * mc.expected_shortfall
+        );
+        assert!(historical.expected_shortfall > historical.value_at_risk);
+
+        // Vol changes reprice the options
+        let up: Vec<f32> = vec![0.05; returns.len()];
+        let vol_up = historical_var(&portfolio, &returns, &up, h, c);
+        assert!(vol_up.value_at_risk > historical.value_at_risk);
+    }
+}
@@ -0,0 +1,396 @@
+//! Variance and volatility swap fair strikes
+//!
+//! * The variance swap strike by static replication of the log contract (Carr and Madan 1998), a strip of out of
+//!   the money puts below the forward and calls above weighted by 1 / K^2. The strip is discretised the way the
+//!   CBOE does, with the at the money strike K0 the first below the forward and a correction for the forward
+//!   falling between strikes
+//! * A VIX style index following the CBOE white paper, from bid and ask quotes of the two expiries around 30 days.
+//!   Each expiry's forward comes from put call parity at the strike where the call and put mids are closest, out
+//!   of the money options with zero bids are dropped and the strip stops after two consecutive zero bids
+//! * Volatility swap strikes, which are below the square root of the variance strike by a convexity adjustment of
+//!   Var(V) / (8 K_var^(3/2)) (Brockhaus and Long 2000), the variance of realised variance V coming from the caller
+//!   or from the Heston model
+use crate::bs::{bs_call, bs_put};
+use crate::heston::HestonParams;
+use wasm_bindgen::prelude::*;
+
+/// Thirty days, the horizon of the VIX
+const VIX_HORIZON: f64 = 30.0 / 365.0;
+
+/// Bid and ask quotes for the options of one expiry, with the strikes ascending
+#[derive(PartialEq, Debug, Clone)]
+pub struct OptionQuotes {
+    pub years_to_expiry: f32,
+    pub risk_free_rate: f32,
+    pub strikes: Vec<f32>,
+    pub call_bid: Vec<f32>,
+    pub call_ask: Vec<f32>,
+    pub put_bid: Vec<f32>,
+    pub put_ask: Vec<f32>,
+}
+
+/// Annualised variance from a strip of (strike, out of the money price) pairs with ascending strikes, where the
+/// price at `k0` is the average of the call and the put
+fn replicate(strip: &[(f64, f64)], forward: f64, k0: f64, t: f64, r: f64) -> f64 {
+    let n = strip.len();
+    let sum: f64 = (0..n)
+        .map(|i| {
+            let (k, price) = strip[i];
+            let dk = match (i, n) {
+                (_, 1) => 0.0,
+                (0, _) => strip[1].0 - k,
+                (i, n) if i == n - 1 => k - strip[i - 1].0,
+                _ => (strip[i + 1].0 - strip[i - 1].0) / 2.0,
+            };
+            dk / (k * k) * price
+        })
+        .sum();
+    2.0 * (r * t).exp() * sum / t - (forward / k0 - 1.0).powi(2) / t
+}
+
+/// The strike at or below the forward, or the lowest strike when they are all above
+fn at_the_money(strikes: &[f32], forward: f64) -> usize {
+    strikes
+        .iter()
+        .rposition(|&k| k as f64 <= forward)
+        .unwrap_or(0)
+}
+
+/// Fair variance swap strike, as an annualised variance, by replication from call and put prices at ascending
+/// strikes. Puts are used below the forward and calls above. NaN without strikes
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn variance_swap_strike(
+    forward: f32,
+    strikes: &[f32],
+    call_prices: &[f32],
+    put_prices: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+) -> f32 {
+    if strikes.is_empty() {
+        return f32::NAN;
+    }
+    let forward = forward as f64;
+    let atm = at_the_money(strikes, forward);
+    let strip: Vec<(f64, f64)> = strikes
+        .iter()
+        .enumerate()
+        .map(|(i, &k)| {
+            let (call, put) = (call_prices[i] as f64, put_prices[i] as f64);
+            let price = match i.cmp(&atm) {
+                std::cmp::Ordering::Less => put,
+                std::cmp::Ordering::Equal => (call + put) / 2.0,
+                std::cmp::Ordering::Greater => call,
+            };
+            (k as f64, price)
+        })
+        .collect();
+    replicate(
+        &strip,
+        forward,
+        strikes[atm] as f64,
+        years_to_expiry as f64,
+        risk_free_rate as f64,
+    ) as f32
+}
+
+/// Fair variance swap strike, as an annualised variance, from a smile of Black Scholes volatilities at ascending
+/// strikes
+/// Years to expiry should be expressed as a f32 such as 20 days is 20/252 = 0.79
+/// Risk free rate, volatilities and dividend yield expressed as f32 with 1.0 = 100%. 0.2 = 20% etc
+#[wasm_bindgen]
+pub fn smile_variance_swap_strike(
+    spot: f32,
+    strikes: &[f32],
+    years_to_expiry: f32,
+    risk_free_rate: f32,
+    volatilities: &[f32],
+    dividend_yield: f32,
+) -> f32 {
+    let n = strikes.len();
+    let spots = vec![spot; n];
+    let t = vec![years_to_expiry; n];
+    let r = vec![risk_free_rate; n];
+    let q = vec![dividend_yield; n];
+    let calls = bs_call(&spots, strikes, &t, &r, volatilities, &q);
+    let puts = bs_put(&spots, strikes, &t, &r, volatilities, &q);
+    let forward = spot * ((risk_free_rate - dividend_yield) * years_to_expiry).exp();
+    variance_swap_strike(
+        forward,
+        strikes,
+        &calls[..n],
+        &puts[..n],
+        years_to_expiry,
+        risk_free_rate,
+    )
+}
+
+/// Out of the money options in strike order away from the money, stopping after two consecutive zero bids
+fn otm_strip(strikes: impl Iterator<Item = (f64, f64, f64)>) -> Vec<(f64, f64)> {
+    let mut strip = Vec::new();
+    let mut zero_bids = 0;
+    for (k, bid, ask) in strikes {
+        if bid <= 0.0 {
+            zero_bids += 1;
+            if zero_bids == 2 {
+                break;
+            }
+            continue;
+        }
+        zero_bids = 0;
+        strip.push((k, (bid + ask) / 2.0));
+    }
+    strip
+}
+
+/// Annualised variance of one expiry by the CBOE methodology, NaN without strikes
+pub fn cboe_variance(quotes: &OptionQuotes) -> f32 {
+    if quotes.strikes.is_empty() {
+        return f32::NAN;
+    }
+    let (t, r) = (quotes.years_to_expiry as f64, quotes.risk_free_rate as f64);
+    let mid = |bid: &[f32], ask: &[f32], i: usize| (bid[i] as f64 + ask[i] as f64) / 2.0;
+    let call = |i| mid(&quotes.call_bid, &quotes.call_ask, i);
+    let put = |i| mid(&quotes.put_bid, &quotes.put_ask, i);
+    let strikes = &quotes.strikes;
+    // Forward from parity where the call and the put are closest
+    let closest = (0..strikes.len())
+        .min_by(|&i, &j| {
+            (call(i) - put(i))
+                .abs()
+                .partial_cmp(&(call(j) - put(j)).abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0);
+    let forward = strikes[closest] as f64 + (r * t).exp() * (call(closest) - put(closest));
+    let atm = at_the_money(strikes, forward);
+
+    let below = (0..atm).rev().map(|i| {
+        (
+            strikes[i] as f64,
+            quotes.put_bid[i] as f64,
+            quotes.put_ask[i] as f64,
+        )
+    });
+    let above = (atm + 1..strikes.len()).map(|i| {
+        (
+            strikes[i] as f64,
+            quotes.call_bid[i] as f64,
+            quotes.call_ask[i] as f64,
+        )
+    });
+    let mut strip = otm_strip(below);
+    strip.reverse();
+    strip.push((strikes[atm] as f64, (call(atm) + put(atm)) / 2.0));
+    strip.extend(otm_strip(above));
+    replicate(&strip, forward, strikes[atm] as f64, t, r) as f32
+}
+
+/// VIX style volatility index, in vol points, interpolating the variances of the expiries either side of 30 days.
+/// NaN when the two expiries are the same
+pub fn vix_index(near: &OptionQuotes, next: &OptionQuotes) -> f32 {
+    let (t1, t2) = (near.years_to_expiry as f64, next.years_to_expiry as f64);
+    if t1 == t2 {
+        return f32::NAN;
+    }
+    let w1 = (t2 - VIX_HORIZON) / (t2 - t1);
+    let total = t1 * cboe_variance(near) as f64 * w1 + t2 * cboe_variance(next) as f64 * (1.0 - w1);
+    (100.0 * (total / VIX_HORIZON).sqrt()) as f32
+}
+
+/// Volatility swap strike from the variance swap strike and the variance of realised variance, both annualised
+#[wasm_bindgen]
+pub fn vol_swap_strike(variance_strike: f32, variance_of_variance: f32) -> f32 {
+    let k = variance_strike as f64;
+    (k.sqrt() - variance_of_variance as f64 / (8.0 * k.powf(1.5))) as f32
+}
+
+/// Mean and variance of the realised variance over `years_to_expiry` in the Heston model
+fn heston_realised_variance(params: &HestonParams, years_to_expiry: f64) -> (f64, f64) {
+    let (v0, kappa, theta, sigma) = (
+        params.v0 as f64,
+        params.kappa as f64,
+        params.theta as f64,
+        params.sigma as f64,
+    );
+    let t = years_to_expiry;
+    let decay = (-kappa * t).exp();
+    let mean = theta + (v0 - theta) * (1.0 - decay) / (kappa * t);
+    // Var(v_s) = c0 + c1 exp(-kappa s) + c2 exp(-2 kappa s), integrated against the covariance with the
+    // rest of the period (1 - exp(-kappa (t - s))) / kappa
+    let a = sigma * sigma / kappa;
+    let c = [a * theta / 2.0, a * (v0 - theta), a * (theta / 2.0 - v0)];
+    let integral = |m: f64| {
+        if m == 0.0 {
+            t
+        } else {
+            (1.0 - (-m * kappa * t).exp()) / (m * kappa)
+        }
+    };
+    let variance: f64 = c
+        .iter()
+        .enumerate()
+        .map(|(m, &c)| c * (integral(m as f64) - decay * integral(m as f64 - 1.0)))
+        .sum::<f64>()
+        * 2.0
+        / kappa;
+    (mean, variance / (t * t))
+}
+
+/// Variance and volatility swap strikes in the Heston model, the volatility swap with the convexity adjustment
+pub fn heston_swap_strikes(params: &HestonParams, years_to_expiry: f32) -> (f32, f32) {
+    let (mean, variance) = heston_realised_variance(params, years_to_expiry as f64);
+    (mean as f32, vol_swap_strike(mean as f32, variance as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bs::OptionDir;
+    use crate::bs_single::bs_price;
+    use crate::monte_carlo::Rng;
+    use crate::normal::inverse_ncd;
+
+    fn strikes(low: f32, high: f32, step: f32) -> Vec<f32> {
+        (0..)
+            .map(|i| low + step * i as f32)
+            .take_while(|&k| k <= high)
+            .collect()
+    }
+
+    #[test]
+    fn flat_and_skewed_smiles() {
+        // With no smile the variance strike is the implied variance
+        let k = strikes(20.0, 400.0, 1.0);
+        let flat = vec![0.25; k.len()];
+        let variance = smile_variance_swap_strike(100.0, &k, 0.5, 0.03, &flat, 0.01);
+        assert!((variance - 0.0625).abs() < 5.0e-4);
+        // Prices straight from quotes agree
+        let n = k.len();
+        let calls: Vec<f32> = k
+            .iter()
+            .map(|&k| bs_price(OptionDir::CALL, 100.0, k, 0.5, 0.03, 0.25, 0.01))
+            .collect();
+        let puts: Vec<f32> = k
+            .iter()
+            .map(|&k| bs_price(OptionDir::PUT, 100.0, k, 0.5, 0.03, 0.25, 0.01))
+            .collect();
+        let forward = 100.0 * (0.02f32 * 0.5).exp();
+        let quoted = variance_swap_strike(forward, &k, &calls, &puts, 0.5, 0.03);
+        assert!((quoted - variance).abs() < 1.0e-4);
+        assert_eq!(calls.len(), n);
+
+        // A downward skew puts more weight on the higher put vols, so the strike is above the at the money variance
+        let skew: Vec<f32> = k.iter().map(|&k| 0.25 - 0.1 * (k / 100.0).ln()).collect();
+        let skewed = smile_variance_swap_strike(100.0, &k, 0.5, 0.03, &skew, 0.01);
+        assert!(skewed > 0.0625 + 1.0e-3);
+    }
+
+    /// Quotes from a flat vol with a spread around the Black Scholes price, and far out of the money strikes
+    /// quoted with zero bids
+    fn quotes(t: f32, v: f32) -> OptionQuotes {
+        let (s, r) = (100.0, 0.02);
+        let strikes = strikes(30.0, 250.0, 1.0);
+        let quote = |dir, k| {
+            let price = bs_price(dir, s, k, t, r, v, 0.0);
+            if price < 0.01 {
+                (0.0, 0.05)
+            } else {
+                (0.99 * price, 1.01 * price)
+            }
+        };
+        let (call_bid, call_ask) = strikes.iter().map(|&k| quote(OptionDir::CALL, k)).unzip();
+        let (put_bid, put_ask) = strikes.iter().map(|&k| quote(OptionDir::PUT, k)).unzip();
+        OptionQuotes {
+            years_to_expiry: t,
+            risk_free_rate: r,
+            strikes,
+            call_bid,
+            call_ask,
+            put_bid,
+            put_ask,
+        }
+    }
+
+    #[test]
+    fn vix() {
+        let near = quotes(23.0 / 365.0, 0.18);
+        let next = quotes(37.0 / 365.0, 0.18);
+        assert!((cboe_variance(&near) - 0.0324).abs() < 1.0e-3);
+        assert!((vix_index(&near, &next) - 18.0).abs() < 0.2);
+
+        // Interpolating in total variance between different vols
+        let next = quotes(37.0 / 365.0, 0.22);
+        let expected =
+            ((23.0 * 0.0324 * 7.0 + 37.0 * 0.0484 * 7.0) / (14.0 * 30.0f32)).sqrt() * 100.0;
+        assert!((vix_index(&near, &next) - expected).abs() < 0.2);
+
+        // A quote beyond two zero bids is not part of the strip
+        let mut far = near.clone();
+        let last = far.strikes.len() - 1;
+        far.call_bid[last] = 1.0;
+        far.call_ask[last] = 1.2;
+        assert_eq!(cboe_variance(&far), cboe_variance(&near));
+
+        // Degenerate inputs
+        assert!(vix_index(&near, &near).is_nan());
+        let empty = OptionQuotes {
+            strikes: vec![],
+            call_bid: vec![],
+            call_ask: vec![],
+            put_bid: vec![],
+            put_ask: vec![],
+            ..near
+        };
+        assert!(cboe_variance(&empty).is_nan());
+        assert!(variance_swap_strike(100.0, &[], &[], &[], 0.5, 0.03).is_nan());
+    }
+
+    #[test]
+    fn heston_vol_swap() {
+        let params = HestonParams {
+            v0: 0.05,
+            kappa: 2.0,
+            theta: 0.04,
+            sigma: 0.4,
+            rho: -0.7,
+        };
+        let t = 1.0;
+        // Integrated variance by simulation of the variance process with full truncation
+        let (paths, steps) = (20_000, 250);
+        let dt = t / steps as f64;
+        let mut rng = Rng::new(17);
+        let realised: Vec<f64> = (0..paths)
+            .map(|_| {
+                let mut v = params.v0 as f64;
+                let mut integral = 0.0;
+                for _ in 0..steps {
+                    let z = inverse_ncd(rng.uniform());
+                    let positive = v.max(0.0);
+                    let next = v
+                        + params.kappa as f64 * (params.theta as f64 - positive) * dt
+                        + params.sigma as f64 * (positive * dt).sqrt() * z;
+                    integral += (positive + next.max(0.0)) / 2.0 * dt;
+                    v = next;
+                }
+                integral / t
+            })
+            .collect();
+        let mc_mean = realised.iter().sum::<f64>() / paths as f64;
+        let mc_variance =
+            realised.iter().map(|x| (x - mc_mean).powi(2)).sum::<f64>() / (paths - 1) as f64;
+        let mc_vol = realised.iter().map(|x| x.sqrt()).sum::<f64>() / paths as f64;
+
+        let (mean, variance) = heston_realised_variance(&params, t);
+        assert!((mean - mc_mean).abs() < 5.0e-4);
+        assert!((variance - mc_variance).abs() < 0.05 * variance);
+        let (variance_strike, vol_strike) = heston_swap_strikes(&params, t as f32);
+        assert!((variance_strike as f64 - mean).abs() < 1.0e-6);
+        assert!(vol_strike < variance_strike.sqrt());
+        assert!((vol_strike as f64 - mc_vol).abs() < 2.0e-3);
+        // No vol of vol, no adjustment
+        assert_eq!(vol_swap_strike(0.04, 0.0), 0.2);
+    }
+}